@@ -26,6 +26,7 @@
             wayland-protocols
             mesa
             vulkan-loader
+            alsa-lib
           ];
 
       in {
@@ -36,7 +37,7 @@
 
           cargoLock = { lockFile = ./Cargo.lock; };
 
-          nativeBuildInputs = [ ];
+          nativeBuildInputs = with pkgs; [ pkg-config ];
           buildInputs = rpathLibs;
 
           postFixup = with pkgs; ''
@@ -48,6 +49,7 @@
 
         devShells.default = pkgs.mkShell {
           LD_LIBRARY_PATH = pkgs.lib.makeLibraryPath rpathLibs;
+          nativeBuildInputs = with pkgs; [ pkg-config ];
           buildInputs = [ ] ++ rpathLibs;
 
         };
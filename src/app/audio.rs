@@ -0,0 +1,115 @@
+use std::fmt::Debug;
+
+use cpal::{
+    BuildStreamError, DefaultStreamConfigError, FromSample, PlayStreamError, SampleFormat,
+    SizedSample, Stream, StreamConfig,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+use thiserror::Error;
+
+use crate::game_boy::{RingBufferReader, RingBufferSink, Sample};
+
+/// Latency of the queue between the emulation and the audio device
+const BUFFERED_SECONDS: f32 = 0.1;
+
+/// Stream playing the samples pushed into a [`RingBufferSink`] on the default output device
+pub struct AudioOutput {
+    /// Playback stops when the stream is dropped
+    _stream: Stream,
+    sink: RingBufferSink,
+    sample_rate: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum AudioError {
+    #[error("no audio output device")]
+    NoDevice,
+    #[error("unsupported sample format {0}")]
+    UnsupportedFormat(SampleFormat),
+    #[error(transparent)]
+    Config(#[from] DefaultStreamConfigError),
+    #[error(transparent)]
+    Build(#[from] BuildStreamError),
+    #[error(transparent)]
+    Play(#[from] PlayStreamError),
+}
+
+impl Debug for AudioOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioOutput")
+            .field("sink", &self.sink)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AudioOutput {
+    /// Open the default output device at its preferred sample rate
+    pub fn open() -> Result<Self, AudioError> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or(AudioError::NoDevice)?;
+        let supported = device.default_output_config()?;
+        let config = supported.config();
+        let sample_rate = config.sample_rate.0;
+        let capacity = (sample_rate as f32 * BUFFERED_SECONDS) as usize;
+        let sink = RingBufferSink::new(capacity);
+        let reader = sink.reader();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, reader)?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, reader)?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, reader)?,
+            format => return Err(AudioError::UnsupportedFormat(format)),
+        };
+        stream.play()?;
+        log::info!(
+            "audio output at {sample_rate} Hz, {} channels",
+            config.channels
+        );
+        Ok(Self {
+            _stream: stream,
+            sink,
+            sample_rate,
+        })
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Queue the emulation pushes its samples into
+    pub fn sink(&self) -> RingBufferSink {
+        self.sink.clone()
+    }
+}
+
+/// Play stereo samples on the first two channels, the other channels stay silent
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    reader: RingBufferReader,
+) -> Result<Stream, BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let mut samples: Vec<Sample> = Vec::new();
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            samples.resize(data.len() / channels, [0.0; 2]);
+            reader.fill(&mut samples);
+            for (frame, [left, right]) in data.chunks_exact_mut(channels).zip(&samples) {
+                for (channel, out) in frame.iter_mut().enumerate() {
+                    let value = match (channel, channels) {
+                        (_, 1) => (left + right) / 2.0,
+                        (0, _) => *left,
+                        (1, _) => *right,
+                        _ => 0.0,
+                    };
+                    *out = T::from_sample(value);
+                }
+            }
+        },
+        |err| log::error!("audio stream error: {err}"),
+        None,
+    )
+}
@@ -0,0 +1,114 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+use crate::game_boy::SyncMode;
+
+const USAGE: &str = "usage: cvgb [ROM [--printer DIRECTORY] [--camera PNG]]
+       cvgb --extract-photos SAVE DIRECTORY
+       cvgb --link ROM1 ROM2
+       cvgb --host [ADDRESS:]PORT ROM [--lockstep]
+       cvgb --join ADDRESS:PORT ROM [--lockstep]";
+
+/// What to run, from the command line arguments
+#[derive(Debug, Default)]
+pub enum Launch {
+    /// Start without a game
+    #[default]
+    Empty,
+    Single {
+        rom: PathBuf,
+        /// Plug a Game Boy Printer saving into this directory
+        printer: Option<PathBuf>,
+        /// Grayscale picture shown to the Game Boy Camera instead of a test pattern
+        camera: Option<PathBuf>,
+    },
+    /// Save the photos in a Game Boy Camera save file as PNGs, without opening a window
+    ExtractPhotos { save: PathBuf, directory: PathBuf },
+    /// Two games in one process connected by a link cable
+    Linked(PathBuf, PathBuf),
+    /// Wait for another process to connect a link cable over TCP
+    Host {
+        /// Only the local machine can connect unless another address is given
+        address: SocketAddr,
+        rom: PathBuf,
+        mode: SyncMode,
+    },
+    /// Connect a link cable to a process hosting over TCP
+    Join {
+        address: String,
+        rom: PathBuf,
+        mode: SyncMode,
+    },
+}
+
+impl Launch {
+    /// Parse the arguments following the program name
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args: Vec<String> = args.into_iter().collect();
+        let lockstep = args.iter().any(|arg| arg == "--lockstep");
+        args.retain(|arg| arg != "--lockstep");
+        let printer = take_option(&mut args, "--printer")?;
+        let camera = take_option(&mut args, "--camera")?;
+        let mode = if lockstep {
+            SyncMode::Lockstep
+        } else {
+            SyncMode::Speculative
+        };
+        match args.as_slice() {
+            // The printer and camera only go with a single game
+            _ if (printer.is_some() || camera.is_some()) && args.len() != 1 => {
+                Err(USAGE.to_owned())
+            }
+            [flag, address, rom] if flag == "--host" => Ok(Self::Host {
+                address: host_address(address)?,
+                rom: rom.into(),
+                mode,
+            }),
+            [flag, address, rom] if flag == "--join" => Ok(Self::Join {
+                address: address.clone(),
+                rom: rom.into(),
+                mode,
+            }),
+            _ if lockstep => Err(USAGE.to_owned()),
+            [] => Ok(Self::Empty),
+            [flag, first, second] if flag == "--link" => {
+                Ok(Self::Linked(first.into(), second.into()))
+            }
+            [flag, save, directory] if flag == "--extract-photos" => Ok(Self::ExtractPhotos {
+                save: save.into(),
+                directory: directory.into(),
+            }),
+            [rom] if !rom.starts_with("--") => Ok(Self::Single {
+                rom: rom.into(),
+                printer,
+                camera,
+            }),
+            _ => Err(USAGE.to_owned()),
+        }
+    }
+}
+
+/// `[ADDRESS:]PORT` to listen on, on the loopback interface if no address is given
+fn host_address(arg: &str) -> Result<SocketAddr, String> {
+    match arg.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+        Err(_) => arg
+            .parse()
+            .map_err(|_| format!("invalid address {arg}\n{USAGE}")),
+    }
+}
+
+/// Remove `flag` and the value following it from `args`
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<PathBuf>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(PathBuf::from(value)))
+        }
+        Some(_) => Err(USAGE.to_owned()),
+        None => Ok(None),
+    }
+}
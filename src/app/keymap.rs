@@ -0,0 +1,75 @@
+use winit::keyboard::KeyCode;
+
+use crate::game_boy::{Input, Tilt};
+
+/// Which keyboard keys press which Game Boy buttons
+#[derive(Debug, Clone, Copy)]
+pub struct KeyMapping {
+    keys: [(KeyCode, Input); 8],
+}
+
+impl KeyMapping {
+    /// Arrows, X/Z for A/B and A/S for Select/Start
+    pub const SINGLE: Self = Self {
+        keys: [
+            (KeyCode::ArrowRight, Input::RIGHT),
+            (KeyCode::ArrowLeft, Input::LEFT),
+            (KeyCode::ArrowUp, Input::UP),
+            (KeyCode::ArrowDown, Input::DOWN),
+            (KeyCode::KeyX, Input::A),
+            (KeyCode::KeyZ, Input::B),
+            (KeyCode::KeyA, Input::SELECT),
+            (KeyCode::KeyS, Input::START),
+        ],
+    };
+    /// Left side of the keyboard: WASD, G/F for A/B and Q/E for Select/Start
+    pub const PLAYER_ONE: Self = Self {
+        keys: [
+            (KeyCode::KeyD, Input::RIGHT),
+            (KeyCode::KeyA, Input::LEFT),
+            (KeyCode::KeyW, Input::UP),
+            (KeyCode::KeyS, Input::DOWN),
+            (KeyCode::KeyG, Input::A),
+            (KeyCode::KeyF, Input::B),
+            (KeyCode::KeyQ, Input::SELECT),
+            (KeyCode::KeyE, Input::START),
+        ],
+    };
+    /// Right side of the keyboard: arrows, Period/Comma for A/B
+    /// and Right Shift/Enter for Select/Start
+    pub const PLAYER_TWO: Self = Self {
+        keys: [
+            (KeyCode::ArrowRight, Input::RIGHT),
+            (KeyCode::ArrowLeft, Input::LEFT),
+            (KeyCode::ArrowUp, Input::UP),
+            (KeyCode::ArrowDown, Input::DOWN),
+            (KeyCode::Period, Input::A),
+            (KeyCode::Comma, Input::B),
+            (KeyCode::ShiftRight, Input::SELECT),
+            (KeyCode::Enter, Input::START),
+        ],
+    };
+
+    pub fn input(&self, code: KeyCode) -> Option<Input> {
+        self.keys
+            .iter()
+            .find(|(key, _)| *key == code)
+            .map(|(_, input)| *input)
+    }
+}
+
+/// I/J/K/L tilt the console fully up/left/down/right while held
+const TILT_KEYS: [(KeyCode, Tilt); 4] = [
+    (KeyCode::KeyI, Tilt { x: 0.0, y: -1.0 }),
+    (KeyCode::KeyJ, Tilt { x: -1.0, y: 0.0 }),
+    (KeyCode::KeyK, Tilt { x: 0.0, y: 1.0 }),
+    (KeyCode::KeyL, Tilt { x: 1.0, y: 0.0 }),
+];
+
+/// Direction tilted by `code`, if it is a tilt key
+pub fn tilt_direction(code: KeyCode) -> Option<Tilt> {
+    TILT_KEYS
+        .iter()
+        .find(|(key, _)| *key == code)
+        .map(|(_, tilt)| *tilt)
+}
@@ -1,12 +1,19 @@
+#[cfg(feature = "audio")]
+mod audio;
+mod cli;
 mod config;
 mod game_renderer;
 mod gui_renderer;
+mod keymap;
 mod renderer;
 mod state;
 mod timing;
 mod ui;
 mod windows;
 
+#[cfg(feature = "audio")]
+use audio::AudioOutput;
+pub use cli::Launch;
 pub use config::Config;
 use renderer::RenderState;
 use windows::AppScreen;
@@ -24,6 +31,9 @@ pub struct CvgbApp {
     renderer_state: Option<RenderState>,
     timing: FrameTiming,
     state: AppState,
+    /// Kept alive for the stream to keep playing
+    #[cfg(feature = "audio")]
+    audio: Option<AudioOutput>,
 }
 
 impl Default for CvgbApp {
@@ -35,11 +45,28 @@ impl Default for CvgbApp {
             timing: FrameTiming::new(frame_duration),
             // TODO: Load config from file
             state: AppState::default(),
+            #[cfg(feature = "audio")]
+            audio: None,
         }
     }
 }
 
 impl CvgbApp {
+    pub fn new(launch: &Launch) -> Self {
+        let mut app = Self::default();
+        if let Err(err) = app.state.launch(launch) {
+            log::error!("Could not load {launch:?}: {err}");
+        }
+        #[cfg(feature = "audio")]
+        match AudioOutput::open() {
+            Ok(audio) => {
+                app.state.set_audio_sink(audio.sink(), audio.sample_rate());
+                app.audio = Some(audio);
+            }
+            Err(err) => log::warn!("Audio disabled: {err}"),
+        }
+        app
+    }
     fn toggle_screen(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
@@ -85,10 +112,17 @@ impl ApplicationHandler for CvgbApp {
         let state = pollster::block_on(RenderState::new());
         self.renderer_state = Some(state);
         self.toggle_screen(event_loop, AppScreen::MainScreen);
+        if self.state.is_linked() {
+            self.toggle_screen(event_loop, AppScreen::LinkScreen);
+        }
 
         self.request_redraw();
     }
 
+    fn exiting(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.state.write_saves();
+    }
+
     fn new_events(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
@@ -150,6 +184,7 @@ impl ApplicationHandler for CvgbApp {
                 {
                     return;
                 }
+                self.state.run_frame();
                 render_state.render(&mut self.state);
                 event_loop.set_control_flow(winit::event_loop::ControlFlow::WaitUntil(
                     self.timing.next_frame_start_time(),
@@ -160,15 +195,25 @@ impl ApplicationHandler for CvgbApp {
                 // always followed by a redraw event
                 render_state.resize(window_id, size);
             }
+            WindowEvent::CursorMoved { position, .. }
+                if self
+                    .state
+                    .window_registry
+                    .get_screen(window_id)
+                    .is_some_and(|app_window| app_window.is_main()) =>
+            {
+                let size = render_state.get_window(window_id).inner_size();
+                self.state
+                    .handle_cursor_moved((position.x, position.y), (size.width, size.height));
+            }
             WindowEvent::KeyboardInput { event, .. } => {
                 self.state.handle_key_event(&event);
-                if let winit::keyboard::PhysicalKey::Code(code) = event.physical_key {
-                    if code == winit::keyboard::KeyCode::F3
-                        && event.state.is_pressed()
-                        && !event.repeat
-                    {
-                        self.toggle_screen(event_loop, AppScreen::OptionsScreen);
-                    }
+                if let winit::keyboard::PhysicalKey::Code(code) = event.physical_key
+                    && code == winit::keyboard::KeyCode::F3
+                    && event.state.is_pressed()
+                    && !event.repeat
+                {
+                    self.toggle_screen(event_loop, AppScreen::OptionsScreen);
                 }
             }
 
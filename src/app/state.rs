@@ -1,17 +1,44 @@
-use winit::{
-    event::KeyEvent,
-    keyboard::{KeyCode, PhysicalKey},
+use std::{
+    fmt::Debug,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
 };
 
-use crate::game_boy;
+use winit::{event::KeyEvent, keyboard::PhysicalKey};
 
-use super::windows::WindowRegistry;
+use crate::game_boy::{
+    self, CameraImage, LinkStatus, LinkedPair, PendingLink, Printer, RingBufferSink, TcpLink, Tilt,
+};
+
+use super::{
+    cli::Launch,
+    keymap::{self, KeyMapping},
+    windows::WindowRegistry,
+};
 
 #[derive(Debug, Default)]
 pub struct AppState {
     pub app_config: super::Config,
     pub game_state: GameState,
     emulation_state: Option<game_boy::System>,
+    link_state: Option<LinkState>,
+    /// Game waiting for another process to connect its link cable
+    pending_host: Option<PendingHost>,
+    /// Connection of a link cable to another process
+    network_link: Option<LinkStatus>,
+    /// Why hosting a link cable failed
+    network_link_error: Option<String>,
+    /// ROMs of the loaded games, in the order of their systems
+    rom_paths: Vec<PathBuf>,
+    /// Tilt of the single game, for cartridges with an accelerometer
+    tilt: Tilt,
+    /// Queue of the audio output, fed by the single game or the first linked game
+    audio_sink: Option<RingBufferSink>,
+    /// Battery-backed memory as last written to each save file, in the order of the systems
+    written_saves: Vec<Option<Vec<u8>>>,
+    /// Frames run since the saves were last flushed
+    frames_since_save: u32,
 
     pub window_registry: WindowRegistry,
 }
@@ -22,31 +49,278 @@ pub struct GameState {
     pub gameboy_config: game_boy::Config,
 }
 
+/// Two games connected by a virtual link cable, shown side by side
+pub struct LinkState {
+    pub pair: LinkedPair,
+    pub key_mappings: [KeyMapping; 2],
+    /// Textures the split screen draws the frames into
+    pub frame_textures: [Option<egui::TextureHandle>; 2],
+}
+
+impl Debug for LinkState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LinkState")
+            .field("pair", &self.pair)
+            .field("key_mappings", &self.key_mappings)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Game that starts once the other process joined
+#[derive(Debug)]
+struct PendingHost {
+    system: game_boy::System,
+    rom: PathBuf,
+    link: PendingLink,
+}
+
+/// How often the battery-backed memory is flushed to the save files, about every second,
+/// so closing the process any other way loses little progress
+const SAVE_INTERVAL_FRAMES: u32 = 60;
+
+/// Battery-backed memory is kept next to the ROM
+fn save_path(rom: &Path) -> PathBuf {
+    rom.with_extension("sav")
+}
+
+fn load_system(path: &Path, config: &game_boy::Config) -> io::Result<game_boy::System> {
+    let rom = std::fs::read(path)?.into_boxed_slice();
+    let mut system = game_boy::System::with_config(rom, config)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let save_path = save_path(path);
+    if system.has_battery() && save_path.exists() {
+        system.load_save_data(&std::fs::read(&save_path)?);
+        log::info!("loaded save file {}", save_path.display());
+    }
+    Ok(system)
+}
+
 impl AppState {
+    pub fn launch(&mut self, launch: &Launch) -> io::Result<()> {
+        let config = &self.game_state.gameboy_config;
+        match launch {
+            Launch::Empty | Launch::ExtractPhotos { .. } => {}
+            Launch::Single {
+                rom,
+                printer,
+                camera,
+            } => {
+                let mut system = load_system(rom, config)?;
+                if let Some(directory) = printer {
+                    system.connect_link(Printer::new(directory));
+                }
+                if let Some(path) = camera {
+                    let image = CameraImage::load_png(path)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    system.set_camera_image(image);
+                }
+                self.emulation_state = Some(system);
+                self.rom_paths = vec![rom.clone()];
+            }
+            Launch::Linked(first, second) => {
+                let pair =
+                    LinkedPair::new(load_system(first, config)?, load_system(second, config)?);
+                self.link_state = Some(LinkState {
+                    pair,
+                    key_mappings: [KeyMapping::PLAYER_ONE, KeyMapping::PLAYER_TWO],
+                    frame_textures: [None, None],
+                });
+                self.rom_paths = vec![first.clone(), second.clone()];
+            }
+            Launch::Host { address, rom, mode } => {
+                let system = load_system(rom, config)?;
+                // The window keeps running while the other player joins
+                let link = TcpLink::listen(address, *mode)?;
+                log::info!("waiting for the other player on {}", link.address());
+                self.pending_host = Some(PendingHost {
+                    system,
+                    rom: rom.clone(),
+                    link,
+                });
+            }
+            Launch::Join { address, rom, mode } => {
+                let mut system = load_system(rom, config)?;
+                let link = TcpLink::connect(address.as_str(), *mode)?;
+                log::info!("link cable connected to {address}");
+                self.network_link = Some(link.status());
+                system.connect_link(link);
+                self.emulation_state = Some(system);
+                self.rom_paths = vec![rom.clone()];
+            }
+        }
+        Ok(())
+    }
+    /// Write the battery-backed memory of every loaded game that changed since it was last
+    /// written to its save file
+    pub fn write_saves(&mut self) {
+        let systems = self
+            .emulation_state
+            .iter()
+            .chain(self.link_state.iter().flat_map(|link| link.pair.systems()));
+        self.written_saves.resize(self.rom_paths.len(), None);
+        for ((system, rom), written) in systems.zip(&self.rom_paths).zip(&mut self.written_saves) {
+            let Some(data) = system.save_data() else {
+                continue;
+            };
+            if written.as_ref() == Some(&data) {
+                continue;
+            }
+            let path = save_path(rom);
+            match std::fs::write(&path, &data) {
+                Ok(()) => {
+                    log::debug!("wrote save file {}", path.display());
+                    *written = Some(data);
+                }
+                Err(err) => log::error!("could not write save file {}: {err}", path.display()),
+            }
+        }
+    }
+    /// Play the audio of the loaded game through `sink`
+    pub fn set_audio_sink(&mut self, sink: RingBufferSink, sample_rate: u32) {
+        if let Some(system) = self.audible_system() {
+            system.set_sample_rate(sample_rate);
+        }
+        self.audio_sink = Some(sink);
+    }
+    fn audible_system(&mut self) -> Option<&mut game_boy::System> {
+        match (&mut self.emulation_state, &mut self.link_state) {
+            (Some(system), _) => Some(system),
+            (None, Some(link_state)) => Some(&mut link_state.pair.systems_mut()[0]),
+            // Set up before the other process joins
+            (None, None) => self.pending_host.as_mut().map(|host| &mut host.system),
+        }
+    }
+    pub fn emulation(&self) -> Option<&game_boy::System> {
+        self.emulation_state.as_ref()
+    }
+    /// Start the hosted game once the other process joined
+    fn poll_pending_host(&mut self) {
+        let Some(result) = self.pending_host.as_ref().and_then(|host| host.link.poll()) else {
+            return;
+        };
+        let PendingHost {
+            mut system, rom, ..
+        } = self.pending_host.take().unwrap();
+        match result {
+            Ok(link) => {
+                self.network_link = Some(link.status());
+                system.connect_link(link);
+                self.emulation_state = Some(system);
+                self.rom_paths = vec![rom];
+            }
+            Err(err) => {
+                log::error!("could not host the link cable: {err}");
+                self.network_link_error = Some(err.to_string());
+            }
+        }
+    }
+    /// Address the other process should join, while the hosted game waits for it
+    pub fn waiting_for_link(&self) -> Option<SocketAddr> {
+        self.pending_host.as_ref().map(|host| host.link.address())
+    }
+    pub fn network_link_error(&self) -> Option<&str> {
+        self.network_link_error.as_deref()
+    }
+    /// Whether a link cable to another process was connected and dropped
+    pub fn network_link_lost(&self) -> bool {
+        self.network_link
+            .as_ref()
+            .is_some_and(|status| !status.is_connected())
+    }
+    pub fn link_state_mut(&mut self) -> Option<&mut LinkState> {
+        self.link_state.as_mut()
+    }
+    pub fn is_linked(&self) -> bool {
+        self.link_state.is_some()
+    }
+    /// Run every loaded game for one frame
+    pub fn run_frame(&mut self) {
+        let frame_time = game_boy::SystemTime::from_seconds(1.0 / game_boy::REFRESH_RATE as f64);
+        self.poll_pending_host();
+        if let Some(system) = self.emulation_state.as_mut() {
+            let target_time = system.time() + frame_time;
+            while system.time() < target_time {
+                let (events, _) = system.advance(target_time - system.time());
+                // The other process is behind, the frame is cut short instead of waiting
+                if events.link_stalled() {
+                    break;
+                }
+            }
+        }
+        if let Some(link_state) = self.link_state.as_mut() {
+            link_state.pair.run_for(frame_time);
+        }
+        self.frames_since_save += 1;
+        if self.frames_since_save >= SAVE_INTERVAL_FRAMES {
+            self.frames_since_save = 0;
+            self.write_saves();
+        }
+        if let Some(mut sink) = self.audio_sink.take() {
+            if let Some(system) = self.audible_system() {
+                system.drain_audio(&mut sink);
+            }
+            self.audio_sink = Some(sink);
+        }
+    }
+    /// Tilt following the mouse cursor, the center of the window being level
+    pub fn handle_cursor_moved(&mut self, position: (f64, f64), window_size: (u32, u32)) {
+        let axis = |position: f64, size: u32| {
+            (position / size.max(1) as f64 * 2.0 - 1.0).clamp(-1.0, 1.0) as f32
+        };
+        self.tilt = Tilt {
+            x: axis(position.0, window_size.0),
+            y: axis(position.1, window_size.1),
+        };
+        self.apply_tilt();
+    }
+    fn apply_tilt(&mut self) {
+        if let Some(system) = self.emulation_state.as_mut() {
+            system.set_tilt(self.tilt);
+        }
+    }
     pub fn handle_key_event(&mut self, event: &KeyEvent) {
         if event.repeat {
             return;
         }
-        if let PhysicalKey::Code(code) = event.physical_key {
-            // TODO: remappable keys
-            if let Some(input) = match code {
-                KeyCode::ArrowRight => Some(game_boy::Input::RIGHT),
-                KeyCode::ArrowLeft => Some(game_boy::Input::LEFT),
-                KeyCode::ArrowUp => Some(game_boy::Input::UP),
-                KeyCode::ArrowDown => Some(game_boy::Input::DOWN),
-                KeyCode::KeyX => Some(game_boy::Input::A),
-                KeyCode::KeyZ => Some(game_boy::Input::B),
-                KeyCode::KeyA => Some(game_boy::Input::SELECT),
-                KeyCode::KeyS => Some(game_boy::Input::START),
-                _ => None,
-            } && let Some(system) = self.emulation_state.as_mut()
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return;
+        };
+        let pressed = event.state.is_pressed();
+        if let Some(direction) = keymap::tilt_direction(code) {
+            let amount = if pressed { 1.0 } else { 0.0 };
+            if direction.x != 0.0 {
+                self.tilt.x = direction.x * amount;
+            }
+            if direction.y != 0.0 {
+                self.tilt.y = direction.y * amount;
+            }
+            self.apply_tilt();
+        }
+        // TODO: remappable keys
+        if let Some(input) = KeyMapping::SINGLE.input(code)
+            && let Some(system) = self.emulation_state.as_mut()
+        {
+            set_key(system, input, pressed);
+        }
+        if let Some(link_state) = self.link_state.as_mut() {
+            for (system, mapping) in link_state
+                .pair
+                .systems_mut()
+                .iter_mut()
+                .zip(link_state.key_mappings)
             {
-                if event.state.is_pressed() {
-                    system.press_key(input);
-                } else {
-                    system.unpress_key(input);
+                if let Some(input) = mapping.input(code) {
+                    set_key(system, input, pressed);
                 }
             }
         }
     }
 }
+
+fn set_key(system: &mut game_boy::System, input: game_boy::Input, pressed: bool) {
+    if pressed {
+        system.press_key(input);
+    } else {
+        system.unpress_key(input);
+    }
+}
@@ -1,9 +1,14 @@
+use crate::game_boy::{self, WINDOW_HEIGHT, WINDOW_WIDTH};
+
 use super::state::AppState;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Copy)]
 pub enum UiLayout {
     MainLayout,
     OptionsLayout,
+    /// Both games of a linked session side by side
+    LinkLayout,
 }
 
 impl UiLayout {
@@ -13,6 +18,27 @@ impl UiLayout {
     pub fn build(&self, ctx: &egui::Context, state: &mut AppState) {
         match self {
             UiLayout::MainLayout => {
+                if let Some(address) = state.emulation().and_then(|system| system.locked_address())
+                {
+                    egui::TopBottomPanel::bottom("cpu_locked").show(ctx, |ui| {
+                        ui.label(format!("CPU locked at ${address:04X}"));
+                    });
+                }
+                if let Some(address) = state.waiting_for_link() {
+                    egui::TopBottomPanel::bottom("link_waiting").show(ctx, |ui| {
+                        ui.label(format!("Waiting for the other player on {address}"));
+                    });
+                }
+                if let Some(err) = state.network_link_error() {
+                    egui::TopBottomPanel::bottom("link_error").show(ctx, |ui| {
+                        ui.label(format!("Could not host the link cable: {err}"));
+                    });
+                }
+                if state.network_link_lost() {
+                    egui::TopBottomPanel::bottom("link_lost").show(ctx, |ui| {
+                        ui.label("Link cable disconnected");
+                    });
+                }
                 // egui::CentralPanel::default().show(ctx, |ui| {
                 //     ui.label("Main Screen UI!");
                 //     if ui.button("Click me!").clicked() {
@@ -20,6 +46,24 @@ impl UiLayout {
                 //     }
                 // });
             }
+            UiLayout::LinkLayout => {
+                let Some(link_state) = state.link_state_mut() else {
+                    return;
+                };
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.columns(2, |columns| {
+                        for (player, column) in columns.iter_mut().enumerate() {
+                            let system = &link_state.pair.systems()[player];
+                            let texture = &mut link_state.frame_textures[player];
+                            column.label(format!("Player {}", player + 1));
+                            column.add(
+                                egui::Image::new(update_frame_texture(ctx, texture, system))
+                                    .shrink_to_fit(),
+                            );
+                        }
+                    });
+                });
+            }
             UiLayout::OptionsLayout => {
                 egui::CentralPanel::default().show(ctx, |ui| {
                     ui.label("Options Screen UI!");
@@ -31,3 +75,23 @@ impl UiLayout {
         }
     }
 }
+
+/// Upload the last frame of `system`, creating the texture on first use
+fn update_frame_texture<'a>(
+    ctx: &egui::Context,
+    texture: &'a mut Option<egui::TextureHandle>,
+    system: &game_boy::System,
+) -> &'a egui::TextureHandle {
+    let image = egui::ColorImage::from_rgba_unmultiplied(
+        [WINDOW_WIDTH as usize, WINDOW_HEIGHT as usize],
+        system.frame().as_flattened(),
+    );
+    let options = egui::TextureOptions::NEAREST;
+    match texture {
+        Some(texture) => {
+            texture.set(image, options);
+            texture
+        }
+        None => texture.insert(ctx.load_texture("frame", image, options)),
+    }
+}
@@ -5,6 +5,7 @@ use winit::window::WindowId;
 
 use super::ui::UiLayout;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Assoc)]
 #[func(pub fn layout(&self) -> Option<UiLayout>)]
 #[func(pub fn is_main(&self) -> bool { false })]
@@ -13,6 +14,9 @@ pub enum AppScreen {
     MainScreen,
     #[assoc(layout = UiLayout::OptionsLayout)]
     OptionsScreen,
+    /// Split screen for two linked games
+    #[assoc(layout = UiLayout::LinkLayout)]
+    LinkScreen,
 }
 
 #[derive(Debug, Default)]
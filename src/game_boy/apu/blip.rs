@@ -0,0 +1,90 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+/// Output samples covered by the band-limited step of a single amplitude change
+const KERNEL_WIDTH: usize = 16;
+/// Sub-sample positions a step can start at
+const KERNEL_PHASES: usize = 64;
+/// Fraction of the output Nyquist frequency kept by the kernel
+const CUTOFF: f64 = 0.9;
+
+/// Band-limited synthesis buffer
+/// Amplitude changes are added at input clock resolution as band-limited impulses,
+/// which are integrated back into steps when reading samples at the output rate.
+/// This removes the aliasing that point sampling the channels would produce.
+#[derive(Debug)]
+pub struct BlipBuffer {
+    /// Output samples per input clock
+    ratio: f64,
+    /// Current time in output samples, relative to the first unread sample
+    time: f64,
+    deltas: VecDeque<f32>,
+    integrator: f32,
+    kernel: Box<[[f32; KERNEL_WIDTH]; KERNEL_PHASES]>,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            ratio: sample_rate as f64 / clock_rate as f64,
+            time: 0.0,
+            deltas: VecDeque::from(vec![0.0; KERNEL_WIDTH]),
+            integrator: 0.0,
+            kernel: Box::new(kernel()),
+        }
+    }
+    /// Change the amplitude by `delta` at the current time
+    pub fn add_delta(&mut self, delta: f32) {
+        let start = self.time.floor();
+        let phase = ((self.time - start) * KERNEL_PHASES as f64) as usize;
+        let start = start as usize;
+        let end = start + KERNEL_WIDTH;
+        if self.deltas.len() < end {
+            self.deltas.resize(end, 0.0);
+        }
+        for (i, tap) in self.kernel[phase].iter().enumerate() {
+            self.deltas[start + i] += tap * delta;
+        }
+    }
+    /// Advance the current time by one input clock
+    pub fn clock(&mut self) {
+        self.time += self.ratio;
+    }
+    /// Pop the next sample that can't be changed by future deltas anymore
+    pub fn read_sample(&mut self) -> Option<f32> {
+        if self.time < 1.0 {
+            return None;
+        }
+        self.time -= 1.0;
+        self.integrator += self.deltas.pop_front().unwrap_or_default();
+        Some(self.integrator)
+    }
+}
+
+/// Windowed sinc impulses for every phase, delayed by half the kernel width
+/// Each phase is normalized so that the integrated step has the exact amplitude
+fn kernel() -> [[f32; KERNEL_WIDTH]; KERNEL_PHASES] {
+    let half_width = KERNEL_WIDTH as f64 / 2.0;
+    let mut kernel = [[0.0; KERNEL_WIDTH]; KERNEL_PHASES];
+    for (phase, taps) in kernel.iter_mut().enumerate() {
+        let offset = phase as f64 / KERNEL_PHASES as f64;
+        let impulse: Vec<f64> = (0..KERNEL_WIDTH)
+            .map(|tap| {
+                let x = tap as f64 + 1.0 - half_width - offset;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+                };
+                // Blackman window over the kernel width
+                let w = (x + half_width) / KERNEL_WIDTH as f64;
+                let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                sinc * window
+            })
+            .collect();
+        let sum: f64 = impulse.iter().sum();
+        for (tap, value) in taps.iter_mut().zip(impulse) {
+            *tap = (value / sum) as f32;
+        }
+    }
+    kernel
+}
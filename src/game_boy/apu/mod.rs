@@ -0,0 +1,295 @@
+mod blip;
+mod noise;
+mod pulse;
+#[cfg(test)]
+mod tests;
+mod units;
+mod wave;
+
+use blip::BlipBuffer;
+use noise::Noise;
+use pulse::Pulse;
+use wave::Wave;
+
+use super::hardware::HardwareMode;
+
+/// The channels are clocked at half the base system clock
+pub const APU_CLOCK_RATE: u32 = 2_097_152;
+/// The frame sequencer steps on the falling edge of DIV bit 4 (bit 5 in double speed)
+const FRAME_SEQUENCER_BIT: u16 = 1 << 12;
+const FRAME_SEQUENCER_BIT_DOUBLE_SPEED: u16 = 1 << 13;
+/// How much the output capacitor keeps its charge every base clock
+const CAPACITOR_CHARGE_FACTOR: f32 = 0.999958;
+
+/// Stereo sample, left then right, in the range -1.0..=1.0
+pub type Sample = [f32; 2];
+
+/// Audio processing unit with 2 pulse channels, a wave channel and a noise channel
+#[derive(Debug)]
+pub struct Apu {
+    hardware: HardwareMode,
+    /// NR52 bit 7
+    powered: bool,
+    ch1: Pulse,
+    ch2: Pulse,
+    ch3: Wave,
+    ch4: Noise,
+    /// Master volume and VIN panning
+    nr50: u8,
+    /// Channel panning, right in the lower nibble, left in the upper nibble
+    nr51: u8,
+    /// Next frame sequencer step
+    frame_step: u8,
+    div_bit: bool,
+    /// Left and right resamplers, no samples are produced without them
+    resampler: Option<[BlipBuffer; 2]>,
+    /// Mixer output on the last APU clock
+    last_mix: Sample,
+    samples: Vec<Sample>,
+    /// High-pass filter removing the DC offset of the DACs
+    capacitor: Sample,
+    capacitor_factor: f32,
+}
+
+impl Apu {
+    /// APU as it is left by the boot ROM
+    pub fn new(hardware: HardwareMode) -> Self {
+        let mut apu = Self {
+            hardware,
+            powered: true,
+            ch1: Pulse::new(true),
+            ch2: Pulse::new(false),
+            ch3: Wave::default(),
+            ch4: Noise::default(),
+            nr50: 0x77,
+            nr51: 0xF3,
+            frame_step: 0,
+            div_bit: false,
+            resampler: None,
+            last_mix: [0.0; 2],
+            samples: Vec::new(),
+            capacitor: [0.0; 2],
+            capacitor_factor: 1.0,
+        };
+        // The boot ROM plays its sound on channel 1 and leaves it faded out but enabled
+        apu.ch1.write(1, 0x80, false);
+        apu.ch1.write(2, 0x08, false);
+        apu.ch1.write(4, 0x80, false);
+        apu.ch1.write(2, 0xF3, false);
+        apu
+    }
+    /// Set the rate of the stereo output, 0 disables it
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.resampler = (sample_rate != 0).then(|| {
+            [
+                BlipBuffer::new(APU_CLOCK_RATE, sample_rate),
+                BlipBuffer::new(APU_CLOCK_RATE, sample_rate),
+            ]
+        });
+        self.last_mix = [0.0; 2];
+        self.capacitor_factor = if sample_rate == 0 {
+            1.0
+        } else {
+            CAPACITOR_CHARGE_FACTOR.powf(2.0 * APU_CLOCK_RATE as f32 / sample_rate as f32)
+        };
+    }
+    /// Take the samples produced since the last call
+    pub fn take_samples(&mut self) -> Vec<Sample> {
+        std::mem::take(&mut self.samples)
+    }
+    /// Advance the APU by one M-cycle
+    /// `div_counter` is the timer's system counter which drives the frame sequencer
+    pub fn tick(&mut self, div_counter: u16, double_speed: bool) {
+        self.update_div(div_counter, double_speed);
+        // The APU runs in real time, which is only one APU clock in double speed
+        let clocks = if double_speed { 1 } else { 2 };
+        for _ in 0..clocks {
+            self.step();
+        }
+    }
+    /// Must be called whenever the system counter changes, including DIV resets
+    pub fn update_div(&mut self, div_counter: u16, double_speed: bool) {
+        let bit = if double_speed {
+            FRAME_SEQUENCER_BIT_DOUBLE_SPEED
+        } else {
+            FRAME_SEQUENCER_BIT
+        };
+        let div_bit = div_counter & bit != 0;
+        if self.div_bit && !div_bit && self.powered {
+            self.step_frame_sequencer();
+        }
+        self.div_bit = div_bit;
+    }
+    /// Advance the channels by one APU clock
+    fn step(&mut self) {
+        if self.powered {
+            self.ch1.tick();
+            self.ch2.tick();
+            self.ch3.tick();
+            self.ch4.tick();
+        }
+        self.resample();
+    }
+    /// Feed the mixer output changes to the resamplers and collect finished samples
+    fn resample(&mut self) {
+        if self.resampler.is_none() {
+            return;
+        }
+        let mix = self.mix();
+        let Some(resampler) = self.resampler.as_mut() else {
+            return;
+        };
+        for (side, blip) in resampler.iter_mut().enumerate() {
+            let delta = mix[side] - self.last_mix[side];
+            if delta != 0.0 {
+                blip.add_delta(delta);
+            }
+            blip.clock();
+        }
+        self.last_mix = mix;
+        // Both sides advance together, so their samples are always ready at the same time
+        while let [Some(left), Some(right)] = resampler.each_mut().map(BlipBuffer::read_sample) {
+            let sample = high_pass(&mut self.capacitor, self.capacitor_factor, [left, right]);
+            self.samples.push(sample);
+        }
+    }
+    fn step_frame_sequencer(&mut self) {
+        // Length counters on even steps, sweep on 2 and 6, envelopes on 7
+        if self.frame_step.is_multiple_of(2) {
+            self.ch1.clock_length();
+            self.ch2.clock_length();
+            self.ch3.clock_length();
+            self.ch4.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.ch1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.ch1.clock_envelope();
+            self.ch2.clock_envelope();
+            self.ch4.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+    /// Whether the next frame sequencer step clocks the length counters
+    fn length_step(&self) -> bool {
+        self.frame_step.is_multiple_of(2)
+    }
+    /// Analog output of every channel's DAC, in the range -1.0..=1.0
+    fn dac_outputs(&self) -> [f32; 4] {
+        let dac = |enabled: bool, output: u8| {
+            if enabled {
+                output as f32 / 7.5 - 1.0
+            } else {
+                0.0
+            }
+        };
+        [
+            dac(self.ch1.dac_enabled(), self.ch1.output()),
+            dac(self.ch2.dac_enabled(), self.ch2.output()),
+            dac(self.ch3.dac_enabled(), self.ch3.output()),
+            dac(self.ch4.dac_enabled(), self.ch4.output()),
+        ]
+    }
+    fn mix(&self) -> Sample {
+        let mut sample = [0.0; 2];
+        for (channel, output) in self.dac_outputs().into_iter().enumerate() {
+            if self.nr51 & (0x10 << channel) != 0 {
+                sample[0] += output;
+            }
+            if self.nr51 & (0x01 << channel) != 0 {
+                sample[1] += output;
+            }
+        }
+        let left_volume = ((self.nr50 >> 4) & 0x07) + 1;
+        let right_volume = (self.nr50 & 0x07) + 1;
+        // Scale so that all 4 channels at full volume fit in -1.0..=1.0
+        sample[0] *= left_volume as f32 / 8.0 / 4.0;
+        sample[1] *= right_volume as f32 / 8.0 / 4.0;
+        sample
+    }
+    fn power_off(&mut self) {
+        self.powered = false;
+        // Only the DMG keeps its length counters while off
+        let keep_length = !self.hardware.is_cgb_hardware();
+        self.ch1.power_off(keep_length);
+        self.ch2.power_off(keep_length);
+        self.ch3.power_off(keep_length);
+        self.ch4.power_off(keep_length);
+        self.nr50 = 0;
+        self.nr51 = 0;
+    }
+    fn power_on(&mut self) {
+        self.powered = true;
+        self.frame_step = 0;
+    }
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF10..=0xFF14 => self.ch1.read(addr - 0xFF10),
+            0xFF15..=0xFF19 => self.ch2.read(addr - 0xFF15),
+            0xFF1A..=0xFF1E => self.ch3.read(addr - 0xFF1A),
+            0xFF1F..=0xFF23 => self.ch4.read(addr - 0xFF1F),
+            0xFF24 => self.nr50,
+            0xFF25 => self.nr51,
+            0xFF26 => {
+                0x70 | ((self.powered as u8) << 7)
+                    | ((self.ch4.enabled() as u8) << 3)
+                    | ((self.ch3.enabled() as u8) << 2)
+                    | ((self.ch2.enabled() as u8) << 1)
+                    | self.ch1.enabled() as u8
+            }
+            0xFF27..=0xFF2F => 0xFF,
+            0xFF30..=0xFF3F => self.ch3.read_ram(addr),
+            _ => unreachable!("{addr:04x} is not an APU register"),
+        }
+    }
+    pub fn write(&mut self, addr: u16, data: u8) {
+        if addr == 0xFF26 {
+            let powered = data & 0x80 != 0;
+            if self.powered && !powered {
+                self.power_off();
+            } else if !self.powered && powered {
+                self.power_on();
+            }
+            return;
+        }
+        if let 0xFF30..=0xFF3F = addr {
+            self.ch3.write_ram(addr, data);
+            return;
+        }
+        if !self.powered {
+            // Registers are read-only while the APU is off, except for the lengths on DMG
+            if !self.hardware.is_cgb_hardware() {
+                match addr {
+                    0xFF11 => self.ch1.write_length(data),
+                    0xFF16 => self.ch2.write_length(data),
+                    0xFF1B => self.ch3.write_length(data),
+                    0xFF20 => self.ch4.write_length(data),
+                    _ => {}
+                }
+            }
+            return;
+        }
+        let length_step = self.length_step();
+        match addr {
+            0xFF10..=0xFF14 => self.ch1.write(addr - 0xFF10, data, length_step),
+            0xFF15..=0xFF19 => self.ch2.write(addr - 0xFF15, data, length_step),
+            0xFF1A..=0xFF1E => self.ch3.write(addr - 0xFF1A, data, length_step),
+            0xFF1F..=0xFF23 => self.ch4.write(addr - 0xFF1F, data, length_step),
+            0xFF24 => self.nr50 = data,
+            0xFF25 => self.nr51 = data,
+            0xFF27..=0xFF2F => {}
+            _ => unreachable!("{addr:04x} is not an APU register"),
+        }
+    }
+}
+
+/// Output capacitor, a high-pass filter removing the DC offset of the DACs
+fn high_pass(capacitor: &mut Sample, factor: f32, sample: Sample) -> Sample {
+    let mut out = [0.0; 2];
+    for i in 0..2 {
+        out[i] = sample[i] - capacitor[i];
+        capacitor[i] = sample[i] - out[i] * factor;
+    }
+    out
+}
@@ -0,0 +1,131 @@
+use super::units::{Envelope, LengthCounter};
+
+/// Noise divisors in APU clocks, selected by the lower 3 bits of NR43
+const DIVISORS: [u32; 8] = [4, 8, 16, 24, 32, 40, 48, 56];
+
+/// Channel 4, pseudo-random noise from a linear feedback shift register
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    enabled: bool,
+    length: LengthCounter,
+    envelope: Envelope,
+    /// NR43
+    control: u8,
+    /// APU clocks left until the next LFSR shift
+    timer: u32,
+    lfsr: u16,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            control: 0,
+            timer: 0,
+            lfsr: 0,
+        }
+    }
+}
+
+impl Noise {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+    /// Digital output, 0-15
+    pub fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 1 == 0 {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 | 1 => 0xFF,
+            2 => self.envelope.read(),
+            3 => self.control,
+            4 => 0xBF | ((self.length.enabled() as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+    pub fn write(&mut self, register: u16, data: u8, length_step: bool) {
+        match register {
+            0 => {}
+            1 => self.write_length(data),
+            2 => {
+                self.envelope.write(data);
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.control = data,
+            4 => {
+                let trigger = data & 0x80 != 0;
+                if self
+                    .length
+                    .write_control(data & 0x40 != 0, trigger, length_step)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.enabled = self.dac_enabled();
+                    self.timer = self.period();
+                    self.envelope.trigger();
+                    self.lfsr = 0x7FFF;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    /// The length can be written even while the APU is off on DMG
+    pub fn write_length(&mut self, data: u8) {
+        self.length.load(data as u16 & 0x3F);
+    }
+    fn shift(&self) -> u8 {
+        self.control >> 4
+    }
+    /// 7 bit LFSR mode
+    fn short_mode(&self) -> bool {
+        self.control & 0x08 != 0
+    }
+    fn period(&self) -> u32 {
+        DIVISORS[(self.control & 0x07) as usize] << self.shift()
+    }
+    /// Advance by one APU clock
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period();
+        // Shifts 14 and 15 stop the LFSR
+        if self.shift() >= 14 {
+            return;
+        }
+        let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+        if self.short_mode() {
+            self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+        }
+    }
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+    pub fn power_off(&mut self, keep_length: bool) {
+        let length = self.length.power_off(keep_length);
+        *self = Self {
+            length,
+            ..Self::default()
+        };
+    }
+}
@@ -0,0 +1,212 @@
+use super::units::{Envelope, LengthCounter};
+
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+/// Frequency sweep of channel 1 (NR10)
+#[derive(Debug, Clone, Copy, Default)]
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow: u16,
+    /// Set once a subtraction was calculated since the last trigger
+    negate_used: bool,
+}
+
+impl Sweep {
+    fn read(&self) -> u8 {
+        0x80 | (self.period << 4) | ((self.negate as u8) << 3) | self.shift
+    }
+    /// Returns true if the channel should be disabled
+    fn write(&mut self, data: u8) -> bool {
+        self.period = (data >> 4) & 0x07;
+        self.negate = data & 0x08 != 0;
+        self.shift = data & 0x07;
+        // Leaving negate mode after it was used disables the channel
+        self.negate_used && !self.negate
+    }
+    fn reload_timer(&mut self) {
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+    fn calculate(&mut self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.negate {
+            self.negate_used = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+    /// Returns true if the channel should be disabled
+    fn trigger(&mut self, frequency: u16) -> bool {
+        self.shadow = frequency;
+        self.negate_used = false;
+        self.reload_timer();
+        self.enabled = self.period != 0 || self.shift != 0;
+        self.shift != 0 && self.calculate() > 0x7FF
+    }
+    /// Clocked by the frame sequencer, returns true if the channel should be disabled
+    fn clock(&mut self, frequency: &mut u16) -> bool {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer != 0 {
+            return false;
+        }
+        self.reload_timer();
+        if !self.enabled || self.period == 0 {
+            return false;
+        }
+        let new_frequency = self.calculate();
+        if new_frequency > 0x7FF {
+            return true;
+        }
+        if self.shift != 0 {
+            self.shadow = new_frequency;
+            *frequency = new_frequency;
+            // The new frequency is checked again right away
+            return self.calculate() > 0x7FF;
+        }
+        false
+    }
+}
+
+/// Square wave channels 1 and 2
+#[derive(Debug, Clone, Copy)]
+pub struct Pulse {
+    enabled: bool,
+    /// Only channel 1 has a sweep unit
+    sweep: Option<Sweep>,
+    duty: u8,
+    duty_step: u8,
+    length: LengthCounter,
+    envelope: Envelope,
+    frequency: u16,
+    /// APU clocks left until the next duty step
+    timer: u16,
+}
+
+impl Pulse {
+    pub fn new(has_sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            sweep: has_sweep.then(Sweep::default),
+            duty: 0,
+            duty_step: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            frequency: 0,
+            timer: 0,
+        }
+    }
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+    /// Digital output, 0-15
+    pub fn output(&self) -> u8 {
+        let high = DUTY_PATTERNS[self.duty as usize] & (0x80 >> self.duty_step) != 0;
+        if self.enabled && high {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => self.sweep.map(|sweep| sweep.read()).unwrap_or(0xFF),
+            1 => (self.duty << 6) | 0x3F,
+            2 => self.envelope.read(),
+            3 => 0xFF,
+            4 => 0xBF | ((self.length.enabled() as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+    pub fn write(&mut self, register: u16, data: u8, length_step: bool) {
+        match register {
+            0 => {
+                if let Some(sweep) = self.sweep.as_mut()
+                    && sweep.write(data)
+                {
+                    self.enabled = false;
+                }
+            }
+            1 => {
+                self.duty = data >> 6;
+                self.write_length(data);
+            }
+            2 => {
+                self.envelope.write(data);
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | data as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((data as u16 & 0x07) << 8);
+                let trigger = data & 0x80 != 0;
+                if self
+                    .length
+                    .write_control(data & 0x40 != 0, trigger, length_step)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    /// The length can be written even while the APU is off on DMG
+    pub fn write_length(&mut self, data: u8) {
+        self.length.load(data as u16 & 0x3F);
+    }
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.timer = self.period();
+        self.envelope.trigger();
+        if let Some(sweep) = self.sweep.as_mut()
+            && sweep.trigger(self.frequency)
+        {
+            self.enabled = false;
+        }
+    }
+    fn period(&self) -> u16 {
+        (2048 - self.frequency) * 2
+    }
+    /// Advance by one APU clock
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period();
+        self.duty_step = (self.duty_step + 1) % 8;
+    }
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+    pub fn clock_sweep(&mut self) {
+        if let Some(sweep) = self.sweep.as_mut()
+            && self.enabled
+            && sweep.clock(&mut self.frequency)
+        {
+            self.enabled = false;
+        }
+    }
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+    pub fn power_off(&mut self, keep_length: bool) {
+        let length = self.length.power_off(keep_length);
+        *self = Self {
+            length,
+            ..Self::new(self.sweep.is_some())
+        };
+    }
+}
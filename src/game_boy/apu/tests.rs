@@ -0,0 +1,193 @@
+use crate::game_boy::hardware::HardwareMode;
+
+use super::{APU_CLOCK_RATE, Apu, blip::BlipBuffer};
+
+/// APU with its own system counter, like the one in the timer
+struct TestApu {
+    apu: Apu,
+    counter: u16,
+}
+
+impl TestApu {
+    fn new() -> Self {
+        Self {
+            apu: Apu::new(HardwareMode::Dmg),
+            counter: 0,
+        }
+    }
+    fn run(&mut self, cycles: usize) {
+        for _ in 0..cycles {
+            self.counter = self.counter.wrapping_add(4);
+            self.apu.tick(self.counter, false);
+        }
+    }
+}
+
+/// M-cycles between frame sequencer steps
+const FRAME_STEP_CYCLES: usize = 2048;
+
+#[test]
+fn pulse_duty() {
+    let mut t = TestApu::new();
+    // 50% duty, full volume, one duty step every 256 M-cycles
+    t.apu.write(0xFF16, 0x80);
+    t.apu.write(0xFF17, 0xF0);
+    t.apu.write(0xFF18, 0x00);
+    t.apu.write(0xFF19, 0x87);
+    let mut outputs = Vec::new();
+    for _ in 0..8 {
+        t.run(256);
+        outputs.push(t.apu.ch2.output());
+    }
+    assert_eq!(outputs.iter().filter(|&&output| output == 15).count(), 4);
+    assert_eq!(outputs.iter().filter(|&&output| output == 0).count(), 4);
+}
+
+#[test]
+fn length_counter() {
+    let mut t = TestApu::new();
+    // Length 62 leaves 2 clocks
+    t.apu.write(0xFF16, 0x3E);
+    t.apu.write(0xFF17, 0xF0);
+    t.apu.write(0xFF19, 0xC0);
+    assert_eq!(t.apu.read(0xFF26) & 0x02, 0x02);
+    // Steps 0 and 1, only the first one clocks the length
+    t.run(FRAME_STEP_CYCLES * 2);
+    assert_eq!(t.apu.read(0xFF26) & 0x02, 0x02);
+    t.run(FRAME_STEP_CYCLES);
+    assert_eq!(t.apu.read(0xFF26) & 0x02, 0x00, "length ran out");
+}
+
+#[test]
+fn sweep_overflow_on_trigger() {
+    let mut t = TestApu::new();
+    t.apu.write(0xFF10, 0x11);
+    t.apu.write(0xFF12, 0xF0);
+    t.apu.write(0xFF13, 0xFF);
+    t.apu.write(0xFF14, 0x87);
+    assert_eq!(t.apu.read(0xFF26) & 0x01, 0x00);
+}
+
+#[test]
+fn noise_short_mode_period() {
+    let mut t = TestApu::new();
+    t.apu.write(0xFF21, 0xF0);
+    t.apu.write(0xFF22, 0x08);
+    t.apu.write(0xFF23, 0x80);
+    let mut outputs = Vec::new();
+    for _ in 0..127 * 2 {
+        // Divisor code 0 shifts the LFSR every 4 APU clocks
+        t.run(2);
+        outputs.push(t.apu.ch4.output());
+    }
+    assert!(outputs.contains(&0) && outputs.contains(&15));
+    assert_eq!(
+        outputs[..127],
+        outputs[127..],
+        "7 bit LFSR repeats every 127 shifts"
+    );
+}
+
+#[test]
+fn power_off() {
+    let mut t = TestApu::new();
+    t.apu.write(0xFF26, 0x00);
+    assert_eq!(t.apu.read(0xFF26), 0x70);
+    assert_eq!(t.apu.read(0xFF24), 0x00);
+    t.apu.write(0xFF24, 0x77);
+    assert_eq!(
+        t.apu.read(0xFF24),
+        0x00,
+        "registers are read-only while off"
+    );
+    t.apu.write(0xFF30, 0x12);
+    assert_eq!(t.apu.read(0xFF30), 0x12, "wave RAM is still accessible");
+    t.apu.write(0xFF26, 0x80);
+    t.apu.write(0xFF24, 0x77);
+    assert_eq!(t.apu.read(0xFF24), 0x77);
+}
+
+#[test]
+fn sample_output() {
+    let mut t = TestApu::new();
+    t.apu.set_sample_rate(48000);
+    // Channel 2 on the left only
+    t.apu.write(0xFF25, 0x20);
+    t.apu.write(0xFF16, 0x80);
+    t.apu.write(0xFF17, 0xF0);
+    t.apu.write(0xFF19, 0x87);
+    // One frame
+    t.run(17556);
+    let samples = t.apu.take_samples();
+    assert!((803..=804).contains(&samples.len()), "{}", samples.len());
+    assert!(samples.iter().any(|[left, _]| *left > 0.1));
+    assert!(samples.iter().any(|[left, _]| *left < -0.1));
+    assert!(samples.iter().all(|[_, right]| *right == 0.0));
+    assert!(t.apu.take_samples().is_empty());
+}
+
+/// Resample a signal given as its level at every APU clock
+fn resample(levels: impl IntoIterator<Item = f32>) -> Vec<f32> {
+    let mut blip = BlipBuffer::new(APU_CLOCK_RATE, 48000);
+    let mut level = 0.0;
+    let mut samples = Vec::new();
+    for next in levels {
+        if next != level {
+            blip.add_delta(next - level);
+            level = next;
+        }
+        blip.clock();
+        samples.extend(std::iter::from_fn(|| blip.read_sample()));
+    }
+    samples
+}
+
+#[test]
+fn blip_step_response() {
+    // APU clocks in an output sample
+    let sample_clocks = APU_CLOCK_RATE as usize / 48000;
+    for delay in 0..=sample_clocks {
+        let levels = (0..APU_CLOCK_RATE as usize / 100).map(|clock| (clock >= delay) as u8 as f32);
+        let samples = resample(levels);
+        // A band-limited step rings, an ideal low-pass overshoots by 9% (Gibbs)
+        let peak = samples.iter().copied().fold(f32::MIN, f32::max);
+        assert!(peak < 1.15, "overshoot of {peak} after {delay} clocks");
+        let settled = &samples[samples.len() - 16..];
+        assert!(settled.iter().all(|sample| (sample - 1.0).abs() < 1e-4));
+    }
+}
+
+/// Largest absolute sample after the first step has settled
+fn ripple(samples: &[f32]) -> f32 {
+    samples[32..]
+        .iter()
+        .map(|sample| sample.abs())
+        .fold(0.0, f32::max)
+}
+
+/// Full scale square wave with a period of `2 * half_period` APU clocks
+fn square_wave(half_period: usize) -> impl Iterator<Item = f32> {
+    (0..APU_CLOCK_RATE as usize / 10).map(move |clock| {
+        if (clock / half_period).is_multiple_of(2) {
+            1.0
+        } else {
+            -1.0
+        }
+    })
+}
+
+#[test]
+fn blip_removes_frequencies_above_nyquist() {
+    // About 40 kHz, point sampling at 48 kHz would alias it down to 8 kHz at full scale
+    let ripple_above = ripple(&resample(square_wave(26)));
+    assert!(
+        ripple_above < 0.03,
+        "{ripple_above} left of a 40 kHz square wave"
+    );
+    // About 1 kHz is kept
+    let ripple_below = ripple(&resample(square_wave(1024)));
+    assert!(
+        ripple_below > 0.9,
+        "1 kHz square wave reduced to {ripple_below}"
+    );
+}
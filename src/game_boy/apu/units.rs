@@ -0,0 +1,108 @@
+/// Length counter shared by every channel, the channel is disabled when it runs out
+#[derive(Debug, Clone, Copy)]
+pub struct LengthCounter {
+    /// 64 for the pulse and noise channels, 256 for the wave channel
+    max: u16,
+    counter: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        Self {
+            max,
+            counter: 0,
+            enabled: false,
+        }
+    }
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    /// Load the length written to NRx1
+    pub fn load(&mut self, length: u16) {
+        self.counter = self.max - length;
+    }
+    /// Clocked by the frame sequencer, returns true if the channel should be disabled
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+    /// Handle a write to NRx4, returns true if the channel should be disabled
+    /// `length_step` tells if the next frame sequencer step clocks the length counters
+    pub fn write_control(&mut self, enable: bool, trigger: bool, length_step: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enable;
+        let mut disable = false;
+        // Enabling the counter during the first half of a length period clocks it once
+        if !was_enabled && enable && !length_step && self.counter > 0 {
+            self.counter -= 1;
+            disable = self.counter == 0 && !trigger;
+        }
+        if trigger && self.counter == 0 {
+            self.counter = self.max;
+            if enable && !length_step {
+                self.counter -= 1;
+            }
+        }
+        disable
+    }
+    /// State left after the APU is powered off
+    /// The DMG keeps the counter itself, the CGB clears it
+    pub fn power_off(self, keep_counter: bool) -> Self {
+        Self {
+            counter: if keep_counter { self.counter } else { 0 },
+            ..Self::new(self.max)
+        }
+    }
+}
+
+/// Volume envelope of the pulse and noise channels (NRx2)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Envelope {
+    initial_volume: u8,
+    increase: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn read(&self) -> u8 {
+        (self.initial_volume << 4) | ((self.increase as u8) << 3) | self.period
+    }
+    pub fn write(&mut self, data: u8) {
+        self.initial_volume = data >> 4;
+        self.increase = data & 0x08 != 0;
+        self.period = data & 0x07;
+    }
+    /// The DAC is off when the upper 5 bits of NRx2 are cleared
+    pub fn dac_enabled(&self) -> bool {
+        self.read() & 0xF8 != 0
+    }
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.period;
+    }
+    /// Clocked by the frame sequencer
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer != 0 {
+            return;
+        }
+        self.timer = self.period;
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use super::units::LengthCounter;
+
+pub const WAVE_RAM_SIZE: usize = 16;
+
+/// Channel 3, plays 32 4-bit samples from wave RAM
+#[derive(Debug, Clone, Copy)]
+pub struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    length: LengthCounter,
+    /// NR32 output level: mute, 100%, 50%, 25%
+    volume_code: u8,
+    frequency: u16,
+    /// APU clocks left until the next sample
+    timer: u16,
+    position: u8,
+    sample_buffer: u8,
+    ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(256),
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample_buffer: 0,
+            ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+}
+
+impl Wave {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+    /// Digital output, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.volume_code == 0 {
+            return 0;
+        }
+        self.sample_buffer >> (self.volume_code - 1)
+    }
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => 0x7F | ((self.dac_enabled as u8) << 7),
+            1 | 3 => 0xFF,
+            2 => 0x9F | (self.volume_code << 5),
+            4 => 0xBF | ((self.length.enabled() as u8) << 6),
+            _ => unreachable!(),
+        }
+    }
+    pub fn write(&mut self, register: u16, data: u8, length_step: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = data & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.write_length(data),
+            2 => self.volume_code = (data >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x700) | data as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((data as u16 & 0x07) << 8);
+                let trigger = data & 0x80 != 0;
+                if self
+                    .length
+                    .write_control(data & 0x40 != 0, trigger, length_step)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.enabled = self.dac_enabled;
+                    self.position = 0;
+                    self.timer = self.period();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    /// The length can be written even while the APU is off on DMG
+    pub fn write_length(&mut self, data: u8) {
+        self.length.load(data as u16);
+    }
+    /// While the channel plays, the CPU can only access the byte being read by the channel
+    fn ram_index(&self, addr: u16) -> usize {
+        if self.enabled {
+            self.position as usize / 2
+        } else {
+            (addr & 0x0F) as usize
+        }
+    }
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        self.ram[self.ram_index(addr)]
+    }
+    pub fn write_ram(&mut self, addr: u16, data: u8) {
+        self.ram[self.ram_index(addr)] = data;
+    }
+    fn period(&self) -> u16 {
+        2048 - self.frequency
+    }
+    /// Advance by one APU clock
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period();
+        if !self.enabled {
+            return;
+        }
+        self.position = (self.position + 1) % 32;
+        let byte = self.ram[self.position as usize / 2];
+        self.sample_buffer = if self.position.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+    }
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+    /// Wave RAM is kept across power cycles
+    pub fn power_off(&mut self, keep_length: bool) {
+        let length = self.length.power_off(keep_length);
+        *self = Self {
+            length,
+            ram: self.ram,
+            ..Self::default()
+        };
+    }
+}
@@ -0,0 +1,20 @@
+pub mod ring;
+#[cfg(test)]
+mod tests;
+pub mod wav;
+
+pub use super::apu::Sample;
+
+/// Destination for the resampled output of the APU
+pub trait AudioSink {
+    /// Receive stereo samples at the rate the system was configured with
+    fn push_samples(&mut self, samples: &[Sample]);
+}
+
+/// Throws every sample away
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn push_samples(&mut self, _samples: &[Sample]) {}
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use super::{AudioSink, Sample};
+
+/// Bounded queue shared between the emulation and an audio callback
+/// When the reader falls behind, the oldest samples are dropped to keep latency bounded
+#[derive(Debug, Clone)]
+pub struct RingBufferSink {
+    buffer: Arc<Mutex<VecDeque<Sample>>>,
+    capacity: usize,
+}
+
+/// Audio callback side of a [`RingBufferSink`]
+#[derive(Debug, Clone)]
+pub struct RingBufferReader {
+    buffer: Arc<Mutex<VecDeque<Sample>>>,
+}
+
+impl RingBufferSink {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+    pub fn reader(&self) -> RingBufferReader {
+        RingBufferReader {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl AudioSink for RingBufferSink {
+    fn push_samples(&mut self, samples: &[Sample]) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend(samples);
+        let overflow = buffer.len().saturating_sub(self.capacity);
+        buffer.drain(..overflow);
+    }
+}
+
+impl RingBufferReader {
+    /// Fill `out` with queued samples, padding with silence on underrun
+    /// Returns the number of samples that were actually queued
+    pub fn fill(&self, out: &mut [Sample]) -> usize {
+        let mut buffer = self.buffer.lock().unwrap();
+        let count = out.len().min(buffer.len());
+        for (slot, sample) in out.iter_mut().zip(buffer.drain(..count)) {
+            *slot = sample;
+        }
+        out[count..].fill([0.0; 2]);
+        count
+    }
+    pub fn len(&self) -> usize {
+        self.buffer.lock().unwrap().len()
+    }
+}
@@ -0,0 +1,93 @@
+use std::{fs, io::Cursor, path::PathBuf};
+
+use crate::game_boy::{System, time::SystemTime};
+
+use super::{
+    AudioSink,
+    ring::{RingBufferReader, RingBufferSink},
+    wav::WavSink,
+};
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// Plays a square wave on channel 2 and noise on channel 4, then loops forever
+const SOUND_PROGRAM: &[u8] = &[
+    0x3E, 0x77, 0xE0, 0x24, // NR50: full volume
+    0x3E, 0xFF, 0xE0, 0x25, // NR51: every channel on both sides
+    0x3E, 0x80, 0xE0, 0x16, // NR21: 50% duty
+    0x3E, 0xF1, 0xE0, 0x17, // NR22: volume 15, fading out
+    0x3E, 0x00, 0xE0, 0x18, // NR23
+    0x3E, 0x86, 0xE0, 0x19, // NR24: trigger at 256 Hz
+    0x3E, 0xA2, 0xE0, 0x21, // NR42: volume 10, fading out
+    0x3E, 0x35, 0xE0, 0x22, // NR43
+    0x3E, 0x80, 0xE0, 0x23, // NR44: trigger
+    0x18, 0xFE, // JR -2
+];
+
+fn sound_rom() -> Box<[u8]> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + SOUND_PROGRAM.len()].copy_from_slice(SOUND_PROGRAM);
+    rom.into_boxed_slice()
+}
+
+/// Run a ROM headlessly, recording its audio into a WAV file in memory
+fn record(rom: Box<[u8]>, seconds: f64) -> Vec<u8> {
+    let mut system = System::now(rom).unwrap();
+    system.set_sample_rate(SAMPLE_RATE);
+    let mut sink = WavSink::new(Cursor::new(Vec::new()), SAMPLE_RATE).unwrap();
+    let end = system.time() + SystemTime::from_seconds(seconds);
+    while system.time() < end {
+        system.advance(end - system.time());
+        system.drain_audio(&mut sink);
+    }
+    sink.finish().unwrap().into_inner()
+}
+
+/// Compare a recording against a golden file, allowing off-by-one rounding differences
+/// Set CVGB_UPDATE_GOLDEN to write the recording as the new golden file
+fn check_golden(name: &str, recording: &[u8]) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test/audio", name]
+        .iter()
+        .collect();
+    if std::env::var_os("CVGB_UPDATE_GOLDEN").is_some() {
+        fs::write(&path, recording).unwrap();
+        return;
+    }
+    let golden = fs::read(&path).unwrap_or_else(|err| {
+        panic!("{name}: can't read golden file ({err}), run with CVGB_UPDATE_GOLDEN=1 to record it")
+    });
+    assert_eq!(golden.len(), recording.len(), "{name}: length differs");
+    assert_eq!(golden[..44], recording[..44], "{name}: header differs");
+    let pcm = |bytes: &[u8]| -> Vec<i16> {
+        bytes[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    };
+    for (i, (expected, actual)) in pcm(&golden).into_iter().zip(pcm(recording)).enumerate() {
+        assert!(
+            (expected as i32 - actual as i32).abs() <= 1,
+            "{name}: sample {i} is {actual}, expected {expected}"
+        );
+    }
+}
+
+#[test]
+fn square_and_noise_golden() {
+    let recording = record(sound_rom(), 0.5);
+    check_golden("square_noise.wav", &recording);
+}
+
+#[test]
+fn ring_buffer_drops_oldest() {
+    let mut sink = RingBufferSink::new(4);
+    let reader: RingBufferReader = sink.reader();
+    let samples: Vec<_> = (0..6).map(|i| [i as f32, 0.0]).collect();
+    sink.push_samples(&samples);
+    assert_eq!(reader.len(), 4);
+    let mut out = [[9.0; 2]; 6];
+    assert_eq!(reader.fill(&mut out), 4);
+    assert_eq!(out[0], [2.0, 0.0]);
+    assert_eq!(out[3], [5.0, 0.0]);
+    assert_eq!(out[4], [0.0, 0.0], "underruns are filled with silence");
+}
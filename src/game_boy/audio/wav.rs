@@ -0,0 +1,87 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use super::{AudioSink, Sample};
+
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+const HEADER_SIZE: u32 = 44;
+
+/// Records samples into a 16 bit stereo PCM WAV file
+/// The sizes in the header are filled in by [`WavSink::finish`]
+#[derive(Debug)]
+pub struct WavSink<W: Write + Seek> {
+    writer: W,
+    data_size: u32,
+    /// First write error, reported when finishing the file
+    error: Option<io::Error>,
+}
+
+impl WavSink<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> io::Result<Self> {
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // PCM
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&CHANNELS.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            data_size: 0,
+            error: None,
+        })
+    }
+    /// Patch the header sizes and return the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+    fn write_samples(&mut self, samples: &[Sample]) -> io::Result<()> {
+        for sample in samples {
+            for value in sample {
+                let pcm = (value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                self.writer.write_all(&pcm.to_le_bytes())?;
+            }
+        }
+        self.data_size += (samples.len() * 2 * 2) as u32;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn push_samples(&mut self, samples: &[Sample]) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = self.write_samples(samples) {
+            self.error = Some(error);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+pub mod photos;
+pub mod sensor;
+
+use sensor::{CameraImage, REGISTER_COUNT};
+
+use crate::game_boy::time::SystemTime;
+
+use super::{ram_bank_index, rom_bank_byte};
+
+/// The captured picture goes to RAM bank 0 from A100
+const PICTURE_ADDRESS: usize = 0x0100;
+/// M-cycles taken by a capture besides the exposure
+const CAPTURE_BASE_CYCLES: u64 = 32446;
+/// Extra M-cycles when the N bit is clear
+const CAPTURE_NON_EXCLUSIVE_CYCLES: u64 = 512;
+/// M-cycles per unit of the exposure registers
+const EXPOSURE_UNIT_CYCLES: u64 = 16;
+
+/// Game Boy Camera mapper with the M64282FP sensor
+#[derive(Debug)]
+pub struct PocketCamera {
+    /// Only gates writes, the RAM can always be read
+    ram_enable: bool,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    /// RAM bank register bit 4, A000-BFFF maps the sensor registers
+    registers_mapped: bool,
+    registers: [u8; REGISTER_COUNT],
+    image: CameraImage,
+    now: SystemTime,
+    /// Time the capture in progress completes
+    capture_until: Option<SystemTime>,
+}
+
+impl PocketCamera {
+    pub fn new() -> Self {
+        Self {
+            ram_enable: false,
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            registers_mapped: false,
+            registers: [0; REGISTER_COUNT],
+            image: CameraImage::default(),
+            now: SystemTime::new(),
+            capture_until: None,
+        }
+    }
+    pub fn set_image(&mut self, image: CameraImage) {
+        self.image = image;
+    }
+    /// Finish the capture in progress once its time is up
+    pub fn tick(&mut self, ram: &mut [u8], time: SystemTime) {
+        self.now = time;
+        if self.capture_until.is_some_and(|until| time >= until) {
+            self.capture_until = None;
+            self.registers[0] &= !0x01;
+            let tiles = sensor::capture(&self.image, &self.registers);
+            if let Some(picture) = ram.get_mut(PICTURE_ADDRESS..PICTURE_ADDRESS + tiles.len()) {
+                picture.copy_from_slice(&*tiles);
+            }
+        }
+    }
+    fn capture_cycles(&self) -> u64 {
+        let exposure = u16::from_be_bytes([self.registers[2], self.registers[3]]) as u64;
+        let exclusive = self.registers[1] & 0x80 != 0;
+        CAPTURE_BASE_CYCLES
+            + if exclusive {
+                0
+            } else {
+                CAPTURE_NON_EXCLUSIVE_CYCLES
+            }
+            + EXPOSURE_UNIT_CYCLES * exposure
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank_number = data & 0x3F,
+            0x4000..=0x5FFF => {
+                self.registers_mapped = data & 0x10 != 0;
+                self.ram_bank_number = data & 0x0F;
+            }
+            _ => {}
+        }
+    }
+    /// Registers repeat every 0x80 bytes, only A000 can be read
+    fn register(addr: u16) -> usize {
+        addr as usize & 0x7F
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if self.registers_mapped {
+            return match Self::register(addr) {
+                0x00 => self.registers[0] & 0x07,
+                _ => 0x00,
+            };
+        }
+        // The picture can't be read while the sensor writes it
+        if self.capture_until.is_some() && self.ram_bank_number == 0 {
+            return 0x00;
+        }
+        ram_bank_index(ram, self.ram_bank_number as usize, addr).map_or(0xFF, |index| ram[index])
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if self.registers_mapped {
+            match Self::register(addr) {
+                0x00 => {
+                    self.registers[0] = data & 0x07;
+                    if data & 0x01 != 0 {
+                        let cycles = self.capture_cycles();
+                        self.capture_until =
+                            Some(self.now + SystemTime::from_system_clocks(cycles));
+                    } else {
+                        self.capture_until = None;
+                    }
+                }
+                register if register < REGISTER_COUNT => self.registers[register] = data,
+                _ => {}
+            }
+            return;
+        }
+        if !self.ram_enable {
+            return;
+        }
+        if let Some(index) = ram_bank_index(ram, self.ram_bank_number as usize, addr) {
+            ram[index] = data;
+        }
+    }
+}
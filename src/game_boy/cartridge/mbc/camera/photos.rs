@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+
+use crate::game_boy::image::save_png;
+
+use super::sensor::{self, TILES_SIZE, WIDTH};
+
+const PHOTO_SLOTS: usize = 30;
+/// Photos are stored 2 per RAM bank, starting at bank 1
+const FIRST_SLOT: usize = 0x2000;
+const SLOT_SIZE: usize = 0x1000;
+/// One byte per slot in bank 0 with the photo's place in the album, FF for an unused slot
+const ALBUM_ADDRESS: usize = 0x11B2;
+
+/// Gray pixels of the photos in the camera's save RAM, in album order
+pub fn extract_photos(ram: &[u8]) -> Vec<Vec<u8>> {
+    let Some(album) = ram.get(ALBUM_ADDRESS..ALBUM_ADDRESS + PHOTO_SLOTS) else {
+        return Vec::new();
+    };
+    let mut slots: Vec<(u8, usize)> = album
+        .iter()
+        .enumerate()
+        .filter(|&(_, &place)| (place as usize) < PHOTO_SLOTS)
+        .map(|(slot, &place)| (place, slot))
+        .collect();
+    slots.sort();
+    slots
+        .into_iter()
+        .filter_map(|(_, slot)| {
+            let start = FIRST_SLOT + slot * SLOT_SIZE;
+            ram.get(start..start + TILES_SIZE)
+        })
+        .map(sensor::decode_tiles)
+        .collect()
+}
+
+/// Save every photo in the camera's save RAM as `photo_NN.png` in `directory`
+pub fn save_photos(ram: &[u8], directory: &Path) -> Result<Vec<PathBuf>, png::EncodingError> {
+    extract_photos(ram)
+        .into_iter()
+        .enumerate()
+        .map(|(index, pixels)| {
+            let path = directory.join(format!("photo_{:02}.png", index + 1));
+            save_png(&path, WIDTH, &pixels).map(|()| path)
+        })
+        .collect()
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use crate::game_boy::image::GrayImage;
+
+/// Size of the picture the sensor delivers to the cartridge
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 112;
+/// The picture as 16x14 2bpp tiles
+pub const TILES_SIZE: usize = WIDTH * HEIGHT / 4;
+/// Registers A000-A035
+pub const REGISTER_COUNT: usize = 0x36;
+
+/// Edge enhancement ratio selected by register 4 bits 4-6
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+/// Gain in dB added by each step of register 1 bits 0-4, relative to the lowest gain
+const GAIN_STEP_DB: f32 = 1.5;
+/// Exposure time giving the scene's brightness at the lowest gain
+const UNIT_EXPOSURE: f32 = 0x0800 as f32;
+
+/// What the sensor is looking at
+#[derive(Debug, Clone, Default)]
+pub enum CameraImage {
+    /// Gray bars above a diagonal gradient
+    #[default]
+    TestPattern,
+    Image(GrayImage),
+}
+
+impl CameraImage {
+    /// Load a PNG, which is stretched to the sensor's 128x112
+    pub fn load_png(path: &Path) -> Result<Self, png::DecodingError> {
+        GrayImage::load_png(path).map(Self::Image)
+    }
+    /// Brightness of the scene at `x`, `y`, 255 being white
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        match self {
+            Self::TestPattern if y < HEIGHT / 2 => (x * 8 / WIDTH * 255 / 7) as u8,
+            Self::TestPattern => ((x + y - HEIGHT / 2) * 255 / (WIDTH + HEIGHT / 2 - 2)) as u8,
+            Self::Image(image) => image.sample(x, y, WIDTH, HEIGHT),
+        }
+    }
+}
+
+/// Take a picture of `image` with the settings in `registers`, returns the dithered tiles
+/// Models exposure, gain, edge enhancement, inversion and the dithering matrix,
+/// the sensor's offset and reference voltages are left out
+pub fn capture(image: &CameraImage, registers: &[u8; REGISTER_COUNT]) -> Box<[u8; TILES_SIZE]> {
+    let exposure = u16::from_be_bytes([registers[2], registers[3]]) as f32 / UNIT_EXPOSURE;
+    let gain = 10f32.powf((registers[1] & 0x1F) as f32 * GAIN_STEP_DB / 20.0);
+    let mut signal = vec![0f32; WIDTH * HEIGHT];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            signal[y * WIDTH + x] = image.pixel(x, y) as f32 * exposure * gain;
+        }
+    }
+
+    let ratio = EDGE_RATIOS[(registers[4] >> 4 & 0x07) as usize];
+    let (horizontal, vertical) = match registers[1] >> 5 & 0x03 {
+        0 => (false, false),
+        1 => (true, false),
+        2 => (false, true),
+        _ => (true, true),
+    };
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, HEIGHT as isize - 1) as usize;
+        signal[y * WIDTH + x]
+    };
+    let invert = registers[4] & 0x08 != 0;
+    let mut tiles = Box::new([0; TILES_SIZE]);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let (sx, sy) = (x as isize, y as isize);
+            let center = at(sx, sy);
+            let mut edge = 0.0;
+            if horizontal {
+                edge += 2.0 * center - at(sx - 1, sy) - at(sx + 1, sy);
+            }
+            if vertical {
+                edge += 2.0 * center - at(sx, sy - 1) - at(sx, sy + 1);
+            }
+            let mut value = (center + ratio * edge).clamp(0.0, 255.0) as u8;
+            if invert {
+                value = 255 - value;
+            }
+            set_pixel(&mut tiles, x, y, dither(registers, x, y, value));
+        }
+    }
+    tiles
+}
+
+/// Each position of the 4x4 matrix has 3 thresholds splitting the 4 colors,
+/// bright values give color 0
+fn dither(registers: &[u8; REGISTER_COUNT], x: usize, y: usize, value: u8) -> u8 {
+    let base = 6 + ((y % 4) * 4 + x % 4) * 3;
+    let thresholds = &registers[base..base + 3];
+    3 - thresholds
+        .iter()
+        .take_while(|&&threshold| value >= threshold)
+        .count() as u8
+}
+
+fn set_pixel(tiles: &mut [u8; TILES_SIZE], x: usize, y: usize, color: u8) {
+    let offset = ((y / 8) * (WIDTH / 8) + x / 8) * 16 + (y % 8) * 2;
+    let bit = 7 - (x % 8);
+    tiles[offset] |= (color & 0x01) << bit;
+    tiles[offset + 1] |= (color >> 1) << bit;
+}
+
+/// Gray pixels of a 2bpp picture in the sensor's tile layout
+pub fn decode_tiles(tiles: &[u8]) -> Vec<u8> {
+    /// Colors are shown as is, 0 being white
+    const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+    let mut pixels = vec![0; WIDTH * HEIGHT];
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (index % WIDTH, index / WIDTH);
+        let offset = ((y / 8) * (WIDTH / 8) + x / 8) * 16 + (y % 8) * 2;
+        let bit = 7 - (x % 8);
+        let color = (tiles[offset] >> bit & 1) | (tiles[offset + 1] >> bit & 1) << 1;
+        *pixel = SHADES[color as usize];
+    }
+    pixels
+}
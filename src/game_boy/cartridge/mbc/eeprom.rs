@@ -0,0 +1,197 @@
+/// 93LC56 organized as 128 16-bit words
+pub const WORDS: usize = 128;
+/// Start bit, 2 opcode bits and 8 address bits
+const COMMAND_BITS: u8 = 11;
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Waiting for a start bit
+    Idle,
+    Command {
+        value: u16,
+        count: u8,
+    },
+    /// Shifting out `address` starting at bit 15 - `count`
+    Read {
+        address: u8,
+        count: u8,
+    },
+    /// Shifting in the data of a WRITE, or of a WRAL without an address
+    Data {
+        address: Option<u8>,
+        value: u16,
+        count: u8,
+    },
+    /// Command done, DO shows ready until CS goes low
+    Done,
+}
+
+/// Microwire serial EEPROM driven by bit banging
+/// Bits are sampled on the rising edges of CLK while CS is high, programming is instant
+#[derive(Debug, Clone)]
+pub struct Eeprom {
+    words: Box<[u16; WORDS]>,
+    write_enable: bool,
+    cs: bool,
+    clk: bool,
+    data_out: bool,
+    state: State,
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Self {
+            // Erased cells read as 1s
+            words: Box::new([0xFFFF; WORDS]),
+            write_enable: false,
+            cs: false,
+            clk: false,
+            data_out: true,
+            state: State::Idle,
+        }
+    }
+}
+
+impl Eeprom {
+    /// Set the CS, CLK and DI pins
+    pub fn write_pins(&mut self, cs: bool, clk: bool, di: bool) {
+        let rising = clk && !self.clk;
+        self.clk = clk;
+        if !cs {
+            self.cs = false;
+            self.state = State::Idle;
+            self.data_out = true;
+            return;
+        }
+        self.cs = true;
+        if rising {
+            self.clock(di);
+        }
+    }
+    /// The DO pin
+    pub fn data_out(&self) -> bool {
+        self.data_out
+    }
+    fn clock(&mut self, di: bool) {
+        self.state = match self.state {
+            State::Idle if di => State::Command { value: 1, count: 1 },
+            State::Idle => State::Idle,
+            State::Command { value, count } => {
+                let value = value << 1 | di as u16;
+                if count + 1 < COMMAND_BITS {
+                    State::Command {
+                        value,
+                        count: count + 1,
+                    }
+                } else {
+                    self.command((value >> 8 & 0b11) as u8, value as u8)
+                }
+            }
+            State::Read { address, count } => {
+                self.data_out = self.words[address as usize] >> (15 - count) & 1 != 0;
+                if count < 15 {
+                    State::Read {
+                        address,
+                        count: count + 1,
+                    }
+                } else {
+                    // Reading goes on with the next word
+                    State::Read {
+                        address: (address + 1) % WORDS as u8,
+                        count: 0,
+                    }
+                }
+            }
+            State::Data {
+                address,
+                value,
+                count,
+            } => {
+                let value = value << 1 | di as u16;
+                if count < 15 {
+                    State::Data {
+                        address,
+                        value,
+                        count: count + 1,
+                    }
+                } else {
+                    match address {
+                        Some(address) => self.program(address, value),
+                        None => {
+                            for address in 0..WORDS as u8 {
+                                self.program(address, value);
+                            }
+                        }
+                    }
+                    self.done()
+                }
+            }
+            State::Done => State::Done,
+        }
+    }
+    fn command(&mut self, opcode: u8, address: u8) -> State {
+        // The top address bit is a don't care in 16-bit mode
+        let word = address & (WORDS as u8 - 1);
+        match opcode {
+            0b10 => {
+                // Dummy 0 before the data
+                self.data_out = false;
+                State::Read {
+                    address: word,
+                    count: 0,
+                }
+            }
+            0b01 => State::Data {
+                address: Some(word),
+                value: 0,
+                count: 0,
+            },
+            0b11 => {
+                self.program(word, 0xFFFF);
+                self.done()
+            }
+            _ => match address >> 6 {
+                0b00 => {
+                    self.write_enable = false;
+                    self.done()
+                }
+                0b01 => State::Data {
+                    address: None,
+                    value: 0,
+                    count: 0,
+                },
+                0b10 => {
+                    for word in 0..WORDS as u8 {
+                        self.program(word, 0xFFFF);
+                    }
+                    self.done()
+                }
+                _ => {
+                    self.write_enable = true;
+                    self.done()
+                }
+            },
+        }
+    }
+    fn done(&mut self) -> State {
+        self.data_out = true;
+        State::Done
+    }
+    /// Writes are ignored until an EWEN command
+    fn program(&mut self, address: u8, value: u16) {
+        if self.write_enable {
+            self.words[address as usize] = value;
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+    pub fn load_bytes(&mut self, bytes: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+/// 1 MiB Macronix MX29F008
+pub const FLASH_SIZE: usize = 1 << 20;
+/// Simplified to 16 uniform sectors
+const SECTOR_SIZE: usize = 0x10000;
+const MANUFACTURER_ID: u8 = 0xC2;
+const DEVICE_ID: u8 = 0x81;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Read,
+    /// AA was written to 5555
+    Unlock1,
+    /// 55 was written to 2AAA
+    Unlock2,
+    /// Reads return the manufacturer and device IDs
+    Id,
+    /// The next write programs a byte
+    Program,
+    /// 80 was written, waiting for another unlock sequence
+    EraseSetup,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+/// Flash memory driven by the JEDEC command sequences
+/// Erasing and programming complete instantly
+#[derive(Debug)]
+pub struct Flash {
+    data: Box<[u8]>,
+    state: State,
+}
+
+impl Default for Flash {
+    fn default() -> Self {
+        Self {
+            data: vec![0xFF; FLASH_SIZE].into_boxed_slice(),
+            state: State::Read,
+        }
+    }
+}
+
+impl Flash {
+    pub fn read(&self, address: usize) -> u8 {
+        let address = address % FLASH_SIZE;
+        match self.state {
+            State::Id => match address & 0xFF {
+                0x00 => MANUFACTURER_ID,
+                0x01 => DEVICE_ID,
+                _ => 0x00,
+            },
+            _ => self.data[address],
+        }
+    }
+    /// Write a command byte, erasing and programming only happen if `write_enable` is set
+    pub fn write(&mut self, address: usize, data: u8, write_enable: bool) {
+        let address = address % FLASH_SIZE;
+        // Commands only decode A0-A14
+        let command_address = address & 0x7FFF;
+        if data == 0xF0 {
+            self.state = State::Read;
+            return;
+        }
+        self.state = match (self.state, command_address, data) {
+            (State::Read | State::Id, 0x5555, 0xAA) => State::Unlock1,
+            (State::Unlock1, 0x2AAA, 0x55) => State::Unlock2,
+            (State::Unlock2, 0x5555, 0x90) => State::Id,
+            (State::Unlock2, 0x5555, 0xA0) => State::Program,
+            (State::Unlock2, 0x5555, 0x80) => State::EraseSetup,
+            (State::Program, ..) => {
+                if write_enable {
+                    // Programming can only clear bits
+                    self.data[address] &= data;
+                }
+                State::Read
+            }
+            (State::EraseSetup, 0x5555, 0xAA) => State::EraseUnlock1,
+            (State::EraseUnlock1, 0x2AAA, 0x55) => State::EraseUnlock2,
+            (State::EraseUnlock2, 0x5555, 0x10) => {
+                if write_enable {
+                    self.data.fill(0xFF);
+                }
+                State::Read
+            }
+            (State::EraseUnlock2, _, 0x30) => {
+                if write_enable {
+                    let start = address / SECTOR_SIZE * SECTOR_SIZE;
+                    self.data[start..start + SECTOR_SIZE].fill(0xFF);
+                }
+                State::Read
+            }
+            // ID mode stays on until reset
+            (State::Id, ..) => State::Id,
+            _ => State::Read,
+        };
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn load_data(&mut self, data: &[u8]) {
+        let len = data.len().min(FLASH_SIZE);
+        self.data[..len].copy_from_slice(&data[..len]);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::game_boy::infrared::{InfraredPeer, NoLight};
+
+use super::{ram_bank_index, rom_bank_byte};
+
+#[derive(Debug)]
+pub struct HuC1 {
+    /// A000-BFFF maps the IR port instead of RAM
+    ir_mode: bool,
+    /// 6 bits, writing 0 selects 1
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    infrared: Box<dyn InfraredPeer>,
+}
+
+impl HuC1 {
+    pub fn new() -> Self {
+        Self {
+            ir_mode: false,
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            infrared: Box::new(NoLight),
+        }
+    }
+    pub fn connect_infrared(&mut self, peer: Box<dyn InfraredPeer>) -> Box<dyn InfraredPeer> {
+        std::mem::replace(&mut self.infrared, peer)
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            // There is no RAM enable, only the IR select
+            0x0000..=0x1FFF => self.ir_mode = data & 0x0F == 0x0E,
+            0x2000..=0x3FFF => self.rom_bank_number = (data & 0x3F).max(1),
+            0x4000..=0x5FFF => self.ram_bank_number = data & 0x03,
+            _ => {}
+        }
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if self.ir_mode {
+            return read_infrared(self.infrared.as_ref());
+        }
+        ram_bank_index(ram, self.ram_bank_number as usize, addr).map_or(0xFF, |index| ram[index])
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if self.ir_mode {
+            self.infrared.set_led(data & 0x01 != 0);
+        } else if let Some(index) = ram_bank_index(ram, self.ram_bank_number as usize, addr) {
+            ram[index] = data;
+        }
+    }
+}
+
+/// IR register of the Hudson mappers, bit 0 is set while light is received
+pub fn read_infrared(infrared: &dyn InfraredPeer) -> u8 {
+    0xC0 | infrared.light() as u8
+}
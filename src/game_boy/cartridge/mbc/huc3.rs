@@ -0,0 +1,215 @@
+use crate::game_boy::{
+    infrared::{InfraredPeer, NoLight},
+    time::SystemTime,
+};
+
+use super::{huc1::read_infrared, ram_bank_index, rom_bank_byte, rtc::ClockDivider};
+
+/// Minutes of day and day counter as u32 LE, a u64 LE UNIX timestamp, then the RTC memory
+pub const FOOTER_SIZE: usize = 16 + RTC_MEMORY_SIZE;
+
+/// Nibbles addressed by the RTC commands
+const RTC_MEMORY_SIZE: usize = 0x100;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+/// 12 bits
+const DAYS: u16 = 0x1000;
+/// Alarm minute and day, 12 bits each, least significant nibble first
+const ALARM_ADDRESS: usize = 0x10;
+/// Bit 0 enables the alarm
+const ALARM_ENABLE_ADDRESS: usize = 0x16;
+/// Tone played by extended command E, 0 silences it
+const TONE_ADDRESS: usize = 0x27;
+
+/// Function of A000-BFFF, selected by writing to 0000-1FFF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    ReadRam,
+    ReadWriteRam,
+    /// Commands for the RTC chip
+    Command,
+    /// Result of the last command
+    Response,
+    /// Writing 0 runs the command, reading tells it is done
+    Semaphore,
+    Infrared,
+    Unmapped,
+}
+
+#[derive(Debug)]
+pub struct HuC3 {
+    mode: Mode,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    /// Command byte waiting for the semaphore: bits 4-6 are the command, bits 0-3 the argument
+    command: u8,
+    /// Nibble read by the last read command
+    response: u8,
+    /// RTC memory address of the next read or write command
+    address: u8,
+    memory: Box<[u8; RTC_MEMORY_SIZE]>,
+    minutes: u16,
+    /// 12 bits
+    days: u16,
+    /// Counts minutes
+    divider: ClockDivider,
+    /// The piezo speaker is playing the alarm tone
+    tone: bool,
+    infrared: Box<dyn InfraredPeer>,
+}
+
+impl HuC3 {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::ReadRam,
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            command: 0,
+            response: 0,
+            address: 0,
+            memory: Box::new([0; RTC_MEMORY_SIZE]),
+            minutes: 0,
+            days: 0,
+            divider: ClockDivider::new(60),
+            tone: false,
+            infrared: Box::new(NoLight),
+        }
+    }
+    pub fn connect_infrared(&mut self, peer: Box<dyn InfraredPeer>) -> Box<dyn InfraredPeer> {
+        std::mem::replace(&mut self.infrared, peer)
+    }
+    /// Whether the alarm tone is playing
+    pub fn tone(&self) -> bool {
+        self.tone
+    }
+    pub fn tick(&mut self, time: SystemTime) {
+        let minutes = self.divider.tick(time);
+        if minutes > 0 {
+            self.advance_minutes(minutes);
+        }
+    }
+    fn advance_minutes(&mut self, minutes: u64) {
+        let total = self.minutes as u64 + minutes;
+        self.minutes = (total % MINUTES_PER_DAY as u64) as u16;
+        self.days = ((self.days as u64 + total / MINUTES_PER_DAY as u64) % DAYS as u64) as u16;
+        let alarm_minutes = self.read_memory_word(ALARM_ADDRESS);
+        let alarm_days = self.read_memory_word(ALARM_ADDRESS + 3);
+        if self.memory[ALARM_ENABLE_ADDRESS] & 0x01 != 0
+            && (self.minutes, self.days) == (alarm_minutes, alarm_days)
+        {
+            self.tone = true;
+        }
+    }
+    /// 12-bit value stored in 3 nibbles starting at `address`
+    fn read_memory_word(&self, address: usize) -> u16 {
+        (0..3).fold(0, |word, nibble| {
+            word | (self.memory[address + nibble] as u16) << (nibble * 4)
+        })
+    }
+    fn write_memory_word(&mut self, address: usize, word: u16) {
+        for nibble in 0..3 {
+            self.memory[address + nibble] = (word >> (nibble * 4) & 0x0F) as u8;
+        }
+    }
+    fn run_command(&mut self) {
+        let argument = self.command & 0x0F;
+        match self.command >> 4 & 0x07 {
+            0x1 => {
+                self.response = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            0x3 => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            0x4 => self.address = self.address & 0xF0 | argument,
+            0x5 => self.address = self.address & 0x0F | argument << 4,
+            0x6 => match argument {
+                0x0 => {
+                    self.write_memory_word(0x00, self.minutes);
+                    self.write_memory_word(0x03, self.days);
+                }
+                0x1 => {
+                    self.minutes = self.read_memory_word(0x00) % MINUTES_PER_DAY;
+                    self.days = self.read_memory_word(0x03);
+                    self.divider.reset();
+                }
+                // Status, the clock is always ready
+                0x2 => self.response = 0x01,
+                0xE => self.tone = self.memory[TONE_ADDRESS] != 0,
+                _ => log::debug!("unknown HuC3 extended command {argument:x}"),
+            },
+            command => log::debug!("unknown HuC3 command {command:x}"),
+        }
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.mode = match data & 0x0F {
+                    0x0 => Mode::ReadRam,
+                    0xA => Mode::ReadWriteRam,
+                    0xB => Mode::Command,
+                    0xC => Mode::Response,
+                    0xD => Mode::Semaphore,
+                    0xE => Mode::Infrared,
+                    _ => Mode::Unmapped,
+                }
+            }
+            0x2000..=0x3FFF => self.rom_bank_number = data & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank_number = data & 0x03,
+            _ => {}
+        }
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        match self.mode {
+            Mode::ReadRam | Mode::ReadWriteRam => {
+                ram_bank_index(ram, self.ram_bank_number as usize, addr)
+                    .map_or(0xFF, |index| ram[index])
+            }
+            // The command is echoed in bits 4-6
+            Mode::Response => 0x80 | self.command & 0x70 | self.response,
+            Mode::Semaphore => 0x01,
+            Mode::Infrared => read_infrared(self.infrared.as_ref()),
+            Mode::Command | Mode::Unmapped => 0xFF,
+        }
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        match self.mode {
+            Mode::ReadWriteRam => {
+                if let Some(index) = ram_bank_index(ram, self.ram_bank_number as usize, addr) {
+                    ram[index] = data;
+                }
+            }
+            Mode::Command => self.command = data & 0x7F,
+            Mode::Semaphore if data & 0x01 == 0 => self.run_command(),
+            Mode::Infrared => self.infrared.set_led(data & 0x01 != 0),
+            _ => {}
+        }
+    }
+    pub fn footer(&self) -> Vec<u8> {
+        let mut footer = Vec::with_capacity(FOOTER_SIZE);
+        footer.extend((self.minutes as u32).to_le_bytes());
+        footer.extend((self.days as u32).to_le_bytes());
+        footer.extend(self.divider.timestamp());
+        footer.extend(*self.memory);
+        footer
+    }
+    /// Restore the clock from a save file footer, counting the time that passed since it was saved
+    pub fn load_footer(&mut self, footer: &[u8]) {
+        if footer.len() < FOOTER_SIZE {
+            log::warn!("HuC3 RTC footer is {} bytes, ignoring it", footer.len());
+            return;
+        }
+        let word = |index: usize| u32::from_le_bytes(footer[index..index + 4].try_into().unwrap());
+        self.minutes = (word(0) % MINUTES_PER_DAY as u32) as u16;
+        self.days = (word(4) % DAYS as u32) as u16;
+        self.memory.copy_from_slice(&footer[16..FOOTER_SIZE]);
+        self.advance_minutes(self.divider.periods_since(&footer[8..16]));
+    }
+}
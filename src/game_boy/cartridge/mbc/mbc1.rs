@@ -0,0 +1,100 @@
+use super::{ROM_BANK_SIZE, ram_bank_index, rom_bank_byte};
+
+/// Size of the multicarts wired as MBC1M
+const MULTICART_ROM_SIZE: usize = 1 << 20;
+/// Nintendo logo checked by the boot ROM, at 0104-0133 in the header of every game
+const LOGO_START: usize = 0x104;
+const LOGO_SIZE: usize = 0x30;
+
+#[derive(Debug)]
+pub struct Mbc1 {
+    ram_enable: bool,
+    /// BANK1, 5 bits, writing 0 selects 1
+    rom_bank_number: u8,
+    /// BANK2, 2 bits, upper ROM bank bits or RAM bank
+    ram_bank_number: u8,
+    /// Mode 1 applies BANK2 to the 0000-3FFF and A000-BFFF windows too
+    banking_mode: bool,
+    /// MBC1M wiring: BANK1 bit 4 isn't connected and BANK2 is shifted by 4 instead of 5
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: &[u8]) -> Self {
+        let multicart = is_multicart(rom);
+        if multicart {
+            log::info!("detected MBC1M multicart");
+        }
+        Self {
+            ram_enable: false,
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            banking_mode: false,
+            multicart,
+        }
+    }
+    fn bank2_shift(&self) -> u32 {
+        if self.multicart { 4 } else { 5 }
+    }
+    fn bank1(&self) -> u8 {
+        if self.multicart {
+            self.rom_bank_number & 0x0F
+        } else {
+            self.rom_bank_number
+        }
+    }
+    fn upper_bank(&self) -> usize {
+        (self.ram_bank_number as usize) << self.bank2_shift()
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF if self.banking_mode => self.upper_bank(),
+            0x0000..=0x3FFF => 0,
+            _ => self.upper_bank() | self.bank1() as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable = data & 0x0F == 0x0A,
+            // The zero check sees all 5 bits, so 20/40/60 can't be selected in 4000-7FFF
+            0x2000..=0x3FFF => self.rom_bank_number = (data & 0x1F).max(1),
+            0x4000..=0x5FFF => self.ram_bank_number = data & 0x03,
+            _ => self.banking_mode = data & 0x01 != 0,
+        }
+    }
+    fn ram_bank(&self) -> usize {
+        if self.banking_mode {
+            self.ram_bank_number as usize
+        } else {
+            0
+        }
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        ram_bank_index(ram, self.ram_bank(), addr).map_or(0xFF, |index| ram[index])
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        if let Some(index) = ram_bank_index(ram, self.ram_bank(), addr) {
+            ram[index] = data;
+        }
+    }
+}
+
+/// MBC1M multicarts are 1 MiB and hold a game with its own header every 256 KiB,
+/// so the Nintendo logo shows up again at the start of bank 10
+fn is_multicart(rom: &[u8]) -> bool {
+    if rom.len() != MULTICART_ROM_SIZE {
+        return false;
+    }
+    let logo = |bank: usize| {
+        let start = bank * ROM_BANK_SIZE + LOGO_START;
+        &rom[start..start + LOGO_SIZE]
+    };
+    logo(0x10) == logo(0)
+}
@@ -0,0 +1,51 @@
+use super::rom_bank_byte;
+
+/// 512 half-byte cells inside the MBC2 chip
+pub const RAM_SIZE: usize = 0x200;
+
+#[derive(Debug)]
+pub struct Mbc2 {
+    ram_enable: bool,
+    /// 4 bits, writing 0 selects 1
+    rom_bank_number: u8,
+}
+
+impl Mbc2 {
+    pub fn new() -> Self {
+        Self {
+            ram_enable: false,
+            rom_bank_number: 1,
+        }
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    /// Only 0000-3FFF is decoded, A8 tells the two registers apart
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => self.ram_enable = data & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.rom_bank_number = (data & 0x0F).max(1),
+            _ => {}
+        }
+    }
+    /// Only A0-A8 are decoded, so the cells repeat across A000-BFFF
+    fn ram_index(addr: u16) -> usize {
+        addr as usize & (RAM_SIZE - 1)
+    }
+    /// The upper nibble isn't connected and reads as 1s
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        0xF0 | ram[Self::ram_index(addr)]
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if self.ram_enable {
+            ram[Self::ram_index(addr)] = data & 0x0F;
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::game_boy::time::SystemTime;
+
+use super::{ram_bank_index, rom_bank_byte, rtc::Rtc};
+
+#[derive(Debug)]
+pub struct Mbc3 {
+    /// Also enables the RTC registers
+    ram_enable: bool,
+    /// 7 bits, writing 0 selects 1
+    rom_bank_number: u8,
+    /// RAM bank 00-03 or RTC register 08-0C mapped at A000-BFFF
+    ram_bank_number: u8,
+    /// Last value written to 6000-7FFF, writing 00 then 01 latches the clock
+    latch: u8,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(timer: bool) -> Self {
+        Self {
+            ram_enable: false,
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            latch: 0xFF,
+            rtc: timer.then(Rtc::default),
+        }
+    }
+    pub fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+    pub fn tick(&mut self, time: SystemTime) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.tick(time);
+        }
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank_number = (data & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank_number = data & 0x0F,
+            _ => {
+                if self.latch == 0x00
+                    && data == 0x01
+                    && let Some(rtc) = self.rtc.as_mut()
+                {
+                    rtc.latch();
+                }
+                self.latch = data;
+            }
+        }
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        match (self.ram_bank_number, self.rtc.as_ref()) {
+            (0x00..=0x03, _) => ram_bank_index(ram, self.ram_bank_number as usize, addr)
+                .map_or(0xFF, |index| ram[index]),
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank_number),
+            _ => 0xFF,
+        }
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        match (self.ram_bank_number, self.rtc.as_mut()) {
+            (0x00..=0x03, _) => {
+                if let Some(index) = ram_bank_index(ram, self.ram_bank_number as usize, addr) {
+                    ram[index] = data;
+                }
+            }
+            (0x08..=0x0C, Some(rtc)) => rtc.write(self.ram_bank_number, data),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use super::{ram_bank_index, rom_bank_byte};
+
+#[derive(Debug)]
+pub struct Mbc5 {
+    ram_enable: bool,
+    /// 9 bits, bank 0 can be mapped at 4000-7FFF too
+    rom_bank_number: u16,
+    /// 4 bits, 3 on rumble cartridges
+    ram_bank_number: u8,
+    /// Rumble cartridges drive the motor with bit 3 of the RAM bank register
+    has_rumble: bool,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub fn new(has_rumble: bool) -> Self {
+        Self {
+            ram_enable: false,
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            has_rumble,
+            rumble: false,
+        }
+    }
+    /// Whether the rumble motor is on
+    pub fn rumble(&self) -> bool {
+        self.rumble
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            // Unlike MBC1, all 8 bits are checked
+            0x0000..=0x1FFF => self.ram_enable = data == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank_number = self.rom_bank_number & 0x100 | data as u16,
+            0x3000..=0x3FFF => {
+                self.rom_bank_number = self.rom_bank_number & 0xFF | (data as u16 & 0x01) << 8
+            }
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.ram_bank_number = data & 0x07;
+                self.rumble = data & 0x08 != 0;
+            }
+            0x4000..=0x5FFF => self.ram_bank_number = data & 0x0F,
+            _ => {}
+        }
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        ram_bank_index(ram, self.ram_bank_number as usize, addr).map_or(0xFF, |index| ram[index])
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        if let Some(index) = ram_bank_index(ram, self.ram_bank_number as usize, addr) {
+            ram[index] = data;
+        }
+    }
+}
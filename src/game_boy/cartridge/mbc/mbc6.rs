@@ -0,0 +1,93 @@
+use super::{flash::Flash, rom_bank_byte};
+
+/// Both the ROM and the flash are banked in 8 KiB
+const HALF_BANK_SIZE: usize = 0x2000;
+/// The two RAM windows are 4 KiB each
+const RAM_HALF_BANK_SIZE: usize = 0x1000;
+
+#[derive(Debug)]
+pub struct Mbc6 {
+    ram_enable: bool,
+    /// Banks mapped at A000-AFFF and B000-BFFF
+    ram_bank_numbers: [u8; 2],
+    /// Banks mapped at 4000-5FFF and 6000-7FFF
+    rom_bank_numbers: [u8; 2],
+    /// Whether each ROM window maps the flash instead of the ROM
+    flash_select: [bool; 2],
+    /// Flash commands are ignored unless set
+    flash_enable: bool,
+    /// Needed to change `flash_enable` and to erase or program the flash
+    flash_write_enable: bool,
+    flash: Flash,
+}
+
+impl Mbc6 {
+    pub fn new() -> Self {
+        Self {
+            ram_enable: false,
+            ram_bank_numbers: [0, 0],
+            rom_bank_numbers: [0, 0],
+            flash_select: [false, false],
+            flash_enable: false,
+            flash_write_enable: false,
+            flash: Flash::default(),
+        }
+    }
+    pub fn flash(&self) -> &Flash {
+        &self.flash
+    }
+    pub fn flash_mut(&mut self) -> &mut Flash {
+        &mut self.flash
+    }
+    /// Which of the 4000-5FFF and 6000-7FFF windows `addr` is in
+    fn window(addr: u16) -> usize {
+        (addr as usize - 0x4000) / HALF_BANK_SIZE
+    }
+    fn flash_address(&self, addr: u16) -> usize {
+        self.rom_bank_numbers[Self::window(addr)] as usize * HALF_BANK_SIZE
+            + (addr as usize & (HALF_BANK_SIZE - 1))
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_byte(rom, 0, addr),
+            _ if self.flash_select[Self::window(addr)] => self.flash.read(self.flash_address(addr)),
+            _ if rom.is_empty() => 0xFF,
+            _ => rom[self.flash_address(addr) % rom.len()],
+        }
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x03FF => self.ram_enable = data & 0x0F == 0x0A,
+            0x0400..=0x07FF => self.ram_bank_numbers[0] = data & 0x07,
+            0x0800..=0x0BFF => self.ram_bank_numbers[1] = data & 0x07,
+            0x0C00..=0x0FFF if self.flash_write_enable => self.flash_enable = data & 0x01 != 0,
+            0x1000 => self.flash_write_enable = data & 0x01 != 0,
+            0x2000..=0x27FF => self.rom_bank_numbers[0] = data & 0x7F,
+            0x2800..=0x2FFF => self.flash_select[0] = data == 0x08,
+            0x3000..=0x37FF => self.rom_bank_numbers[1] = data & 0x7F,
+            0x3800..=0x3FFF => self.flash_select[1] = data == 0x08,
+            0x4000..=0x7FFF if self.flash_select[Self::window(addr)] && self.flash_enable => {
+                let address = self.flash_address(addr);
+                self.flash.write(address, data, self.flash_write_enable);
+            }
+            _ => {}
+        }
+    }
+    fn ram_index(&self, ram: &[u8], addr: u16) -> Option<usize> {
+        if !self.ram_enable || ram.is_empty() {
+            return None;
+        }
+        let window = (addr as usize - 0xA000) / RAM_HALF_BANK_SIZE;
+        let index = self.ram_bank_numbers[window] as usize * RAM_HALF_BANK_SIZE
+            + (addr as usize & (RAM_HALF_BANK_SIZE - 1));
+        Some(index % ram.len())
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        self.ram_index(ram, addr).map_or(0xFF, |index| ram[index])
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if let Some(index) = self.ram_index(ram, addr) {
+            ram[index] = data;
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::game_boy::input::Tilt;
+
+use super::{eeprom::Eeprom, rom_bank_byte};
+
+/// Accelerometer reading when level
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+/// Change of the accelerometer reading for a tilt of 1g
+const ACCELEROMETER_G: f32 = 0x70 as f32;
+/// Value of the latch registers after an erase
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+#[derive(Debug)]
+pub struct Mbc7 {
+    /// Both need to be set to access the registers at A000-AFFF
+    ram_enable_1: bool,
+    ram_enable_2: bool,
+    rom_bank_number: u8,
+    tilt: Tilt,
+    /// The latch only captures the accelerometer after it was erased
+    latch_ready: bool,
+    accelerometer_x: u16,
+    accelerometer_y: u16,
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub fn new() -> Self {
+        Self {
+            ram_enable_1: false,
+            ram_enable_2: false,
+            rom_bank_number: 1,
+            tilt: Tilt::default(),
+            latch_ready: false,
+            accelerometer_x: ACCELEROMETER_ERASED,
+            accelerometer_y: ACCELEROMETER_ERASED,
+            eeprom: Eeprom::default(),
+        }
+    }
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        self.tilt = tilt;
+    }
+    pub fn eeprom(&self) -> &Eeprom {
+        &self.eeprom
+    }
+    pub fn eeprom_mut(&mut self) -> &mut Eeprom {
+        &mut self.eeprom
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_number as usize,
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable_1 = data == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank_number = data & 0x7F,
+            0x4000..=0x5FFF => self.ram_enable_2 = data == 0x40,
+            _ => {}
+        }
+    }
+    fn registers_enabled(&self, addr: u16) -> bool {
+        self.ram_enable_1 && self.ram_enable_2 && addr < 0xB000
+    }
+    /// Registers are selected by A4-A7 and repeat every 0x100 bytes
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        if !self.registers_enabled(addr) {
+            return 0xFF;
+        }
+        match addr >> 4 & 0x0F {
+            0x2 => self.accelerometer_x as u8,
+            0x3 => (self.accelerometer_x >> 8) as u8,
+            0x4 => self.accelerometer_y as u8,
+            0x5 => (self.accelerometer_y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.data_out() as u8,
+            _ => 0xFF,
+        }
+    }
+    pub fn write_ram(&mut self, addr: u16, data: u8) {
+        if !self.registers_enabled(addr) {
+            return;
+        }
+        match addr >> 4 & 0x0F {
+            0x0 if data == 0x55 => {
+                self.latch_ready = true;
+                self.accelerometer_x = ACCELEROMETER_ERASED;
+                self.accelerometer_y = ACCELEROMETER_ERASED;
+            }
+            0x1 if data == 0xAA && self.latch_ready => {
+                self.latch_ready = false;
+                // X goes down when tilting right, Y goes up when tilting towards the bottom
+                self.accelerometer_x = accelerometer_reading(-self.tilt.x);
+                self.accelerometer_y = accelerometer_reading(self.tilt.y);
+            }
+            0x8 => self
+                .eeprom
+                .write_pins(data & 0x80 != 0, data & 0x40 != 0, data & 0x02 != 0),
+            _ => {}
+        }
+    }
+}
+
+fn accelerometer_reading(g: f32) -> u16 {
+    (ACCELEROMETER_CENTER + ACCELEROMETER_G * g.clamp(-1.0, 1.0)).round() as u16
+}
@@ -0,0 +1,167 @@
+use super::{ROM_BANK_SIZE, ram_bank_index, rom_bank_byte};
+
+/// The menu and the header are in the last 32 KiB of the ROM
+pub const MENU_SIZE: usize = 0x8000;
+
+/// Multicart mapper that boots into a menu in the last 32 KiB of the ROM
+/// The menu picks a game by setting the outer bank bits and masks, then locks them,
+/// after which the game sees an MBC1-like mapper limited to its own banks
+#[derive(Debug)]
+pub struct Mmm01 {
+    /// Set by writing bit 6 to 0000-1FFF, the outer bits and masks can't change anymore
+    locked: bool,
+    ram_enable: bool,
+    /// ROM bank bits 0-4, the bits set in `rom_bank_mask` are fixed once locked
+    rom_bank_low: u8,
+    /// ROM bank bits 5-6
+    rom_bank_mid: u8,
+    /// ROM bank bits 7-8
+    rom_bank_high: u8,
+    /// Fixes ROM bank bits 1-4
+    rom_bank_mask: u8,
+    /// RAM bank bits 0-1, the bits set in `ram_bank_mask` are fixed once locked
+    ram_bank_low: u8,
+    /// RAM bank bits 2-3
+    ram_bank_high: u8,
+    ram_bank_mask: u8,
+    /// MBC1 banking mode
+    banking_mode: bool,
+    /// The game can't change the banking mode
+    mode_write_disable: bool,
+    /// The RAM bank register acts as MBC1's BANK2, selecting ROM bank bits 5-6
+    multiplex: bool,
+}
+
+impl Mmm01 {
+    pub fn new() -> Self {
+        Self {
+            locked: false,
+            ram_enable: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            banking_mode: false,
+            mode_write_disable: false,
+            multiplex: false,
+        }
+    }
+    /// ROM bank bits 0-4 that the game can change
+    fn rom_low_writable(&self) -> u8 {
+        if self.locked {
+            !(self.rom_bank_mask << 1) & 0x1F
+        } else {
+            0x1F
+        }
+    }
+    fn ram_low_writable(&self) -> u8 {
+        if self.locked {
+            !self.ram_bank_mask & 0x03
+        } else {
+            0x03
+        }
+    }
+    /// ROM bank bits 5-6 and RAM bank bits 0-1, swapped by multiplexing
+    fn mid_bits(&self) -> (u8, u8) {
+        if !self.multiplex {
+            (self.rom_bank_mid, self.ram_bank_low)
+        } else if self.banking_mode {
+            (self.ram_bank_low, self.rom_bank_mid)
+        } else {
+            (self.ram_bank_low, 0)
+        }
+    }
+    fn rom_bank(&self, low: u8, mid: u8) -> usize {
+        (self.rom_bank_high as usize) << 7 | (mid as usize) << 5 | low as usize
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        if !self.locked {
+            let menu_bank = rom.len().saturating_sub(MENU_SIZE) / ROM_BANK_SIZE;
+            return rom_bank_byte(rom, menu_bank + addr as usize / ROM_BANK_SIZE, addr);
+        }
+        let (mid, _) = self.mid_bits();
+        let fixed_low = self.rom_bank_low & !self.rom_low_writable();
+        let bank = match addr {
+            0x0000..=0x3FFF if self.multiplex && !self.banking_mode => {
+                self.rom_bank(fixed_low, self.rom_bank_mid)
+            }
+            0x0000..=0x3FFF => self.rom_bank(fixed_low, mid),
+            _ => {
+                // Like MBC1, selecting bank 0 of the game maps bank 1
+                let low = if self.rom_bank_low & self.rom_low_writable() == 0 {
+                    self.rom_bank_low | 1
+                } else {
+                    self.rom_bank_low
+                };
+                self.rom_bank(low, mid)
+            }
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enable = data & 0x0F == 0x0A;
+                if !self.locked {
+                    self.ram_bank_mask = data >> 4 & 0x03;
+                    self.locked = data & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let writable = self.rom_low_writable();
+                self.rom_bank_low = self.rom_bank_low & !writable | data & writable;
+                if !self.locked {
+                    self.rom_bank_mid = data >> 5 & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                let writable = self.ram_low_writable();
+                self.ram_bank_low = self.ram_bank_low & !writable | data & writable;
+                if !self.locked {
+                    self.rom_bank_high = data >> 2 & 0x03;
+                    self.ram_bank_high = data >> 4 & 0x03;
+                    self.mode_write_disable = data & 0x40 != 0;
+                }
+            }
+            _ => {
+                if !(self.locked && self.mode_write_disable) {
+                    self.banking_mode = data & 0x01 != 0;
+                }
+                if !self.locked {
+                    self.rom_bank_mask = data >> 2 & 0x0F;
+                    self.multiplex = data & 0x40 != 0;
+                }
+            }
+        }
+    }
+    fn ram_bank(&self) -> usize {
+        let (_, low) = self.mid_bits();
+        (self.ram_bank_high as usize) << 2 | low as usize
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        ram_bank_index(ram, self.ram_bank(), addr).map_or(0xFF, |index| ram[index])
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        if let Some(index) = ram_bank_index(ram, self.ram_bank(), addr) {
+            ram[index] = data;
+        }
+    }
+}
+
+/// MMM01 ROMs have the menu's header, the one saying MMM01, at the start of the last 32 KiB
+pub fn header_offset(rom: &[u8]) -> Option<usize> {
+    let offset = rom
+        .len()
+        .checked_sub(MENU_SIZE)
+        .filter(|&offset| offset > 0)?;
+    matches!(rom.get(offset + 0x147), Some(0x0B..=0x0D)).then_some(offset)
+}
@@ -0,0 +1,256 @@
+pub mod camera;
+mod eeprom;
+mod flash;
+mod huc1;
+mod huc3;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod rtc;
+mod tama5;
+
+pub use camera::PocketCamera;
+pub use huc1::HuC1;
+pub use huc3::HuC3;
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use mbc6::Mbc6;
+pub use mbc7::Mbc7;
+pub use mmm01::{Mmm01, header_offset as mmm01_header_offset};
+pub use tama5::Tama5;
+
+use crate::game_boy::{infrared::InfraredPeer, input::Tilt, time::SystemTime};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+#[derive(Debug, Default)]
+pub enum MemoryBankController {
+    #[default]
+    None,
+    MBC1(Mbc1),
+    MBC2(Mbc2),
+    MBC3(Mbc3),
+    MBC5(Mbc5),
+    MBC6(Mbc6),
+    MBC7(Mbc7),
+    MMM01(Mmm01),
+    PocketCamera(PocketCamera),
+    Tama5(Tama5),
+    HuC1(HuC1),
+    HuC3(HuC3),
+}
+
+impl MemoryBankController {
+    pub fn none() -> Self {
+        Self::None
+    }
+    pub fn mbc1(rom: &[u8]) -> Self {
+        Self::MBC1(Mbc1::new(rom))
+    }
+    pub fn mbc2() -> Self {
+        Self::MBC2(Mbc2::new())
+    }
+    pub fn mbc3(timer: bool) -> Self {
+        Self::MBC3(Mbc3::new(timer))
+    }
+    pub fn mbc5(rumble: bool) -> Self {
+        Self::MBC5(Mbc5::new(rumble))
+    }
+    pub fn mbc6() -> Self {
+        Self::MBC6(Mbc6::new())
+    }
+    pub fn mbc7() -> Self {
+        Self::MBC7(Mbc7::new())
+    }
+    pub fn mmm01() -> Self {
+        Self::MMM01(Mmm01::new())
+    }
+    pub fn pocket_camera() -> Self {
+        Self::PocketCamera(PocketCamera::new())
+    }
+    pub fn tama5() -> Self {
+        Self::Tama5(Tama5::new())
+    }
+    pub fn huc1() -> Self {
+        Self::HuC1(HuC1::new())
+    }
+    pub fn huc3() -> Self {
+        Self::HuC3(HuC3::new())
+    }
+    /// Size of the RAM inside the MBC chip itself, which the header doesn't describe
+    pub fn builtin_ram_size(&self) -> Option<usize> {
+        match self {
+            Self::MBC2(_) => Some(mbc2::RAM_SIZE),
+            _ => None,
+        }
+    }
+}
+
+impl MemoryBankController {
+    /// Advance the clocks on the cartridge, called every M-cycle
+    pub fn tick(&mut self, ram: &mut [u8], time: SystemTime) {
+        match self {
+            Self::MBC3(mbc) => mbc.tick(time),
+            Self::PocketCamera(mbc) => mbc.tick(ram, time),
+            Self::Tama5(mbc) => mbc.tick(time),
+            Self::HuC3(mbc) => mbc.tick(time),
+            _ => {}
+        }
+    }
+    /// Whether the cartridge's rumble motor is on
+    pub fn rumble(&self) -> bool {
+        match self {
+            Self::MBC5(mbc) => mbc.rumble(),
+            _ => false,
+        }
+    }
+    /// Whether the cartridge's speaker is playing the alarm tone
+    pub fn tone(&self) -> bool {
+        match self {
+            Self::HuC3(mbc) => mbc.tone(),
+            _ => false,
+        }
+    }
+    /// Point the cartridge's IR port at `peer`, returns the previous peer,
+    /// or `peer` itself if the cartridge has no IR port
+    pub fn connect_infrared(&mut self, peer: Box<dyn InfraredPeer>) -> Box<dyn InfraredPeer> {
+        match self {
+            Self::HuC1(mbc) => mbc.connect_infrared(peer),
+            Self::HuC3(mbc) => mbc.connect_infrared(peer),
+            _ => peer,
+        }
+    }
+    /// What the cartridge's camera sees
+    pub fn set_camera_image(&mut self, image: camera::sensor::CameraImage) {
+        if let Self::PocketCamera(mbc) = self {
+            mbc.set_image(image);
+        }
+    }
+    /// Tilt seen by the cartridge's accelerometer
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        if let Self::MBC7(mbc) = self {
+            mbc.set_tilt(tilt);
+        }
+    }
+    /// State saved after the RAM in the save file
+    pub fn save_footer(&self) -> Option<Vec<u8>> {
+        match self {
+            Self::MBC3(mbc) => mbc.rtc().map(|rtc| rtc.footer().to_vec()),
+            Self::MBC6(mbc) => Some(mbc.flash().data().to_vec()),
+            Self::MBC7(mbc) => Some(mbc.eeprom().to_bytes()),
+            Self::HuC3(mbc) => Some(mbc.footer()),
+            Self::Tama5(mbc) => Some(mbc.save()),
+            _ => None,
+        }
+    }
+    /// Restore the state saved after the RAM, `footer` is empty for save files without one
+    pub fn load_save_footer(&mut self, footer: &[u8]) {
+        if footer.is_empty() {
+            return;
+        }
+        match self {
+            Self::MBC3(mbc) => {
+                if let Some(rtc) = mbc.rtc_mut() {
+                    rtc.load_footer(footer);
+                }
+            }
+            Self::MBC6(mbc) => mbc.flash_mut().load_data(footer),
+            Self::MBC7(mbc) => mbc.eeprom_mut().load_bytes(footer),
+            Self::HuC3(mbc) => mbc.load_footer(footer),
+            Self::Tama5(mbc) => mbc.load(footer),
+            _ => {}
+        }
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match self {
+            Self::MBC1(mbc) => mbc.read_rom(rom, addr),
+            Self::MBC2(mbc) => mbc.read_rom(rom, addr),
+            Self::MBC3(mbc) => mbc.read_rom(rom, addr),
+            Self::MBC5(mbc) => mbc.read_rom(rom, addr),
+            Self::MBC6(mbc) => mbc.read_rom(rom, addr),
+            Self::MBC7(mbc) => mbc.read_rom(rom, addr),
+            Self::MMM01(mbc) => mbc.read_rom(rom, addr),
+            Self::PocketCamera(mbc) => mbc.read_rom(rom, addr),
+            Self::Tama5(mbc) => mbc.read_rom(rom, addr),
+            Self::HuC1(mbc) => mbc.read_rom(rom, addr),
+            Self::HuC3(mbc) => mbc.read_rom(rom, addr),
+            // Mappers without their own banking logic expose the first 32 KiB
+            _ => rom.get(addr as usize).copied().unwrap_or(0xFF),
+        }
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        match self {
+            Self::MBC1(mbc) => mbc.write_rom(addr, data),
+            Self::MBC2(mbc) => mbc.write_rom(addr, data),
+            Self::MBC3(mbc) => mbc.write_rom(addr, data),
+            Self::MBC5(mbc) => mbc.write_rom(addr, data),
+            Self::MBC6(mbc) => mbc.write_rom(addr, data),
+            Self::MBC7(mbc) => mbc.write_rom(addr, data),
+            Self::MMM01(mbc) => mbc.write_rom(addr, data),
+            Self::PocketCamera(mbc) => mbc.write_rom(addr, data),
+            Self::Tama5(mbc) => mbc.write_rom(addr, data),
+            Self::HuC1(mbc) => mbc.write_rom(addr, data),
+            Self::HuC3(mbc) => mbc.write_rom(addr, data),
+            _ => log::trace!("ignored MBC write {data:02x} to {addr:04x}"),
+        }
+    }
+    pub fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        match self {
+            Self::MBC1(mbc) => mbc.read_ram(ram, addr),
+            Self::MBC2(mbc) => mbc.read_ram(ram, addr),
+            Self::MBC3(mbc) => mbc.read_ram(ram, addr),
+            Self::MBC5(mbc) => mbc.read_ram(ram, addr),
+            Self::MBC6(mbc) => mbc.read_ram(ram, addr),
+            Self::MBC7(mbc) => mbc.read_ram(addr),
+            Self::MMM01(mbc) => mbc.read_ram(ram, addr),
+            Self::PocketCamera(mbc) => mbc.read_ram(ram, addr),
+            Self::Tama5(mbc) => mbc.read_ram(addr),
+            Self::HuC1(mbc) => mbc.read_ram(ram, addr),
+            Self::HuC3(mbc) => mbc.read_ram(ram, addr),
+            _ => ram.get((addr & 0x1FFF) as usize).copied().unwrap_or(0xFF),
+        }
+    }
+    pub fn write_ram(&mut self, ram: &mut [u8], addr: u16, data: u8) {
+        match self {
+            Self::MBC1(mbc) => mbc.write_ram(ram, addr, data),
+            Self::MBC2(mbc) => mbc.write_ram(ram, addr, data),
+            Self::MBC3(mbc) => mbc.write_ram(ram, addr, data),
+            Self::MBC5(mbc) => mbc.write_ram(ram, addr, data),
+            Self::MBC6(mbc) => mbc.write_ram(ram, addr, data),
+            Self::MBC7(mbc) => mbc.write_ram(addr, data),
+            Self::MMM01(mbc) => mbc.write_ram(ram, addr, data),
+            Self::PocketCamera(mbc) => mbc.write_ram(ram, addr, data),
+            Self::Tama5(mbc) => mbc.write_ram(addr, data),
+            Self::HuC1(mbc) => mbc.write_ram(ram, addr, data),
+            Self::HuC3(mbc) => mbc.write_ram(ram, addr, data),
+            _ => {
+                if let Some(byte) = ram.get_mut((addr & 0x1FFF) as usize) {
+                    *byte = data;
+                }
+            }
+        }
+    }
+}
+
+/// Byte at `addr` of a 16 KiB ROM bank, bank numbers past the end of the ROM wrap around
+fn rom_bank_byte(rom: &[u8], bank: usize, addr: u16) -> u8 {
+    if rom.is_empty() {
+        return 0xFF;
+    }
+    rom[(bank * ROM_BANK_SIZE + (addr as usize & (ROM_BANK_SIZE - 1))) % rom.len()]
+}
+
+/// Index of `addr` in an 8 KiB RAM bank, bank numbers past the end of the RAM wrap around
+fn ram_bank_index(ram: &[u8], bank: usize, addr: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+    Some((bank * RAM_BANK_SIZE + (addr as usize & (RAM_BANK_SIZE - 1))) % ram.len())
+}
@@ -0,0 +1,202 @@
+use crate::game_boy::time::{BASE_SYSTEM_CLOCK, SystemTime};
+
+/// Size of the RTC footer appended to the save file, in the format shared by most emulators:
+/// the 5 registers then the 5 latched registers as u32 LE, then a u64 LE UNIX timestamp
+pub const FOOTER_SIZE: usize = 48;
+
+const DAYS: u16 = 512;
+
+/// Divides the system clock into the periods a cartridge clock counts, seconds or minutes,
+/// and tells how many went by while the game was off from the timestamp in its save file
+#[derive(Debug, Clone)]
+pub struct ClockDivider {
+    /// Master clocks in a period
+    period: u64,
+    /// Time the divider was last brought up to
+    now: SystemTime,
+    /// Master clocks counted towards the next period
+    counted: u64,
+}
+
+impl Default for ClockDivider {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl ClockDivider {
+    pub fn new(period_seconds: u64) -> Self {
+        Self {
+            period: BASE_SYSTEM_CLOCK * period_seconds,
+            now: SystemTime::new(),
+            counted: 0,
+        }
+    }
+    /// Count the time up to `time`, returns the number of periods completed
+    pub fn tick(&mut self, time: SystemTime) -> u64 {
+        self.counted += (time - self.now).master_clocks();
+        self.now = time;
+        let periods = self.counted / self.period;
+        self.counted %= self.period;
+        periods
+    }
+    /// Let the time up to `time` go by without counting it, for a stopped clock
+    pub fn skip(&mut self, time: SystemTime) {
+        self.now = time;
+    }
+    /// Restart the current period, when the clock is set
+    pub fn reset(&mut self) {
+        self.counted = 0;
+    }
+    /// UNIX timestamp to store in a save file, read back by [`ClockDivider::periods_since`]
+    pub fn timestamp(&self) -> [u8; 8] {
+        unix_time().to_le_bytes()
+    }
+    /// Whole periods that passed since `timestamp` was saved
+    pub fn periods_since(&self, timestamp: &[u8]) -> u64 {
+        let saved_at = u64::from_le_bytes(timestamp[..8].try_into().unwrap());
+        unix_time().saturating_sub(saved_at) * BASE_SYSTEM_CLOCK / self.period
+    }
+}
+
+/// Real-time clock of the MBC3, counting from the system clock's 32768 Hz crystal
+#[derive(Debug, Clone, Default)]
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 9 bits
+    days: u16,
+    halt: bool,
+    day_carry: bool,
+    /// S, M, H, DL, DH as they were on the last latch
+    latched: [u8; 5],
+    divider: ClockDivider,
+}
+
+impl Rtc {
+    /// Advance the clock up to `time`, the clock doesn't count while halted
+    pub fn tick(&mut self, time: SystemTime) {
+        if self.halt {
+            self.divider.skip(time);
+            return;
+        }
+        let seconds = self.divider.tick(time);
+        if seconds > 0 {
+            self.advance_seconds(seconds);
+        }
+    }
+    fn increment(&mut self) {
+        // Counters set out of range count up to their bit width and wrap without a carry
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days == DAYS {
+            self.days = 0;
+            self.day_carry = true;
+        }
+    }
+    fn advance_seconds(&mut self, mut seconds: u64) {
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.increment();
+            seconds -= 1;
+        }
+        let total = seconds
+            + self.seconds as u64
+            + self.minutes as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days as u64 * 86400;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        let days = total / 86400;
+        if days >= DAYS as u64 {
+            self.day_carry = true;
+        }
+        self.days = (days % DAYS as u64) as u16;
+    }
+    fn registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            (self.day_carry as u8) << 7 | (self.halt as u8) << 6 | (self.days >> 8) as u8,
+        ]
+    }
+    /// Copy the counters to the registers the CPU reads
+    pub fn latch(&mut self) {
+        self.latched = self.registers();
+    }
+    /// Read latched register 08-0C
+    pub fn read(&self, register: u8) -> u8 {
+        let unused = [0xC0, 0xC0, 0xE0, 0x00, 0x3E][(register - 0x08) as usize];
+        unused | self.latched[(register - 0x08) as usize]
+    }
+    /// Write counter register 08-0C, the write shows up in the latched register right away
+    pub fn write(&mut self, register: u8, data: u8) {
+        match register {
+            0x08 => {
+                self.seconds = data & 0x3F;
+                // Writing the seconds resets the divider
+                self.divider.reset();
+            }
+            0x09 => self.minutes = data & 0x3F,
+            0x0A => self.hours = data & 0x1F,
+            0x0B => self.days = self.days & 0x100 | data as u16,
+            _ => {
+                self.days = self.days & 0xFF | (data as u16 & 0x01) << 8;
+                self.halt = data & 0x40 != 0;
+                self.day_carry = data & 0x80 != 0;
+            }
+        }
+        self.latched[(register - 0x08) as usize] = self.registers()[(register - 0x08) as usize];
+    }
+    pub fn footer(&self) -> [u8; FOOTER_SIZE] {
+        let mut footer = [0; FOOTER_SIZE];
+        let registers = self.registers().into_iter().chain(self.latched);
+        for (chunk, register) in footer.chunks_exact_mut(4).zip(registers) {
+            chunk.copy_from_slice(&(register as u32).to_le_bytes());
+        }
+        footer[40..].copy_from_slice(&self.divider.timestamp());
+        footer
+    }
+    /// Restore the clock from a save file footer, counting the time that passed since it was saved
+    pub fn load_footer(&mut self, footer: &[u8]) {
+        if footer.len() < FOOTER_SIZE {
+            log::warn!("RTC footer is {} bytes, ignoring it", footer.len());
+            return;
+        }
+        let word = |index: usize| footer[index * 4];
+        for register in 0x08..=0x0C {
+            self.write(register, word((register - 0x08) as usize));
+        }
+        for (index, latched) in self.latched.iter_mut().enumerate() {
+            *latched = word(index + 5);
+        }
+        let elapsed = self.divider.periods_since(&footer[40..48]);
+        if !self.halt {
+            log::info!("RTC catching up on {elapsed} seconds");
+            self.advance_seconds(elapsed);
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
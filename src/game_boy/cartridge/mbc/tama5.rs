@@ -0,0 +1,296 @@
+use crate::game_boy::time::SystemTime;
+
+use super::{rom_bank_byte, rtc::ClockDivider};
+
+/// Battery-backed memory inside the TAMA5
+pub const MEMORY_SIZE: usize = 0x20;
+/// The memory, the 7 clock fields as bytes, whether the clock is stopped,
+/// then a u64 LE UNIX timestamp
+pub const SAVE_SIZE: usize = MEMORY_SIZE + 8 + 8;
+
+// There is no datasheet for the TAMA5 or the TAMA6 clock behind it, the register and command
+// map follows mGBA's TAMA5 mapper (src/gb/mbc/tama5.c), worked out from Tamagotchi 3
+const ROM_BANK_LOW: usize = 0x0;
+const ROM_BANK_HIGH: usize = 0x1;
+const DATA_LOW: usize = 0x4;
+const DATA_HIGH: usize = 0x5;
+/// Bit 0 is address bit 4, bits 1-3 the command
+const ADDRESS_HIGH: usize = 0x6;
+/// Writing it runs the command
+const ADDRESS_LOW: usize = 0x7;
+/// Reads 1 when the chip is ready for a command
+const STATUS: usize = 0xA;
+const OUTPUT_LOW: usize = 0xC;
+const OUTPUT_HIGH: usize = 0xD;
+
+const WRITE_MEMORY: u8 = 0x0;
+const READ_MEMORY: u8 = 0x1;
+/// Timer control, or reading the minutes and hours, selected by the address
+const TIMER: u8 = 0x2;
+/// Access to the TAMA6 clock registers, the address selects the operation
+const CLOCK: u8 = 0x4;
+
+/// Addresses of the timer command
+const STOP_TIMER: u8 = 0x0;
+const START_TIMER: u8 = 0x1;
+/// The minutes and hours digits are read from addresses 4-7
+const TIMER_DIGITS: u8 = 0x4;
+
+/// Addresses of the clock command: data low selects the register, data high is written
+const WRITE_CLOCK: u8 = 0x0;
+const READ_CLOCK: u8 = 0x1;
+
+/// Calendar clock, read and written one BCD digit at a time
+/// The digits are numbered like the registers of the TAMA6's first page
+#[derive(Debug, Clone)]
+struct Clock {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 0-6
+    weekday: u8,
+    /// From 1
+    day: u8,
+    /// From 1
+    month: u8,
+    /// 2 digits, leap years are the multiples of 4
+    year: u8,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            weekday: 0,
+            day: 1,
+            month: 1,
+            year: 0,
+        }
+    }
+}
+
+impl Clock {
+    fn days_in_month(&self) -> u8 {
+        match self.month {
+            2 if self.year.is_multiple_of(4) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+    fn advance_seconds(&mut self, seconds: u64) {
+        let total = self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600;
+        let total = total + seconds;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        for _ in 0..total / 86400 {
+            self.next_day();
+        }
+    }
+    fn next_day(&mut self) {
+        self.weekday = (self.weekday + 1) % 7;
+        self.day += 1;
+        if self.day <= self.days_in_month() {
+            return;
+        }
+        self.day = 1;
+        self.month += 1;
+        if self.month <= 12 {
+            return;
+        }
+        self.month = 1;
+        self.year = (self.year + 1) % 100;
+    }
+    /// Field and whether `digit` is its tens digit
+    fn field(&mut self, digit: u8) -> Option<(&mut u8, bool)> {
+        Some(match digit {
+            0x0 => (&mut self.seconds, false),
+            0x1 => (&mut self.seconds, true),
+            0x2 => (&mut self.minutes, false),
+            0x3 => (&mut self.minutes, true),
+            0x4 => (&mut self.hours, false),
+            0x5 => (&mut self.hours, true),
+            0x6 => (&mut self.weekday, false),
+            0x7 => (&mut self.day, false),
+            0x8 => (&mut self.day, true),
+            0x9 => (&mut self.month, false),
+            0xA => (&mut self.month, true),
+            0xB => (&mut self.year, false),
+            0xC => (&mut self.year, true),
+            _ => return None,
+        })
+    }
+    fn read_digit(&mut self, digit: u8) -> u8 {
+        match self.field(digit) {
+            Some((field, true)) => *field / 10,
+            Some((field, false)) => *field % 10,
+            None => 0x0,
+        }
+    }
+    fn write_digit(&mut self, digit: u8, value: u8) {
+        match self.field(digit) {
+            Some((field, true)) => *field = value * 10 + *field % 10,
+            Some((field, false)) => *field = *field / 10 * 10 + value,
+            None => log::debug!("write {value:x} to unknown TAMA5 clock digit {digit:x}"),
+        }
+    }
+    fn to_bytes(&self) -> [u8; 7] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.weekday,
+            self.day,
+            self.month,
+            self.year,
+        ]
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            seconds: bytes[0] % 60,
+            minutes: bytes[1] % 60,
+            hours: bytes[2] % 24,
+            weekday: bytes[3] % 7,
+            day: bytes[4].clamp(1, 31),
+            month: bytes[5].clamp(1, 12),
+            year: bytes[6] % 100,
+        }
+    }
+}
+
+/// Bandai TAMA5, used by Tamagotchi 3
+/// Everything goes through 4-bit registers: A001 selects one, A000 writes or reads it
+#[derive(Debug)]
+pub struct Tama5 {
+    registers: [u8; 0x10],
+    selected: usize,
+    /// Result of the last read command
+    output: u8,
+    memory: [u8; MEMORY_SIZE],
+    clock: Clock,
+    /// The game stops the clock while setting it
+    stopped: bool,
+    /// Counts seconds
+    divider: ClockDivider,
+}
+
+impl Tama5 {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 0x10],
+            selected: 0,
+            output: 0,
+            memory: [0; MEMORY_SIZE],
+            clock: Clock::default(),
+            stopped: false,
+            divider: ClockDivider::new(1),
+        }
+    }
+    pub fn tick(&mut self, time: SystemTime) {
+        if self.stopped {
+            self.divider.skip(time);
+            return;
+        }
+        let seconds = self.divider.tick(time);
+        if seconds > 0 {
+            self.clock.advance_seconds(seconds);
+        }
+    }
+    fn rom_bank(&self) -> usize {
+        ((self.registers[ROM_BANK_HIGH] & 0x01) << 4 | self.registers[ROM_BANK_LOW]) as usize
+    }
+    fn run_command(&mut self) {
+        let address =
+            ((self.registers[ADDRESS_HIGH] & 0x01) << 4 | self.registers[ADDRESS_LOW]) as usize;
+        let data = self.registers[DATA_HIGH] << 4 | self.registers[DATA_LOW];
+        match self.registers[ADDRESS_HIGH] >> 1 {
+            WRITE_MEMORY => self.memory[address] = data,
+            READ_MEMORY => self.output = self.memory[address],
+            TIMER => match address as u8 {
+                STOP_TIMER => self.stopped = true,
+                START_TIMER => {
+                    self.stopped = false;
+                    // Starting the timer restarts the current minute
+                    self.clock.seconds = 0;
+                    self.divider.reset();
+                }
+                digit @ TIMER_DIGITS..=0x7 => {
+                    let digit = digit - TIMER_DIGITS + 0x2;
+                    self.output = self.clock.read_digit(digit);
+                }
+                _ => log::debug!("unknown TAMA5 timer command {address:x}"),
+            },
+            CLOCK => {
+                let register = self.registers[DATA_LOW];
+                match self.registers[ADDRESS_LOW] {
+                    WRITE_CLOCK => self.clock.write_digit(register, self.registers[DATA_HIGH]),
+                    READ_CLOCK => self.output = self.clock.read_digit(register),
+                    operation => log::debug!("unknown TAMA5 clock operation {operation:x}"),
+                }
+            }
+            command => log::debug!("unknown TAMA5 command {command:x}"),
+        }
+    }
+    pub fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank(),
+        };
+        rom_bank_byte(rom, bank, addr)
+    }
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        log::trace!("ignored TAMA5 write {data:02x} to {addr:04x}");
+    }
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        if addr != 0xA000 {
+            return 0xFF;
+        }
+        match self.selected {
+            STATUS => 0xF1,
+            OUTPUT_LOW => 0xF0 | self.output & 0x0F,
+            OUTPUT_HIGH => 0xF0 | self.output >> 4,
+            _ => 0xFF,
+        }
+    }
+    pub fn write_ram(&mut self, addr: u16, data: u8) {
+        match addr {
+            0xA000 => {
+                self.registers[self.selected] = data & 0x0F;
+                if self.selected == ADDRESS_LOW {
+                    self.run_command();
+                }
+            }
+            0xA001 => self.selected = (data & 0x0F) as usize,
+            _ => {}
+        }
+    }
+    pub fn save(&self) -> Vec<u8> {
+        let mut save = Vec::with_capacity(SAVE_SIZE);
+        save.extend(self.memory);
+        save.extend(self.clock.to_bytes());
+        save.push(self.stopped as u8);
+        save.extend(self.divider.timestamp());
+        save
+    }
+    /// Restore the memory and the clock, counting the time that passed since the save
+    /// if the clock was running
+    pub fn load(&mut self, save: &[u8]) {
+        if save.len() < SAVE_SIZE {
+            log::warn!("TAMA5 save is {} bytes, ignoring it", save.len());
+            return;
+        }
+        self.memory.copy_from_slice(&save[..MEMORY_SIZE]);
+        self.clock = Clock::from_bytes(&save[MEMORY_SIZE..MEMORY_SIZE + 7]);
+        self.stopped = save[MEMORY_SIZE + 7] != 0;
+        if self.stopped {
+            return;
+        }
+        self.clock.advance_seconds(
+            self.divider
+                .periods_since(&save[MEMORY_SIZE + 8..SAVE_SIZE]),
+        );
+    }
+}
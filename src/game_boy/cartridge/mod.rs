@@ -1,15 +1,24 @@
 mod mbc;
+#[cfg(test)]
+mod tests;
+
+pub use mbc::camera::{photos::save_photos, sensor::CameraImage};
 
 use compact_str::CompactString;
 use mbc::MemoryBankController;
 use thiserror::Error;
 
+use super::{infrared::InfraredPeer, input::Tilt, time::SystemTime};
+
 pub type Rom = Box<[u8]>;
 
 #[derive(Debug)]
 pub struct Cartridge {
     rom: Rom,
     ram: Box<[u8]>,
+    mbc: MemoryBankController,
+    battery: bool,
+    cgb_flag: u8,
 }
 
 #[derive(Debug, Error)]
@@ -20,7 +29,8 @@ pub enum CartridgeParseError {
 
 impl Cartridge {
     pub fn from_rom(rom: Rom) -> Result<Self, CartridgeParseError> {
-        let read = |addr| rom.get(addr).copied().unwrap_or(0x00);
+        let header_offset = mbc::mmm01_header_offset(&rom).unwrap_or(0);
+        let read = |addr| rom.get(header_offset + addr).copied().unwrap_or(0x00);
         let read_ascii = |start, end| {
             let mut ascii = CompactString::new("");
             for addr in start..=end {
@@ -36,6 +46,9 @@ impl Cartridge {
             ascii
         };
         log::info!("Loading cartridge...");
+        if header_offset != 0 {
+            log::info!("header found at {header_offset:x}");
+        }
         let title = read_ascii(0x134, 0x013E);
         log::info!("title = \"{title}\"");
         let manufacturer_code = read_ascii(0x13F, 0x0142);
@@ -76,43 +89,124 @@ impl Cartridge {
         };
         let (mbc, has_ram, battery) = match cartridge_type {
             0x00 => (MemoryBankController::none(), false, false),
-            0x01 => (MemoryBankController::mbc1(), false, false),
-            0x02 => (MemoryBankController::mbc1(), true, false),
-            0x03 => (MemoryBankController::mbc1(), true, true),
-            0x05 => (MemoryBankController::MBC2, false, false),
-            0x06 => (MemoryBankController::MBC2, false, true),
+            0x01 => (MemoryBankController::mbc1(&rom), false, false),
+            0x02 => (MemoryBankController::mbc1(&rom), true, false),
+            0x03 => (MemoryBankController::mbc1(&rom), true, true),
+            0x05 => (MemoryBankController::mbc2(), true, false),
+            0x06 => (MemoryBankController::mbc2(), true, true),
             // 0x08 => (MemoryBankController::None, false, false),
             // 0x09 => (MemoryBankController::None, false, false),
-            0x0B => (MemoryBankController::MMM01, false, false),
-            0x0C => (MemoryBankController::MMM01, true, false),
-            0x0D => (MemoryBankController::MMM01, true, true),
-            0x0F => (MemoryBankController::MBC3, false, true),
-            0x10 => (MemoryBankController::MBC3, true, true),
-            0x11 => (MemoryBankController::MBC3, false, false),
-            0x12 => (MemoryBankController::MBC3, true, false),
-            0x13 => (MemoryBankController::MBC3, true, true),
-            0x19 => (MemoryBankController::MBC5, false, false),
-            0x1A => (MemoryBankController::MBC5, true, false),
-            0x1B => (MemoryBankController::MBC5, true, true),
-            0x1C => (MemoryBankController::MBC5, false, false),
-            0x1D => (MemoryBankController::MBC5, true, false),
-            0x1E => (MemoryBankController::MBC5, true, true),
-            0x20 => (MemoryBankController::MBC6, false, false),
-            0x22 => (MemoryBankController::MBC7, true, true),
-            0xFE => (MemoryBankController::HuC3, false, false),
-            0xFF => (MemoryBankController::HuC1, true, true),
+            0x0B => (MemoryBankController::mmm01(), false, false),
+            0x0C => (MemoryBankController::mmm01(), true, false),
+            0x0D => (MemoryBankController::mmm01(), true, true),
+            0x0F => (MemoryBankController::mbc3(true), false, true),
+            0x10 => (MemoryBankController::mbc3(true), true, true),
+            0x11 => (MemoryBankController::mbc3(false), false, false),
+            0x12 => (MemoryBankController::mbc3(false), true, false),
+            0x13 => (MemoryBankController::mbc3(false), true, true),
+            0x19 => (MemoryBankController::mbc5(false), false, false),
+            0x1A => (MemoryBankController::mbc5(false), true, false),
+            0x1B => (MemoryBankController::mbc5(false), true, true),
+            0x1C => (MemoryBankController::mbc5(true), false, false),
+            0x1D => (MemoryBankController::mbc5(true), true, false),
+            0x1E => (MemoryBankController::mbc5(true), true, true),
+            // The flash is saved after the RAM
+            0x20 => (MemoryBankController::mbc6(), true, true),
+            // The EEPROM is saved instead of RAM
+            0x22 => (MemoryBankController::mbc7(), false, true),
+            0xFC => (MemoryBankController::pocket_camera(), true, true),
+            // The memory inside the TAMA5 is saved instead of RAM
+            0xFD => (MemoryBankController::tama5(), false, true),
+            0xFE => (MemoryBankController::huc3(), true, true),
+            0xFF => (MemoryBankController::huc1(), true, true),
             _ => return Err(CartridgeParseError::UnknownCartridgeType(cartridge_type)),
         };
         let ram = {
-            // 16 KiB / bank
-            let ram_size = if has_ram {
-                ram_bank_count * (16 << 10)
-            } else {
-                0
+            // 8 KiB / bank
+            let ram_size = match mbc.builtin_ram_size() {
+                _ if !has_ram => 0,
+                Some(size) => size,
+                None => ram_bank_count * (8 << 10),
             };
-            let ram_bytes = vec![0; ram_size].into_boxed_slice();
-            ram_bytes
+            vec![0; ram_size].into_boxed_slice()
         };
-        Ok(Self { rom, ram })
+        Ok(Self {
+            rom,
+            ram,
+            mbc,
+            battery,
+            cgb_flag,
+        })
+    }
+    /// Read from the ROM area (0000-7FFF)
+    pub fn read_rom(&self, addr: u16) -> u8 {
+        self.mbc.read_rom(&self.rom, addr)
+    }
+    /// Write to the ROM area (0000-7FFF), which controls the MBC
+    pub fn write_rom(&mut self, addr: u16, data: u8) {
+        self.mbc.write_rom(addr, data)
+    }
+    /// Read from the external RAM area (A000-BFFF)
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        self.mbc.read_ram(&self.ram, addr)
+    }
+    /// Write to the external RAM area (A000-BFFF)
+    pub fn write_ram(&mut self, addr: u16, data: u8) {
+        self.mbc.write_ram(&mut self.ram, addr, data)
+    }
+    pub fn has_battery(&self) -> bool {
+        self.battery
+    }
+    /// Contents of the battery-backed memory, to be written to a save file
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+        let mut data = self.ram.to_vec();
+        data.extend(self.mbc.save_footer().unwrap_or_default());
+        Some(data)
+    }
+    /// Restore the battery-backed memory from a save file
+    /// Save files of the wrong size are loaded as far as they go
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if data.len() < self.ram.len() {
+            log::warn!(
+                "save file is {:x} bytes, expected {:x}",
+                data.len(),
+                self.ram.len()
+            );
+        }
+        let (ram, footer) = data.split_at(data.len().min(self.ram.len()));
+        self.ram[..ram.len()].copy_from_slice(ram);
+        self.mbc.load_save_footer(footer);
+    }
+    /// Whether the rumble motor is on
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+    /// Whether the alarm tone is playing
+    pub fn tone(&self) -> bool {
+        self.mbc.tone()
+    }
+    /// Point the IR port at `peer`, returns the previous peer,
+    /// or `peer` itself if the cartridge has no IR port
+    pub fn connect_infrared(&mut self, peer: Box<dyn InfraredPeer>) -> Box<dyn InfraredPeer> {
+        self.mbc.connect_infrared(peer)
+    }
+    /// What the camera sees, for the Game Boy Camera
+    pub fn set_camera_image(&mut self, image: CameraImage) {
+        self.mbc.set_camera_image(image);
+    }
+    /// Tilt seen by the cartridge's accelerometer
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        self.mbc.set_tilt(tilt);
+    }
+    /// Advance the cartridge's clocks up to `time`
+    pub fn tick(&mut self, time: SystemTime) {
+        self.mbc.tick(&mut self.ram, time);
+    }
+    /// Header flag 0x80 (CGB enhanced) or 0xC0 (CGB only)
+    pub fn supports_cgb(&self) -> bool {
+        self.cgb_flag & 0x80 != 0
     }
 }
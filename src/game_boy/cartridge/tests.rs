@@ -0,0 +1,592 @@
+use crate::game_boy::{
+    System,
+    image::{GrayImage, save_png},
+    infrared::infrared_beam,
+    input::Tilt,
+    time::SystemTime,
+};
+
+use super::{
+    CameraImage, Cartridge,
+    mbc::camera::photos::{extract_photos, save_photos},
+};
+
+const LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// ROM of `banks` 16 KiB banks whose first byte is the bank number
+fn banked_rom(banks: usize, cartridge_type: u8, ram_size: u8) -> Box<[u8]> {
+    let mut rom = vec![0; banks * 0x4000];
+    for (bank, data) in rom.chunks_mut(0x4000).enumerate() {
+        data[0] = bank as u8;
+    }
+    rom[0x104..0x134].copy_from_slice(&LOGO);
+    rom[0x147] = cartridge_type;
+    rom[0x148] = (banks / 2).trailing_zeros() as u8;
+    rom[0x149] = ram_size;
+    rom.into_boxed_slice()
+}
+
+#[test]
+fn mbc1_rom_banking() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(64, 0x01, 0x00)).unwrap();
+    assert_eq!(cartridge.read_rom(0x4000), 1);
+    cartridge.write_rom(0x2000, 0x05);
+    assert_eq!(cartridge.read_rom(0x4000), 5);
+    // Bank 0 maps bank 1, and so does every multiple of 0x20
+    cartridge.write_rom(0x2000, 0x00);
+    assert_eq!(cartridge.read_rom(0x4000), 1);
+    cartridge.write_rom(0x4000, 0x01);
+    assert_eq!(cartridge.read_rom(0x4000), 0x21);
+    // Only the lower 5 bits are wired
+    cartridge.write_rom(0x2000, 0xE3);
+    assert_eq!(cartridge.read_rom(0x4000), 0x23);
+    // Mode 1 applies BANK2 to 0000-3FFF
+    assert_eq!(cartridge.read_rom(0x0000), 0);
+    cartridge.write_rom(0x6000, 0x01);
+    assert_eq!(cartridge.read_rom(0x0000), 0x20);
+    // Banks past the end of the ROM wrap around
+    cartridge.write_rom(0x4000, 0x03);
+    assert_eq!(cartridge.read_rom(0x4000), 0x23);
+}
+
+#[test]
+fn mbc1_ram_banking() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(4, 0x03, 0x03)).unwrap();
+    cartridge.write_ram(0xA000, 0x12);
+    assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_ram(0xA000, 0x12);
+    assert_eq!(cartridge.read_ram(0xA000), 0x12);
+    // BANK2 only selects the RAM bank in mode 1
+    cartridge.write_rom(0x4000, 0x02);
+    assert_eq!(cartridge.read_ram(0xA000), 0x12);
+    cartridge.write_rom(0x6000, 0x01);
+    assert_eq!(cartridge.read_ram(0xA000), 0x00);
+    cartridge.write_ram(0xA000, 0x34);
+    cartridge.write_rom(0x6000, 0x00);
+    assert_eq!(cartridge.read_ram(0xA000), 0x12);
+    cartridge.write_rom(0x0000, 0x00);
+    assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc1m_multicart() {
+    let mut rom = banked_rom(64, 0x01, 0x00).into_vec();
+    rom[0x10 * 0x4000 + 0x104..0x10 * 0x4000 + 0x134].copy_from_slice(&LOGO);
+    let mut cartridge = Cartridge::from_rom(rom.into_boxed_slice()).unwrap();
+    // BANK2 is shifted by 4 and BANK1 bit 4 is ignored
+    cartridge.write_rom(0x2000, 0x12);
+    cartridge.write_rom(0x4000, 0x01);
+    assert_eq!(cartridge.read_rom(0x4000), 0x12);
+    cartridge.write_rom(0x6000, 0x01);
+    assert_eq!(cartridge.read_rom(0x0000), 0x10);
+}
+
+#[test]
+fn mbc2_registers_and_ram() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(16, 0x06, 0x00)).unwrap();
+    // A8 set selects the ROM bank register
+    cartridge.write_rom(0x2100, 0x07);
+    assert_eq!(cartridge.read_rom(0x4000), 7);
+    cartridge.write_rom(0x0100, 0x00);
+    assert_eq!(cartridge.read_rom(0x4000), 1);
+    // A8 clear is RAM enable, even in 2000-3FFF
+    cartridge.write_rom(0x2000, 0x0A);
+    cartridge.write_ram(0xA000, 0x5C);
+    assert_eq!(cartridge.read_ram(0xA000), 0xFC);
+    // The 512 cells repeat across A000-BFFF
+    assert_eq!(cartridge.read_ram(0xA200), 0xFC);
+    assert_eq!(cartridge.read_ram(0xBE00), 0xFC);
+    cartridge.write_ram(0xA1FF, 0x03);
+    assert_eq!(cartridge.read_ram(0xB3FF), 0xF3);
+
+    let save = cartridge.save_data().unwrap();
+    assert_eq!(save.len(), 0x200);
+    let mut loaded = Cartridge::from_rom(banked_rom(16, 0x06, 0x00)).unwrap();
+    loaded.load_save_data(&save);
+    loaded.write_rom(0x0000, 0x0A);
+    assert_eq!(loaded.read_ram(0xA000), 0xFC);
+    cartridge.write_rom(0x0000, 0x00);
+    assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+}
+
+/// Select RTC register `register` and read it after latching
+fn read_rtc(cartridge: &mut Cartridge, register: u8) -> u8 {
+    cartridge.write_rom(0x6000, 0x00);
+    cartridge.write_rom(0x6000, 0x01);
+    cartridge.write_rom(0x4000, register);
+    cartridge.read_ram(0xA000)
+}
+
+#[test]
+fn mbc3_rtc_counts_and_latches() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(4, 0x10, 0x03)).unwrap();
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.tick(SystemTime::from_seconds(61.5));
+    assert_eq!(read_rtc(&mut cartridge, 0x08), 0xC1);
+    assert_eq!(read_rtc(&mut cartridge, 0x09), 0xC1);
+    // The latched value stays until the next 00 -> 01 write
+    cartridge.tick(SystemTime::from_seconds(63.0));
+    assert_eq!(cartridge.read_ram(0xA000), 0xC1);
+    cartridge.write_rom(0x6000, 0x01);
+    assert_eq!(cartridge.read_ram(0xA000), 0xC1);
+    assert_eq!(read_rtc(&mut cartridge, 0x09), 0xC1);
+    assert_eq!(read_rtc(&mut cartridge, 0x08), 0xC3);
+
+    // Halted, then set to the last second of day 511
+    cartridge.write_rom(0x4000, 0x0C);
+    cartridge.write_ram(0xA000, 0x41);
+    for (register, value) in [(0x08, 59), (0x09, 59), (0x0A, 23), (0x0B, 0xFF)] {
+        cartridge.write_rom(0x4000, register);
+        cartridge.write_ram(0xA000, value);
+    }
+    cartridge.tick(SystemTime::from_seconds(70.0));
+    assert_eq!(read_rtc(&mut cartridge, 0x08), 0xC0 | 59);
+    cartridge.write_rom(0x4000, 0x0C);
+    cartridge.write_ram(0xA000, 0x01);
+    cartridge.tick(SystemTime::from_seconds(71.0));
+    // The day counter overflows into the sticky carry bit
+    assert_eq!(read_rtc(&mut cartridge, 0x08), 0xC0);
+    assert_eq!(read_rtc(&mut cartridge, 0x0A), 0xE0);
+    assert_eq!(read_rtc(&mut cartridge, 0x0B), 0x00);
+    assert_eq!(read_rtc(&mut cartridge, 0x0C), 0xBE);
+    // RAM banks are still there
+    cartridge.write_rom(0x4000, 0x02);
+    cartridge.write_ram(0xA000, 0x42);
+    assert_eq!(cartridge.read_ram(0xA000), 0x42);
+}
+
+#[test]
+fn mbc3_rtc_save_footer() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(4, 0x10, 0x03)).unwrap();
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_rom(0x4000, 0x09);
+    cartridge.write_ram(0xA000, 10);
+    let mut save = cartridge.save_data().unwrap();
+    assert_eq!(save.len(), 0x8000 + 48);
+    assert_eq!(save[0x8000 + 4..0x8000 + 8], [10, 0, 0, 0]);
+    // Saved two minutes ago
+    let footer = &mut save[0x8000..];
+    let saved_at = u64::from_le_bytes(footer[40..48].try_into().unwrap());
+    footer[40..48].copy_from_slice(&(saved_at - 120).to_le_bytes());
+
+    let mut loaded = Cartridge::from_rom(banked_rom(4, 0x10, 0x03)).unwrap();
+    loaded.load_save_data(&save);
+    loaded.write_rom(0x0000, 0x0A);
+    assert!(read_rtc(&mut loaded, 0x09) & 0x3F >= 12);
+}
+
+#[test]
+fn mbc5_banking() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(512, 0x1B, 0x04)).unwrap();
+    cartridge.write_rom(0x2000, 0x00);
+    assert_eq!(cartridge.read_rom(0x4000), 0);
+    cartridge.write_rom(0x3000, 0x01);
+    assert_eq!(cartridge.read_rom(0x4000), 0);
+    cartridge.write_rom(0x2000, 0x2A);
+    assert_eq!(cartridge.read_rom(0x4000), 0x2A);
+    cartridge.write_rom(0x3000, 0x00);
+    cartridge.write_rom(0x2000, 0x05);
+    assert_eq!(cartridge.read_rom(0x4000), 5);
+
+    // Only 0A enables the RAM
+    cartridge.write_rom(0x0000, 0x1A);
+    assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    cartridge.write_rom(0x0000, 0x0A);
+    for bank in 0..16 {
+        cartridge.write_rom(0x4000, bank);
+        cartridge.write_ram(0xA123, bank);
+    }
+    for bank in 0..16 {
+        cartridge.write_rom(0x4000, bank);
+        assert_eq!(cartridge.read_ram(0xA123), bank);
+    }
+    assert!(!cartridge.rumble());
+}
+
+#[test]
+fn mbc5_rumble_events() {
+    let mut rom = banked_rom(4, 0x1C, 0x00).into_vec();
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    let program = [
+        0x3E, 0x08, 0xEA, 0x00, 0x40, // LD A,08; LD (4000),A
+        0x3E, 0x08, 0xEA, 0x00, 0x40, // Same again, no change
+        0xAF, 0xEA, 0x00, 0x40, // XOR A; LD (4000),A
+        0x18, 0xFE, // JR -2
+    ];
+    rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+    let mut system = System::now(rom.into_boxed_slice()).unwrap();
+    let mut changes = Vec::new();
+    while system.time() < SystemTime::from_system_clocks(100) {
+        if system.step().rumble_changed() {
+            changes.push(system.rumble());
+        }
+    }
+    assert_eq!(changes, [true, false]);
+}
+
+/// Clock `bits` into the MBC7 EEPROM, MSB first, returns what DO showed after each bit
+fn eeprom_bits(cartridge: &mut Cartridge, value: u32, bits: u32) -> u32 {
+    let mut out = 0;
+    for bit in (0..bits).rev() {
+        let di = ((value >> bit) & 1) as u8;
+        cartridge.write_ram(0xA080, 0x80 | di << 1);
+        cartridge.write_ram(0xA080, 0xC0 | di << 1);
+        out = out << 1 | (cartridge.read_ram(0xA080) & 0x01) as u32;
+    }
+    cartridge.write_ram(0xA080, 0x00);
+    out
+}
+
+/// Start bit, opcode and address
+fn eeprom_command(opcode: u32, address: u32) -> u32 {
+    1 << 10 | opcode << 8 | address
+}
+
+#[test]
+fn mbc7_accelerometer_and_eeprom() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(64, 0x22, 0x00)).unwrap();
+    cartridge.write_rom(0x0000, 0x0A);
+    assert_eq!(cartridge.read_ram(0xA020), 0xFF);
+    cartridge.write_rom(0x4000, 0x40);
+
+    cartridge.set_tilt(Tilt { x: 1.0, y: 0.0 });
+    // Latching needs an erase first
+    cartridge.write_ram(0xA010, 0xAA);
+    assert_eq!(cartridge.read_ram(0xA030), 0x80);
+    cartridge.write_ram(0xA000, 0x55);
+    cartridge.write_ram(0xA010, 0xAA);
+    let x = u16::from_le_bytes([cartridge.read_ram(0xA020), cartridge.read_ram(0xA030)]);
+    let y = u16::from_le_bytes([cartridge.read_ram(0xA040), cartridge.read_ram(0xA050)]);
+    assert_eq!((x, y), (0x81D0 - 0x70, 0x81D0));
+    assert_eq!(cartridge.read_ram(0xA160), 0x00);
+
+    // EWEN, then WRITE 1234 to word 5 and read it back
+    eeprom_bits(&mut cartridge, eeprom_command(0b00, 0xC0), 11);
+    eeprom_bits(&mut cartridge, eeprom_command(0b01, 5) << 16 | 0x1234, 27);
+    // A dummy 0 comes before the data
+    let read = eeprom_bits(&mut cartridge, eeprom_command(0b10, 5) << 16, 27);
+    assert_eq!(read & 0x1FFFF, 0x1234);
+    // EWDS, after which ERASE is ignored
+    eeprom_bits(&mut cartridge, eeprom_command(0b00, 0x00), 11);
+    eeprom_bits(&mut cartridge, eeprom_command(0b11, 5), 11);
+
+    let save = cartridge.save_data().unwrap();
+    assert_eq!(save.len(), 256);
+    assert_eq!(save[10..12], [0x34, 0x12]);
+}
+
+/// Send a flash command through the MBC6 windows, with bank 2 at 4000 and bank 1 at 6000
+fn flash_command(cartridge: &mut Cartridge, command: u8) {
+    cartridge.write_rom(0x2000, 0x02);
+    cartridge.write_rom(0x3000, 0x01);
+    cartridge.write_rom(0x5555, 0xAA);
+    cartridge.write_rom(0x6AAA, 0x55);
+    cartridge.write_rom(0x5555, command);
+}
+
+#[test]
+fn mbc6_windows_and_flash() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(64, 0x20, 0x03)).unwrap();
+    // 8 KiB banks, so 16 KiB bank n starts at 8 KiB bank 2n
+    cartridge.write_rom(0x2000, 0x06);
+    cartridge.write_rom(0x3000, 0x0A);
+    assert_eq!(cartridge.read_rom(0x4000), 3);
+    assert_eq!(cartridge.read_rom(0x6000), 5);
+
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_rom(0x0400, 0x01);
+    cartridge.write_rom(0x0800, 0x02);
+    cartridge.write_ram(0xA000, 0x11);
+    cartridge.write_ram(0xB000, 0x22);
+    cartridge.write_rom(0x0800, 0x01);
+    assert_eq!(cartridge.read_ram(0xB000), 0x11);
+
+    cartridge.write_rom(0x2800, 0x08);
+    cartridge.write_rom(0x3800, 0x08);
+    cartridge.write_rom(0x1000, 0x01);
+    cartridge.write_rom(0x0C00, 0x01);
+    flash_command(&mut cartridge, 0x90);
+    cartridge.write_rom(0x2000, 0x00);
+    assert_eq!(cartridge.read_rom(0x4000), 0xC2);
+    cartridge.write_rom(0x4000, 0xF0);
+
+    flash_command(&mut cartridge, 0xA0);
+    cartridge.write_rom(0x2000, 0x10);
+    cartridge.write_rom(0x4123, 0x5A);
+    assert_eq!(cartridge.read_rom(0x4123), 0x5A);
+    // The ROM window is still there
+    cartridge.write_rom(0x2800, 0x00);
+    assert_eq!(cartridge.read_rom(0x4000), 8);
+
+    let save = cartridge.save_data().unwrap();
+    assert_eq!(save.len(), 0x8000 + (1 << 20));
+    assert_eq!(save[0x8000 + 0x10 * 0x2000 + 0x123], 0x5A);
+
+    cartridge.write_rom(0x2800, 0x08);
+    // Sector erase needs a second unlock sequence
+    flash_command(&mut cartridge, 0x80);
+    cartridge.write_rom(0x5555, 0xAA);
+    cartridge.write_rom(0x6AAA, 0x55);
+    cartridge.write_rom(0x2000, 0x10);
+    cartridge.write_rom(0x4000, 0x30);
+    assert_eq!(cartridge.read_rom(0x4123), 0xFF);
+}
+
+#[test]
+fn hudson_infrared_beam() {
+    let mut huc1 = Cartridge::from_rom(banked_rom(4, 0xFF, 0x03)).unwrap();
+    let mut huc3 = Cartridge::from_rom(banked_rom(4, 0xFE, 0x03)).unwrap();
+    let (first, second) = infrared_beam();
+    huc1.connect_infrared(Box::new(first));
+    huc3.connect_infrared(Box::new(second));
+    huc1.write_rom(0x0000, 0x0E);
+    huc3.write_rom(0x0000, 0x0E);
+    assert_eq!(huc3.read_ram(0xA000), 0xC0);
+    huc1.write_ram(0xA000, 0x01);
+    assert_eq!(huc3.read_ram(0xA000), 0xC1);
+    assert_eq!(huc1.read_ram(0xA000), 0xC0);
+    huc3.write_ram(0xA000, 0x01);
+    assert_eq!(huc1.read_ram(0xA000), 0xC1);
+    huc1.write_ram(0xA000, 0x00);
+    assert_eq!(huc3.read_ram(0xA000), 0xC0);
+    // Back in RAM mode the LED stays as it was
+    huc1.write_rom(0x0000, 0x00);
+    huc1.write_ram(0xA000, 0x42);
+    assert_eq!(huc1.read_ram(0xA000), 0x42);
+    assert_eq!(huc3.read_ram(0xA000), 0xC0);
+}
+
+/// Run a HuC3 RTC command, returns the response register
+fn huc3_command(cartridge: &mut Cartridge, command: u8) -> u8 {
+    cartridge.write_rom(0x0000, 0x0B);
+    cartridge.write_ram(0xA000, command);
+    cartridge.write_rom(0x0000, 0x0D);
+    cartridge.write_ram(0xA000, 0x00);
+    cartridge.write_rom(0x0000, 0x0C);
+    cartridge.read_ram(0xA000)
+}
+
+#[test]
+fn huc3_rtc_commands() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(4, 0xFE, 0x03)).unwrap();
+    // 1 day, 2 hours and 3 minutes
+    cartridge.tick(SystemTime::from_seconds(
+        ((24 + 2) * 60 + 3) as f64 * 60.0 + 30.0,
+    ));
+    huc3_command(&mut cartridge, 0x60);
+    huc3_command(&mut cartridge, 0x40);
+    huc3_command(&mut cartridge, 0x50);
+    let minutes: Vec<u8> = (0..6)
+        .map(|_| huc3_command(&mut cartridge, 0x10) & 0x0F)
+        .collect();
+    assert_eq!(minutes, [0xB, 0x7, 0x0, 0x1, 0x0, 0x0]);
+    assert_eq!(huc3_command(&mut cartridge, 0x10), 0x90);
+
+    // Alarm for the next minute, with tone 1
+    huc3_command(&mut cartridge, 0x40);
+    huc3_command(&mut cartridge, 0x51);
+    for nibble in [0xC, 0x7, 0x0, 0x1, 0x0, 0x0, 0x1] {
+        huc3_command(&mut cartridge, 0x30 | nibble);
+    }
+    assert!(!cartridge.tone());
+    cartridge.tick(SystemTime::from_seconds(((24 + 2) * 60 + 4) as f64 * 60.0));
+    assert!(cartridge.tone());
+    // Extended command E with tone 0 silences it
+    huc3_command(&mut cartridge, 0x6E);
+    assert!(!cartridge.tone());
+
+    let save = cartridge.save_data().unwrap();
+    assert_eq!(save.len(), 0x8000 + 16 + 0x100);
+    assert_eq!(save[0x8000..0x8008], [0x7C, 0, 0, 0, 1, 0, 0, 0]);
+}
+
+#[test]
+fn mmm01_menu_and_lock() {
+    // Two 64 KiB games followed by the menu, whose header says MMM01
+    let mut rom = banked_rom(10, 0x01, 0x00).into_vec();
+    rom[8 * 0x4000 + 0x104..8 * 0x4000 + 0x134].copy_from_slice(&LOGO);
+    rom[8 * 0x4000 + 0x147] = 0x0B;
+    let mut cartridge = Cartridge::from_rom(rom.into_boxed_slice()).unwrap();
+    assert_eq!(cartridge.read_rom(0x0000), 8);
+    assert_eq!(cartridge.read_rom(0x4000), 9);
+
+    // Second game, bank bits 2-4 fixed, then lock
+    cartridge.write_rom(0x2000, 0x04);
+    cartridge.write_rom(0x6000, 0x38);
+    cartridge.write_rom(0x0000, 0x40);
+    assert_eq!(cartridge.read_rom(0x0000), 4);
+    assert_eq!(cartridge.read_rom(0x4000), 5);
+    cartridge.write_rom(0x2000, 0x03);
+    assert_eq!(cartridge.read_rom(0x4000), 7);
+    // The game can't leave its banks
+    cartridge.write_rom(0x2000, 0x1C);
+    assert_eq!(cartridge.read_rom(0x4000), 5);
+    cartridge.write_rom(0x6000, 0x00);
+    cartridge.write_rom(0x2000, 0x02);
+    assert_eq!(cartridge.read_rom(0x4000), 6);
+}
+
+/// Neutral settings with thresholds at 40/80/C0 for the whole matrix, then start a capture
+fn start_capture(cartridge: &mut Cartridge) {
+    cartridge.write_rom(0x4000, 0x10);
+    cartridge.write_ram(0xA001, 0x00);
+    cartridge.write_ram(0xA002, 0x08);
+    cartridge.write_ram(0xA003, 0x00);
+    cartridge.write_ram(0xA004, 0x00);
+    for position in 0..16 {
+        for (index, threshold) in [0x40, 0x80, 0xC0].into_iter().enumerate() {
+            cartridge.write_ram(0xA006 + position * 3 + index as u16, threshold);
+        }
+    }
+    cartridge.write_ram(0xA000, 0x01);
+}
+
+/// Color of pixel `x`, `y` in a picture stored as tiles from `addr`
+fn picture_color(cartridge: &Cartridge, addr: u16, x: u16, y: u16) -> u8 {
+    let offset = addr + ((y / 8) * 16 + x / 8) * 16 + (y % 8) * 2;
+    let bit = 7 - (x % 8);
+    (cartridge.read_ram(offset) >> bit & 1) | (cartridge.read_ram(offset + 1) >> bit & 1) << 1
+}
+
+#[test]
+fn pocket_camera_capture() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(64, 0xFC, 0x04)).unwrap();
+    cartridge.write_rom(0x0000, 0x0A);
+    start_capture(&mut cartridge);
+    // 32446 + 512 for N clear + 16 per exposure unit
+    let done = SystemTime::from_system_clocks(32446 + 512 + 16 * 0x0800);
+    cartridge.tick(done - SystemTime::from_system_clocks(1));
+    assert_eq!(cartridge.read_ram(0xA000), 0x01);
+    // Registers repeat every 0x80 bytes
+    assert_eq!(cartridge.read_ram(0xA080), 0x01);
+    cartridge.tick(done);
+    assert_eq!(cartridge.read_ram(0xA000), 0x00);
+
+    // The test pattern's bars go from black on the left to white on the right
+    cartridge.write_rom(0x4000, 0x00);
+    assert_eq!(picture_color(&cartridge, 0xA100, 0, 0), 3);
+    assert_eq!(picture_color(&cartridge, 0xA100, 127, 0), 0);
+
+    // Save it as the first photo of the album
+    let picture: Vec<u8> = (0xA100..0xAF00)
+        .map(|addr| cartridge.read_ram(addr))
+        .collect();
+    cartridge.write_ram(0xB1B2, 0x00);
+    for addr in 0xB1B3..0xB1D0 {
+        cartridge.write_ram(addr, 0xFF);
+    }
+    cartridge.write_rom(0x4000, 0x01);
+    for (addr, byte) in (0xA000..).zip(&picture) {
+        cartridge.write_ram(addr, *byte);
+    }
+    let save = cartridge.save_data().unwrap();
+    let photos = extract_photos(&save);
+    assert_eq!(photos.len(), 1);
+    assert_eq!((photos[0][0], photos[0][127]), (0x00, 0xFF));
+
+    let directory = std::env::temp_dir().join(format!("cvgb_camera_{}", std::process::id()));
+    let paths = save_photos(&save, &directory).unwrap();
+    let image = GrayImage::load_png(&paths[0]).unwrap();
+    assert_eq!((image.width, image.height), (128, 112));
+    assert_eq!(*image.pixels, *photos[0]);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn pocket_camera_png_image() {
+    let path = std::env::temp_dir().join(format!("cvgb_camera_{}.png", std::process::id()));
+    // Left half black, right half white, at a different size than the sensor
+    let pixels: Vec<u8> = (0..64 * 56)
+        .map(|index| if index % 64 < 32 { 0x00 } else { 0xFF })
+        .collect();
+    save_png(&path, 64, &pixels).unwrap();
+    let mut cartridge = Cartridge::from_rom(banked_rom(64, 0xFC, 0x04)).unwrap();
+    cartridge.set_camera_image(CameraImage::load_png(&path).unwrap());
+    let _ = std::fs::remove_file(&path);
+
+    // Halving the exposure turns white into gray
+    start_capture(&mut cartridge);
+    cartridge.write_ram(0xA002, 0x04);
+    cartridge.write_ram(0xA000, 0x01);
+    cartridge.tick(SystemTime::from_seconds(1.0));
+    cartridge.write_rom(0x4000, 0x00);
+    assert_eq!(picture_color(&cartridge, 0xA100, 10, 50), 3);
+    assert_eq!(picture_color(&cartridge, 0xA100, 100, 50), 2);
+}
+
+/// Write nibble `data` to TAMA5 register `register`
+fn tama5_write(cartridge: &mut Cartridge, register: u8, data: u8) {
+    cartridge.write_ram(0xA001, register);
+    cartridge.write_ram(0xA000, data);
+}
+
+/// Run TAMA5 command `command` on `address`, returns the output registers
+fn tama5_command(cartridge: &mut Cartridge, command: u8, address: u8) -> u8 {
+    tama5_write(cartridge, 0x6, command << 1 | address >> 4);
+    tama5_write(cartridge, 0x7, address & 0x0F);
+    cartridge.write_ram(0xA001, 0x0C);
+    let low = cartridge.read_ram(0xA000) & 0x0F;
+    cartridge.write_ram(0xA001, 0x0D);
+    let high = cartridge.read_ram(0xA000) & 0x0F;
+    high << 4 | low
+}
+
+#[test]
+fn tama5_registers_clock_and_save() {
+    let mut cartridge = Cartridge::from_rom(banked_rom(32, 0xFD, 0x00)).unwrap();
+    tama5_write(&mut cartridge, 0x0, 0x3);
+    tama5_write(&mut cartridge, 0x1, 0x1);
+    assert_eq!(cartridge.read_rom(0x4000), 0x13);
+    cartridge.write_ram(0xA001, 0x0A);
+    assert_eq!(cartridge.read_ram(0xA000), 0xF1);
+
+    tama5_write(&mut cartridge, 0x4, 0xD);
+    tama5_write(&mut cartridge, 0x5, 0xA);
+    tama5_command(&mut cartridge, 0x0, 0x1F);
+    assert_eq!(tama5_command(&mut cartridge, 0x1, 0x1F), 0xAD);
+
+    // 23:59:58 on the 28th of February of year 04, set with the timer stopped
+    tama5_command(&mut cartridge, 0x2, 0x0);
+    let digits = [8, 5, 9, 5, 3, 2, 0, 8, 2, 2, 0, 4, 0];
+    for (register, value) in digits.into_iter().enumerate() {
+        tama5_write(&mut cartridge, 0x4, register as u8);
+        tama5_write(&mut cartridge, 0x5, value);
+        tama5_command(&mut cartridge, 0x4, 0x0);
+    }
+    cartridge.tick(SystemTime::from_seconds(3.0));
+    let read_register = |cartridge: &mut Cartridge, register: u8| {
+        tama5_write(cartridge, 0x4, register);
+        tama5_command(cartridge, 0x4, 0x1)
+    };
+    assert_eq!(
+        read_register(&mut cartridge, 0x0),
+        8,
+        "the timer is stopped"
+    );
+    // Starting the timer clears the seconds
+    tama5_command(&mut cartridge, 0x2, 0x1);
+    cartridge.tick(SystemTime::from_seconds(63.0));
+    let clock: Vec<u8> = (0x0..=0xC)
+        .map(|register| read_register(&mut cartridge, register))
+        .collect();
+    // A leap year, so the 29th comes next
+    assert_eq!(clock, [0, 0, 0, 0, 0, 0, 1, 9, 2, 2, 0, 4, 0]);
+    cartridge.tick(SystemTime::from_seconds(63.0 + 12.0 * 60.0));
+    let timer: Vec<u8> = (0x4..=0x7)
+        .map(|address| tama5_command(&mut cartridge, 0x2, address))
+        .collect();
+    assert_eq!(timer, [2, 1, 0, 0], "00:12 as minutes then hours");
+
+    let save = cartridge.save_data().unwrap();
+    assert_eq!(save.len(), 48);
+    let mut loaded = Cartridge::from_rom(banked_rom(32, 0xFD, 0x00)).unwrap();
+    loaded.load_save_data(&save);
+    assert_eq!(tama5_command(&mut loaded, 0x1, 0x1F), 0xAD);
+    tama5_write(&mut loaded, 0x4, 0x7);
+    assert_eq!(tama5_command(&mut loaded, 0x4, 0x1), 9);
+}
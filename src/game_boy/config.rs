@@ -1,2 +1,14 @@
 #[derive(Debug, Default)]
-pub struct Config {}
+pub struct Config {
+    pub model: Model,
+}
+
+/// Which console to emulate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Model {
+    /// Game Boy Color for cartridges that support it, Game Boy otherwise
+    #[default]
+    Auto,
+    Dmg,
+    Cgb,
+}
@@ -0,0 +1,124 @@
+/// Number of bytes copied into OAM by a transfer
+const TRANSFER_LENGTH: u8 = 0xA0;
+
+#[derive(Debug, Clone, Copy)]
+struct PendingStart {
+    page: u8,
+    /// M-cycles left before the transfer starts
+    delay: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    page: u8,
+    index: u8,
+}
+
+/// The CPU and the DMA reach memory through two buses, a transfer only
+/// conflicts with CPU accesses that go through the bus it reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    /// Cartridge ROM and RAM, and WRAM
+    External,
+    /// VRAM
+    Video,
+}
+
+impl Bus {
+    /// Bus behind `addr`, OAM, IO registers and HRAM are on neither
+    pub fn of(addr: u16) -> Option<Self> {
+        match addr {
+            0x8000..=0x9FFF => Some(Self::Video),
+            0x0000..=0xFDFF => Some(Self::External),
+            0xFE00..=0xFFFF => None,
+        }
+    }
+}
+
+/// A byte that should be copied from `source` into OAM at `index`
+#[derive(Debug, Clone, Copy)]
+pub struct DmaCopy {
+    pub source: u16,
+    pub index: u8,
+}
+
+/// OAM DMA controller (FF46)
+/// Copies 160 bytes into OAM, one per M-cycle, after a one cycle start-up delay
+#[derive(Debug, Default)]
+pub struct Dma {
+    /// Last value written to FF46
+    register: u8,
+    pending: Option<PendingStart>,
+    active: Option<Transfer>,
+    /// Bus the DMA owns this cycle
+    copying: Option<Bus>,
+    /// Byte the DMA read this cycle
+    bus_value: u8,
+}
+
+impl Dma {
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+    /// Request a transfer from `data`00-`data`9F
+    /// An ongoing transfer keeps running until the new one starts
+    pub fn write(&mut self, data: u8) {
+        self.register = data;
+        self.pending = Some(PendingStart {
+            page: data,
+            delay: 1,
+        });
+    }
+    /// Advance the DMA by one M-cycle, returning the byte to copy this cycle
+    pub fn tick(&mut self) -> Option<DmaCopy> {
+        if let Some(pending) = self.pending.as_mut() {
+            if pending.delay == 0 {
+                self.active = Some(Transfer {
+                    page: pending.page,
+                    index: 0,
+                });
+                self.pending = None;
+            } else {
+                pending.delay -= 1;
+            }
+        }
+        let Some(transfer) = self.active.as_mut() else {
+            self.copying = None;
+            return None;
+        };
+        let copy = DmaCopy {
+            source: source_address(transfer.page, transfer.index),
+            index: transfer.index,
+        };
+        self.copying = Bus::of(copy.source);
+        transfer.index += 1;
+        if transfer.index == TRANSFER_LENGTH {
+            self.active = None;
+        }
+        Some(copy)
+    }
+    /// Latch the byte read for this cycle's copy
+    pub fn set_bus_value(&mut self, data: u8) {
+        self.bus_value = data;
+    }
+    /// OAM is written by the DMA and can't be accessed by the CPU while copying
+    pub fn oam_locked(&self) -> bool {
+        self.copying.is_some()
+    }
+    /// While copying, CPU accesses to `addr` see the DMA's byte instead
+    /// if they go through the bus the DMA is reading from
+    pub fn bus_conflict(&self, addr: u16) -> Option<u8> {
+        let bus = Bus::of(addr)?;
+        (self.copying == Some(bus)).then_some(self.bus_value)
+    }
+}
+
+fn source_address(page: u8, index: u8) -> u16 {
+    let address = u16::from_be_bytes([page, index]);
+    // Sources past DFFF are routed to WRAM like echo RAM
+    if address >= 0xE000 {
+        address - 0x2000
+    } else {
+        address
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::BitOrAssign;
+
 use enum_assoc::Assoc;
 use modular_bitfield::prelude::*;
 
@@ -53,3 +55,9 @@ impl InterruptFlags {
         *self = Self::new()
     }
 }
+
+impl BitOrAssign for InterruptFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = (u8::from(*self) | u8::from(rhs)).into();
+    }
+}
@@ -1,35 +1,82 @@
+use dma::Dma;
 use interrupts::{Interrupt, InterruptFlags};
 use p1::P1;
+use serial::Serial;
+use timer::Timer;
 
 use super::{
-    cartridge::Cartridge, cpu::CpuContext, events::Events, input::Input, time::SystemTime,
+    apu::{Apu, Sample},
+    cartridge::Cartridge,
+    cpu::{CPUState, CpuContext},
+    events::Events,
+    hardware::HardwareMode,
+    input::{Input, Tilt},
+    link::LinkPeer,
+    ppu::{FrameBuffer, Ppu},
+    time::SystemTime,
 };
 
+mod dma;
 pub mod interrupts;
 mod p1;
+mod serial;
+#[cfg(test)]
+mod tests;
+mod timer;
+
+const WRAM_BANK_SIZE: usize = 0x1000;
+/// 8 banks on CGB, only the first two are used on DMG
+const WRAM_SIZE: usize = WRAM_BANK_SIZE * 8;
+const HRAM_SIZE: usize = 0x7F;
 
 #[derive(Debug)]
 pub struct Context {
+    hardware: HardwareMode,
     time: SystemTime,
     events: Events,
     cartridge: Cartridge,
     boot_rom_enabled: bool,
     p1: P1,
+    timer: Timer,
+    serial: Serial,
+    ppu: Ppu,
+    apu: Apu,
+    dma: Dma,
     interrupts: InterruptFlags,
     interrupt_enable: InterruptFlags,
+    wram: Box<[u8; WRAM_SIZE]>,
+    /// SVBK, WRAM bank mapped at D000-DFFF
+    wram_bank: u8,
+    hram: [u8; HRAM_SIZE],
+    /// KEY0, CGB compatibility mode selected by the boot ROM
+    key0: u8,
+    /// KEY1 bit 0, a speed switch happens on the next STOP
+    speed_switch_armed: bool,
+    /// KEY1 bit 7, the CPU runs at 8.388608 MHz
+    double_speed: bool,
 }
 
 impl CpuContext for Context {
     fn cycle_read_itrs(&mut self, addr: u16) -> (u8, InterruptFlags) {
-        todo!()
+        self.tick();
+        let data = self.read(addr);
+        (data, self.pending_interrupts())
     }
 
     fn cycle_write_itrs(&mut self, addr: u16, data: u8) -> InterruptFlags {
-        todo!()
+        self.tick();
+        self.write(addr, data);
+        self.pending_interrupts()
     }
 
-    fn cycle_state_itrs(&mut self, state: super::cpu::CPUState) -> InterruptFlags {
-        todo!()
+    fn cycle_state_itrs(&mut self, state: CPUState) -> InterruptFlags {
+        if state.is_stop() {
+            // The system clock is stopped, only real time goes on
+            self.time += SystemTime::from_master_clocks(self.cycle_dots());
+        } else {
+            self.tick();
+        }
+        self.pending_interrupts()
     }
 
     fn ack_interrupt(&mut self, itr: Interrupt) {
@@ -43,28 +90,231 @@ impl CpuContext for Context {
     }
 
     fn has_interrupt(&mut self) -> bool {
-        self.interrupts.has_interrupt()
+        self.pending_interrupts().has_interrupt()
     }
 
-    fn speed_switch(&mut self) {
-        todo!()
+    fn speed_switch(&mut self) -> bool {
+        if !self.hardware.cgb_features() || !self.speed_switch_armed {
+            return false;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        self.reset_div();
+        true
     }
 
     fn has_pressed_input(&self) -> bool {
-        todo!()
+        self.p1.has_pressed_input()
+    }
+
+    fn enter_stop(&mut self) {
+        self.reset_div();
+        self.ppu.stop();
     }
 }
 
 impl Context {
-    pub fn new(cartridge: Cartridge) -> Self {
+    pub fn new(cartridge: Cartridge, hardware: HardwareMode) -> Self {
         Self {
+            hardware,
             time: Default::default(),
             events: Default::default(),
             cartridge,
+            // There is no boot ROM image to map, the CPU starts in the post-boot state
+            // and finish_boot performs the last writes of the boot ROM
             boot_rom_enabled: true,
             p1: Default::default(),
+            timer: Timer::post_boot(hardware),
+            serial: Serial::new(hardware.cgb_features()),
+            ppu: Ppu::new(hardware),
+            apu: Apu::new(hardware),
+            dma: Default::default(),
             interrupts: Default::default(),
             interrupt_enable: Default::default(),
+            wram: Box::new([0; WRAM_SIZE]),
+            wram_bank: 1,
+            hram: [0; HRAM_SIZE],
+            key0: hardware.key0(),
+            speed_switch_armed: false,
+            double_speed: false,
+        }
+    }
+    /// Perform the last writes of the boot ROM: select the CPU mode through KEY0,
+    /// then unmap the boot ROM, which locks KEY0 until reset
+    /// DIV is left at a value that depends on the selected mode
+    pub fn finish_boot(&mut self, key0: u8) {
+        self.write_io(0xFF4C, key0);
+        self.write_io(0xFF50, 0x11);
+        self.timer = Timer::post_boot(self.hardware);
+        self.update_div();
+    }
+    fn set_hardware(&mut self, hardware: HardwareMode) {
+        self.hardware = hardware;
+        self.serial.set_cgb_features(hardware.cgb_features());
+        self.ppu.set_hardware(hardware);
+    }
+    /// Advance every component by one M-cycle
+    /// In double speed mode the timer and DMA follow the CPU,
+    /// while the PPU and APU keep running in real time and only see half the dots
+    fn tick(&mut self) {
+        let dots = self.cycle_dots();
+        self.time += SystemTime::from_master_clocks(dots);
+        self.cartridge.tick(self.time);
+        if self.timer.tick() {
+            self.interrupts.set_timer(true);
+        }
+        if self.serial.tick(self.timer.counter(), self.time) {
+            self.interrupts.set_serial(true);
+        }
+        self.apu.tick(self.timer.counter(), self.double_speed);
+        for _ in 0..dots {
+            let ppu_interrupts = self.ppu.tick();
+            if ppu_interrupts.vblank() {
+                self.events.set_vblank(true);
+            }
+            self.interrupts |= ppu_interrupts;
+        }
+        if let Some(copy) = self.dma.tick() {
+            let data = self.read_bus(copy.source);
+            self.dma.set_bus_value(data);
+            self.ppu.write_oam_dma(copy.index, data);
+        }
+    }
+    /// The serial clock and the APU's frame sequencer see the falling edges caused by DIV resets
+    fn reset_div(&mut self) {
+        self.timer.reset_div();
+        self.update_div();
+    }
+    fn update_div(&mut self) {
+        self.serial.update_counter(self.timer.counter());
+        self.apu.update_div(self.timer.counter(), self.double_speed);
+    }
+    /// Dots (base clocks) in an M-cycle at the current speed
+    fn cycle_dots(&self) -> u64 {
+        if self.double_speed { 2 } else { 4 }
+    }
+    /// Interrupts that are both requested and enabled
+    fn pending_interrupts(&self) -> InterruptFlags {
+        let requested: u8 = self.interrupts.into();
+        let enabled: u8 = self.interrupt_enable.into();
+        (requested & enabled).into()
+    }
+    /// Read as seen by the CPU
+    fn read(&self, addr: u16) -> u8 {
+        if self.dma.oam_locked() && (0xFE00..=0xFEFF).contains(&addr) {
+            return 0xFF;
+        }
+        if let Some(value) = self.dma.bus_conflict(addr) {
+            return value;
+        }
+        self.read_bus(addr)
+    }
+    fn read_bus(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.cartridge.read_rom(addr),
+            0x8000..=0x9FFF => self.ppu.read_vram(addr),
+            0xA000..=0xBFFF => self.cartridge.read_ram(addr),
+            // Echo RAM mirrors C000-DDFF
+            0xC000..=0xFDFF => self.wram[self.wram_index(addr)],
+            0xFE00..=0xFE9F => self.ppu.read_oam(addr),
+            // Unusable region, reads 00 on DMG unless OAM is locked
+            0xFEA0..=0xFEFF => {
+                if self.ppu.oam_accessible() {
+                    0x00
+                } else {
+                    0xFF
+                }
+            }
+            0xFF00..=0xFF7F => self.read_io(addr),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
+            0xFFFF => self.interrupt_enable.into(),
+        }
+    }
+    fn write(&mut self, addr: u16, data: u8) {
+        let oam_locked = self.dma.oam_locked() && (0xFE00..=0xFEFF).contains(&addr);
+        if oam_locked || self.dma.bus_conflict(addr).is_some() {
+            return;
+        }
+        match addr {
+            0x0000..=0x7FFF => {
+                let rumble = self.cartridge.rumble();
+                self.cartridge.write_rom(addr, data);
+                if self.cartridge.rumble() != rumble {
+                    self.events.set_rumble_changed(true);
+                }
+            }
+            0x8000..=0x9FFF => self.ppu.write_vram(addr, data),
+            0xA000..=0xBFFF => self.cartridge.write_ram(addr, data),
+            0xC000..=0xFDFF => self.wram[self.wram_index(addr)] = data,
+            0xFE00..=0xFE9F => self.ppu.write_oam(addr, data),
+            0xFEA0..=0xFEFF => {}
+            0xFF00..=0xFF7F => self.write_io(addr, data),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize] = data,
+            0xFFFF => self.interrupt_enable = data.into(),
+        }
+    }
+    fn wram_index(&self, addr: u16) -> usize {
+        let offset = (addr & 0x1FFF) as usize;
+        if offset < WRAM_BANK_SIZE {
+            offset
+        } else {
+            self.wram_bank as usize * WRAM_BANK_SIZE + offset - WRAM_BANK_SIZE
+        }
+    }
+    fn read_io(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF00 => self.p1.read(),
+            0xFF01 | 0xFF02 => self.serial.read(addr),
+            0xFF04..=0xFF07 => self.timer.read(addr),
+            0xFF10..=0xFF3F => self.apu.read(addr),
+            // The upper 3 bits of IF are unused and always read as 1
+            0xFF0F => 0xE0 | u8::from(self.interrupts),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.read(addr),
+            0xFF46 => self.dma.read(),
+            0xFF4C if self.boot_rom_enabled && self.hardware.is_cgb_hardware() => self.key0,
+            0xFF4D if self.hardware.cgb_features() => {
+                0x7E | (self.double_speed as u8) << 7 | self.speed_switch_armed as u8
+            }
+            0xFF4F | 0xFF68..=0xFF6C if self.hardware.cgb_features() => self.ppu.read(addr),
+            0xFF70 if self.hardware.cgb_features() => 0xF8 | self.wram_bank,
+            _ => {
+                log::trace!("read from unmapped io register {addr:04x}");
+                0xFF
+            }
+        }
+    }
+    fn write_io(&mut self, addr: u16, data: u8) {
+        match addr {
+            0xFF00 => {
+                if self.p1.write(data) {
+                    self.interrupts.set_joypad(true);
+                }
+            }
+            0xFF01 | 0xFF02 => self.serial.write(addr, data),
+            0xFF04..=0xFF07 => {
+                self.timer.write(addr, data);
+                self.update_div();
+            }
+            0xFF10..=0xFF3F => self.apu.write(addr, data),
+            0xFF0F => self.interrupts = (data & 0x1F).into(),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.write(addr, data),
+            0xFF46 => self.dma.write(data),
+            // KEY0 can only be written by the boot ROM
+            0xFF4C if self.boot_rom_enabled && self.hardware.is_cgb_hardware() => {
+                self.key0 = data;
+                self.set_hardware(HardwareMode::from_key0(data));
+            }
+            0xFF4D if self.hardware.cgb_features() => self.speed_switch_armed = data & 0x01 != 0,
+            0xFF4F | 0xFF68..=0xFF6C if self.hardware.cgb_features() => self.ppu.write(addr, data),
+            // Bank 0 maps bank 1 instead
+            0xFF70 if self.hardware.cgb_features() => self.wram_bank = (data & 0x07).max(1),
+            // Any write with bit 0 set unmaps the boot ROM until reset
+            0xFF50 => {
+                if data & 0x01 != 0 {
+                    self.boot_rom_enabled = false;
+                }
+            }
+            _ => log::trace!("write {data:02x} to unmapped io register {addr:04x}"),
         }
     }
     pub fn set_input(&mut self, input: Input) {
@@ -80,6 +330,36 @@ impl Context {
     pub fn system_time(&self) -> SystemTime {
         self.time
     }
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        self.cartridge.set_tilt(tilt);
+    }
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+    pub fn connect_link(&mut self, peer: Box<dyn LinkPeer>) -> Box<dyn LinkPeer> {
+        self.serial.connect(peer)
+    }
+    /// Whether the link cable lets the next M-cycle run
+    pub fn link_ready(&mut self) -> bool {
+        let time = self.time + SystemTime::from_master_clocks(self.cycle_dots());
+        self.serial.ready(time)
+    }
+    /// Whether a serial transfer may complete in the middle of the next instruction
+    pub fn link_clocking(&self) -> bool {
+        self.serial.clocking()
+    }
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.apu.set_sample_rate(sample_rate);
+    }
+    pub fn take_samples(&mut self) -> Vec<Sample> {
+        self.apu.take_samples()
+    }
+    pub fn frame(&self) -> &FrameBuffer {
+        self.ppu.frame()
+    }
     pub fn press_key(&mut self, input: Input) {
         if self.p1.press(input) {
             self.interrupts.set_joypad(true);
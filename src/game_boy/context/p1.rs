@@ -24,9 +24,16 @@ impl P1 {
         }
         res
     }
-    pub fn write(&mut self, val: u8) {
-        self.select_buttons = val & SELECT_BUTTONS_MASK == 0;
-        self.select_dpad = val & SELECT_DPAD_MASK == 0;
+    /// Selects the button/dpad lines, returns true if the joypad interrupt was triggered
+    pub fn write(&mut self, val: u8) -> bool {
+        self.watch_interrupt_line(|slf| {
+            slf.select_buttons = val & SELECT_BUTTONS_MASK == 0;
+            slf.select_dpad = val & SELECT_DPAD_MASK == 0;
+        })
+    }
+    /// Whether a selected button line is low, which wakes the CPU from STOP
+    pub fn has_pressed_input(&self) -> bool {
+        !self.interrupt_line()
     }
     /// When the interrupts line goes from true to false, the joypad interrupt should be triggered
     fn interrupt_line(&self) -> bool {
@@ -0,0 +1,133 @@
+use modular_bitfield::prelude::*;
+
+use crate::game_boy::{
+    link::{Disconnected, LinkPeer},
+    time::SystemTime,
+};
+
+/// Counter bit whose falling edge shifts a bit at 8192 Hz
+const NORMAL_CLOCK_MASK: u16 = 1 << 8;
+/// Counter bit whose falling edge shifts a bit at 262144 Hz, CGB only
+const FAST_CLOCK_MASK: u16 = 1 << 3;
+
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Sc {
+    internal_clock: bool,
+    fast_clock: bool,
+    #[skip]
+    __: B5,
+    transfer: bool,
+}
+
+/// SB/SC serial port
+/// With the internal clock, bits are shifted on the falling edges of a system counter bit,
+/// so double speed mode doubles the transfer rate.
+/// The byte is exchanged with the peer as a whole once the 8th bit is shifted
+#[derive(Debug)]
+pub struct Serial {
+    sb: u8,
+    sc: Sc,
+    /// Bits shifted in the current transfer
+    shifted: u8,
+    clock_signal: bool,
+    cgb_features: bool,
+    peer: Box<dyn LinkPeer>,
+}
+
+impl Serial {
+    pub fn new(cgb_features: bool) -> Self {
+        Self {
+            sb: 0,
+            sc: Sc::new(),
+            shifted: 0,
+            clock_signal: false,
+            cgb_features,
+            peer: Box::new(Disconnected),
+        }
+    }
+    pub fn set_cgb_features(&mut self, cgb_features: bool) {
+        self.cgb_features = cgb_features;
+    }
+    /// Plug `peer` into the link port, returns the previous peer
+    pub fn connect(&mut self, peer: Box<dyn LinkPeer>) -> Box<dyn LinkPeer> {
+        let old = std::mem::replace(&mut self.peer, peer);
+        self.peer.listen(self.offer());
+        old
+    }
+    /// Advance by one M-cycle, returns true if the serial interrupt was triggered
+    pub fn tick(&mut self, counter: u16, time: SystemTime) -> bool {
+        self.peer.sync(time);
+        self.update_counter(counter);
+        if self.sc.transfer() && !self.sc.internal_clock() {
+            let Some(data) = self.peer.poll(time) else {
+                return false;
+            };
+            self.sb = data;
+            self.finish_transfer();
+            return true;
+        }
+        if self.shifted < 8 {
+            return false;
+        }
+        self.sb = self.peer.exchange(time, self.sb);
+        self.finish_transfer();
+        true
+    }
+    /// Whether the peer lets the M-cycle ending at `time` run, see [`LinkPeer::ready`]
+    pub fn ready(&mut self, time: SystemTime) -> bool {
+        let exchange_due =
+            self.clocking() && (self.shifted >= 8 || (self.shifted == 7 && self.clock_signal));
+        self.peer.ready(time, exchange_due)
+    }
+    /// Whether a transfer is driven by our internal clock,
+    /// it completes on an M-cycle that may fall in the middle of an instruction
+    pub fn clocking(&self) -> bool {
+        self.sc.transfer() && self.sc.internal_clock()
+    }
+    /// Must be called whenever the system counter changes, including DIV resets
+    /// A falling edge of the serial clock shifts a bit of an internal clock transfer
+    pub fn update_counter(&mut self, counter: u16) {
+        let signal = counter & self.clock_mask() != 0;
+        if self.clock_signal && !signal && self.clocking() {
+            self.shifted += 1;
+        }
+        self.clock_signal = signal;
+    }
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF01 => self.sb,
+            0xFF02 if self.cgb_features => 0x7C | u8::from(self.sc),
+            0xFF02 => 0x7E | u8::from(self.sc),
+            _ => unreachable!("{addr:04x} is not a serial register"),
+        }
+    }
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0xFF01 => self.sb = data,
+            0xFF02 => {
+                let mask = if self.cgb_features { 0x83 } else { 0x81 };
+                self.sc = (data & mask).into();
+                self.shifted = 0;
+            }
+            _ => unreachable!("{addr:04x} is not a serial register"),
+        }
+        self.peer.listen(self.offer());
+    }
+    fn finish_transfer(&mut self) {
+        self.sc.set_transfer(false);
+        self.shifted = 0;
+    }
+    /// SB is shifted out by the other side's clock while waiting for it
+    fn offer(&self) -> Option<u8> {
+        (self.sc.transfer() && !self.sc.internal_clock()).then_some(self.sb)
+    }
+    fn clock_mask(&self) -> u16 {
+        if self.sc.fast_clock() {
+            FAST_CLOCK_MASK
+        } else {
+            NORMAL_CLOCK_MASK
+        }
+    }
+}
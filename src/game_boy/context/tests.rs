@@ -0,0 +1,302 @@
+use crate::game_boy::{
+    Input,
+    cartridge::Cartridge,
+    cpu::{CPUState, CpuContext},
+    hardware::HardwareMode,
+    link::cable::link_cable,
+    time::SystemTime,
+};
+
+use super::Context;
+
+fn context() -> Context {
+    let mut rom = vec![0; 0x8000];
+    rom[0x4000] = 0x5A;
+    let rom = rom.into_boxed_slice();
+    Context::new(Cartridge::from_rom(rom).unwrap(), HardwareMode::Dmg)
+}
+
+#[test]
+fn memory_map() {
+    let mut ctx = context();
+    assert_eq!(ctx.cycle_read(0x4000), 0x5A);
+    ctx.cycle_write(0x4000, 0x00);
+    assert_eq!(ctx.cycle_read(0x4000), 0x5A, "ROM is read-only");
+    ctx.cycle_write(0xC123, 0x42);
+    assert_eq!(ctx.cycle_read(0xE123), 0x42, "echo RAM mirrors WRAM");
+    ctx.cycle_write(0xFF85, 0x99);
+    assert_eq!(ctx.cycle_read(0xFF85), 0x99);
+    ctx.cycle_write(0xFF0F, 0xFF);
+    assert_eq!(ctx.cycle_read(0xFF0F), 0xFF);
+    ctx.cycle_write(0xFFFF, 0x04);
+    let (_, itrs) = ctx.cycle_read_itrs(0x0000);
+    assert_eq!(u8::from(itrs), 0x04, "pending interrupts are IF & IE");
+}
+
+#[test]
+fn timer_overflow_reload_delay() {
+    let mut ctx = context();
+    ctx.cycle_write(0xFF0F, 0x00);
+    // 262144 Hz: TIMA increments every 4 M-cycles
+    ctx.cycle_write(0xFF06, 0x50);
+    ctx.cycle_write(0xFF05, 0xFF);
+    ctx.cycle_write(0xFF04, 0x00);
+    ctx.cycle_write(0xFF07, 0x05);
+    // Counter bit 3 falls 4 cycles after the DIV reset
+    ctx.cycle();
+    ctx.cycle();
+    assert_eq!(
+        ctx.cycle_read(0xFF05),
+        0x00,
+        "TIMA reads 00 during the overflow cycle"
+    );
+    assert_eq!(
+        ctx.cycle_read(0xFF05),
+        0x50,
+        "TIMA is reloaded one cycle later"
+    );
+    assert_eq!(ctx.cycle_read(0xFF0F) & 0x04, 0x04);
+}
+
+#[test]
+fn timer_div_reset_glitch() {
+    let mut ctx = context();
+    ctx.cycle_write(0xFF05, 0x00);
+    ctx.cycle_write(0xFF04, 0x00);
+    ctx.cycle_write(0xFF07, 0x05);
+    // Bit 3 of the counter is set after 2 more cycles
+    ctx.cycle();
+    ctx.cycle();
+    ctx.cycle_write(0xFF04, 0x00);
+    assert_eq!(
+        ctx.cycle_read(0xFF05),
+        0x01,
+        "resetting DIV causes a falling edge"
+    );
+}
+
+#[test]
+fn post_boot_div() {
+    let cartridge = || Cartridge::from_rom(vec![0; 0x8000].into_boxed_slice()).unwrap();
+    let mut ctx = Context::new(cartridge(), HardwareMode::Dmg);
+    ctx.finish_boot(HardwareMode::Dmg.key0());
+    assert_eq!(ctx.cycle_read(0xFF04), 0xAB);
+    let mut ctx = Context::new(cartridge(), HardwareMode::Cgb);
+    ctx.finish_boot(HardwareMode::DmgCompatibility.key0());
+    assert_eq!(
+        ctx.cycle_read(0xFF04),
+        0x26,
+        "DIV follows the mode KEY0 selects"
+    );
+}
+
+#[test]
+fn oam_dma() {
+    let mut ctx = context();
+    // Keep the PPU from locking OAM
+    ctx.cycle_write(0xFF40, 0x00);
+    for i in 0..0xA0 {
+        ctx.cycle_write(0xC000 + i, i as u8);
+    }
+    ctx.cycle_write(0xFF46, 0xC0);
+    // Start-up cycle, the bus is still free
+    assert_eq!(ctx.cycle_read(0xC010), 0x10);
+    // The first byte is copied, the CPU sees it instead of WRAM
+    assert_eq!(ctx.cycle_read(0xC010), 0x00);
+    assert_eq!(ctx.cycle_read(0xFE00), 0xFF, "OAM is locked during DMA");
+    ctx.cycle_write(0xFF80, 0x12);
+    assert_eq!(ctx.cycle_read(0xFF80), 0x12, "HRAM is still accessible");
+    ctx.cycle_write(0x8000, 0x34);
+    assert_eq!(
+        ctx.cycle_read(0x8000),
+        0x34,
+        "VRAM is on another bus than WRAM"
+    );
+    ctx.cycle_write(0xC010, 0xAA);
+    for _ in 0..152 {
+        ctx.cycle();
+    }
+    assert_eq!(ctx.cycle_read(0xC010), 0x9F, "last byte");
+    assert_eq!(ctx.cycle_read(0xC010), 0x10, "the write was ignored");
+    assert_eq!(ctx.cycle_read(0xFE42), 0x42);
+
+    // A transfer from VRAM leaves WRAM and the cartridge accessible
+    ctx.cycle_write(0xFF46, 0x80);
+    ctx.cycle();
+    assert_eq!(ctx.cycle_read(0x9000), 0x34, "the DMA's byte from 8000");
+    assert_eq!(ctx.cycle_read(0xC010), 0x10);
+    assert_eq!(ctx.cycle_read(0x4000), 0x5A);
+}
+
+#[test]
+fn cgb_banking() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = 0x80;
+    let cartridge = Cartridge::from_rom(rom.into_boxed_slice()).unwrap();
+    let mut ctx = Context::new(cartridge, HardwareMode::Cgb);
+    ctx.cycle_write(0xFF40, 0x00);
+    for bank in 1..8 {
+        ctx.cycle_write(0xFF70, bank);
+        ctx.cycle_write(0xD000, bank);
+    }
+    ctx.cycle_write(0xFF70, 0x00);
+    assert_eq!(ctx.cycle_read(0xD000), 1, "SVBK 0 maps bank 1");
+    ctx.cycle_write(0xFF70, 0x05);
+    assert_eq!(ctx.cycle_read(0xF000), 5, "echo RAM follows SVBK");
+    ctx.cycle_write(0xFF4F, 0x01);
+    ctx.cycle_write(0x8000, 0xAB);
+    ctx.cycle_write(0xFF4F, 0x00);
+    assert_eq!(ctx.cycle_read(0x8000), 0x00);
+    assert_eq!(ctx.cycle_read(0xFF4F), 0xFE);
+    // Auto-incrementing palette writes
+    ctx.cycle_write(0xFF68, 0x80 | 0x3F);
+    ctx.cycle_write(0xFF69, 0x12);
+    ctx.cycle_write(0xFF69, 0x34);
+    assert_eq!(ctx.cycle_read(0xFF68), 0xC1, "the address wraps around");
+    ctx.cycle_write(0xFF68, 0x00);
+    assert_eq!(ctx.cycle_read(0xFF69), 0x34);
+}
+
+#[test]
+fn key0_selects_compatibility_mode_and_locks() {
+    let rom = vec![0; 0x8000];
+    let cartridge = Cartridge::from_rom(rom.into_boxed_slice()).unwrap();
+    let mut ctx = Context::new(cartridge, HardwareMode::Cgb);
+    assert_eq!(ctx.cycle_read(0xFF70), 0xF9, "SVBK is mapped in CGB mode");
+    ctx.cycle_write(0xFF4C, 0x04);
+    assert_eq!(
+        ctx.cycle_read(0xFF4C),
+        0x04,
+        "KEY0 is readable while the boot ROM is mapped"
+    );
+    assert_eq!(
+        ctx.cycle_read(0xFF70),
+        0xFF,
+        "CGB registers are gone in compatibility mode"
+    );
+    ctx.cycle_write(0xFF50, 0x11);
+    ctx.cycle_write(0xFF4C, 0x00);
+    assert_eq!(
+        ctx.cycle_read(0xFF4C),
+        0xFF,
+        "KEY0 is locked once the boot ROM is unmapped"
+    );
+    assert_eq!(
+        ctx.cycle_read(0xFF70),
+        0xFF,
+        "the mode can't be changed back"
+    );
+}
+
+#[test]
+fn cgb_speed_switch() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = 0x80;
+    let cartridge = Cartridge::from_rom(rom.into_boxed_slice()).unwrap();
+    let mut ctx = Context::new(cartridge, HardwareMode::Cgb);
+    assert!(!ctx.speed_switch(), "KEY1 was not prepared");
+    ctx.cycle_write(0xFF4D, 0x01);
+    assert_eq!(ctx.cycle_read(0xFF4D), 0x7F);
+    assert!(ctx.speed_switch());
+    assert_eq!(ctx.cycle_read(0xFF4D), 0xFE);
+    assert_eq!(ctx.cycle_read(0xFF04), 0x00, "the switch resets DIV");
+    let before = ctx.system_time();
+    ctx.cycle_read(0x0000);
+    assert_eq!(
+        ctx.system_time() - before,
+        SystemTime::from_master_clocks(2)
+    );
+}
+
+#[test]
+fn stop_mode() {
+    let mut ctx = context();
+    // Select the buttons
+    ctx.cycle_write(0xFF00, 0x10);
+    ctx.enter_stop();
+    let ly = ctx.cycle_read(0xFF44);
+    for _ in 0..1000 {
+        ctx.cycle_state_itrs(CPUState::Stop);
+    }
+    assert_eq!(ctx.cycle_read(0xFF04), 0x00, "DIV is reset and stopped");
+    assert_eq!(ctx.cycle_read(0xFF44), ly, "the PPU is stopped");
+    ctx.press_key(Input::UP);
+    assert!(!ctx.has_pressed_input(), "the d-pad is not selected");
+    ctx.press_key(Input::START);
+    assert!(ctx.has_pressed_input());
+}
+
+/// Cycles until the serial interrupt is requested
+fn serial_transfer_cycles(ctx: &mut Context) -> u32 {
+    let mut cycles = 0;
+    while ctx.cycle_read(0xFF0F) & 0x08 == 0 {
+        cycles += 1;
+        assert!(cycles < 10_000, "the transfer never completed");
+    }
+    cycles
+}
+
+#[test]
+fn serial_internal_clock() {
+    let mut ctx = context();
+    ctx.cycle_write(0xFF0F, 0x00);
+    ctx.cycle_write(0xFF01, 0x42);
+    ctx.cycle_write(0xFF04, 0x00);
+    ctx.cycle_write(0xFF02, 0x81);
+    // 8192 Hz: a bit every 128 M-cycles
+    assert!((1020..=1024).contains(&serial_transfer_cycles(&mut ctx)));
+    assert_eq!(ctx.cycle_read(0xFF01), 0xFF, "nothing is connected");
+    assert_eq!(ctx.cycle_read(0xFF02), 0x7F, "the transfer is done");
+
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = 0x80;
+    let cartridge = Cartridge::from_rom(rom.into_boxed_slice()).unwrap();
+    let mut ctx = Context::new(cartridge, HardwareMode::Cgb);
+    ctx.cycle_write(0xFF0F, 0x00);
+    ctx.cycle_write(0xFF04, 0x00);
+    ctx.cycle_write(0xFF02, 0x83);
+    assert_eq!(ctx.cycle_read(0xFF02), 0xFF);
+    // 262144 Hz: a bit every 4 M-cycles
+    assert!((28..=32).contains(&serial_transfer_cycles(&mut ctx)));
+}
+
+#[test]
+fn serial_external_clock() {
+    let mut ctx = context();
+    ctx.cycle_write(0xFF0F, 0x00);
+    ctx.cycle_write(0xFF02, 0x80);
+    for _ in 0..5000 {
+        ctx.cycle();
+    }
+    assert_eq!(
+        ctx.cycle_read(0xFF02),
+        0xFE,
+        "waits forever without a clock"
+    );
+    assert_eq!(ctx.cycle_read(0xFF0F) & 0x08, 0x00);
+}
+
+#[test]
+fn serial_cable_transfer() {
+    let (end_a, end_b) = link_cable();
+    let mut a = context();
+    let mut b = context();
+    a.connect_link(Box::new(end_a));
+    b.connect_link(Box::new(end_b));
+    b.cycle_write(0xFF0F, 0x00);
+    b.cycle_write(0xFF01, 0x99);
+    b.cycle_write(0xFF02, 0x80);
+    a.cycle_write(0xFF0F, 0x00);
+    a.cycle_write(0xFF01, 0x42);
+    a.cycle_write(0xFF02, 0x81);
+    // Keep both sides in lockstep
+    for _ in 0..1100 {
+        a.cycle();
+        b.cycle();
+    }
+    assert_eq!(a.cycle_read(0xFF01), 0x99);
+    assert_eq!(b.cycle_read(0xFF01), 0x42);
+    assert_eq!(a.cycle_read(0xFF0F) & 0x08, 0x08);
+    assert_eq!(b.cycle_read(0xFF0F) & 0x08, 0x08);
+    assert_eq!(b.cycle_read(0xFF02), 0x7E, "the slave's transfer is done");
+}
@@ -0,0 +1,158 @@
+use modular_bitfield::prelude::*;
+
+use crate::game_boy::hardware::HardwareMode;
+
+/// System counter left by the DMG boot ROM
+const POST_BOOT_COUNTER_DMG: u16 = 0xABCC;
+/// The CGB boot ROM runs for a different time, longer for DMG cartridges
+/// whose palette it picks, the values follow mGBA's post-boot state
+const POST_BOOT_COUNTER_CGB: u16 = 0x2F00;
+const POST_BOOT_COUNTER_DMG_COMPATIBILITY: u16 = 0x2600;
+
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Tac {
+    clock_select: B2,
+    enable: bool,
+    #[skip]
+    __: B5,
+}
+
+impl Tac {
+    /// Bit of the system counter whose falling edge increments TIMA
+    fn counter_mask(self) -> u16 {
+        match self.clock_select() {
+            0b00 => 1 << 9,
+            0b01 => 1 << 3,
+            0b10 => 1 << 5,
+            0b11 => 1 << 7,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum TimaState {
+    #[default]
+    Running,
+    /// TIMA overflowed this cycle and reads as 00, the reload happens next cycle
+    Overflowed,
+    /// TIMA was just reloaded from TMA, writes to TIMA are ignored this cycle
+    Reloading,
+}
+
+/// DIV/TIMA/TMA/TAC timer
+/// Every register is derived from a 16 bit system counter running at 4.194304 MHz,
+/// TIMA is incremented on the falling edge of (selected counter bit && timer enable)
+#[derive(Debug)]
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: Tac,
+    state: TimaState,
+}
+
+impl Timer {
+    /// Timer as it is left by the boot ROM of `hardware`
+    pub fn post_boot(hardware: HardwareMode) -> Self {
+        let counter = match hardware {
+            HardwareMode::Dmg => POST_BOOT_COUNTER_DMG,
+            HardwareMode::Cgb => POST_BOOT_COUNTER_CGB,
+            HardwareMode::DmgCompatibility => POST_BOOT_COUNTER_DMG_COMPATIBILITY,
+        };
+        Self {
+            counter,
+            tima: 0,
+            tma: 0,
+            tac: Tac::new(),
+            state: TimaState::Running,
+        }
+    }
+    /// Advance the timer by one M-cycle, returns true if the timer interrupt was triggered
+    pub fn tick(&mut self) -> bool {
+        let mut interrupt = false;
+        match self.state {
+            TimaState::Overflowed => {
+                self.tima = self.tma;
+                self.state = TimaState::Reloading;
+                interrupt = true;
+            }
+            TimaState::Reloading => self.state = TimaState::Running,
+            TimaState::Running => {}
+        }
+        self.set_counter(self.counter.wrapping_add(4));
+        interrupt
+    }
+    /// Reset the system counter, like a write to DIV
+    pub fn reset_div(&mut self) {
+        self.set_counter(0);
+    }
+    /// Upper byte of the system counter
+    pub fn div(&self) -> u8 {
+        self.counter.to_be_bytes()[0]
+    }
+    /// The full 16 bit system counter
+    pub fn counter(&self) -> u16 {
+        self.counter
+    }
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF04 => self.div(),
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            0xFF07 => 0xF8 | u8::from(self.tac),
+            _ => unreachable!("{addr:04x} is not a timer register"),
+        }
+    }
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            // Resetting the counter can produce a falling edge and increment TIMA
+            0xFF04 => self.reset_div(),
+            0xFF05 => match self.state {
+                // TMA is being copied into TIMA this cycle, which wins over the write
+                TimaState::Reloading => {}
+                // Writing during the overflow cycle cancels the reload and interrupt
+                TimaState::Overflowed => {
+                    self.tima = data;
+                    self.state = TimaState::Running;
+                }
+                TimaState::Running => self.tima = data,
+            },
+            0xFF06 => {
+                self.tma = data;
+                // The reload latches TMA during the whole cycle
+                if self.state == TimaState::Reloading {
+                    self.tima = data;
+                }
+            }
+            0xFF07 => {
+                let old_signal = self.signal();
+                self.tac = (data & 0x07).into();
+                self.detect_falling_edge(old_signal);
+            }
+            _ => unreachable!("{addr:04x} is not a timer register"),
+        }
+    }
+    fn signal(&self) -> bool {
+        self.tac.enable() && self.counter & self.tac.counter_mask() != 0
+    }
+    fn set_counter(&mut self, counter: u16) {
+        let old_signal = self.signal();
+        self.counter = counter;
+        self.detect_falling_edge(old_signal);
+    }
+    fn detect_falling_edge(&mut self, old_signal: bool) {
+        if old_signal && !self.signal() {
+            self.increment();
+        }
+    }
+    fn increment(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        if overflow {
+            self.state = TimaState::Overflowed;
+        }
+    }
+}
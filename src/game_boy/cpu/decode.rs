@@ -26,10 +26,10 @@ impl Opcode {
             // Block 0
             0x00 => match opcode & 0x0F {
                 0x00 | 0x08 => match opcode >> 3 {
-                    0x000 => Self::NOP,
-                    0x001 => Self::LD_imm16_sp,
-                    0x010 => Self::STOP,
-                    0x011 => Self::JR_imm8,
+                    0b000 => Self::NOP,
+                    0b001 => Self::LD_imm16_sp,
+                    0b010 => Self::STOP,
+                    0b011 => Self::JR_imm8,
                     _ => Self::JR_cond_imm8 { cond: attrs.cond() },
                 },
                 0x01 => Self::LD_r16_imm16 { dest: attrs.r16() },
@@ -213,14 +213,14 @@ impl OpcodeAttrs {
         use R8::*;
         use Reg8::*;
         match (self.0 >> shift) & 0b111 {
-            0b000 => Reg(A),
-            0b001 => Reg(B),
-            0b010 => Reg(C),
-            0b011 => Reg(D),
-            0b100 => Reg(E),
-            0b101 => Reg(H),
-            0b110 => Reg(L),
-            0b111 => HLaddr,
+            0b000 => Reg(B),
+            0b001 => Reg(C),
+            0b010 => Reg(D),
+            0b011 => Reg(E),
+            0b100 => Reg(H),
+            0b101 => Reg(L),
+            0b110 => HLaddr,
+            0b111 => Reg(A),
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,229 @@
+use super::{
+    Cpu, CpuContext,
+    instructions::{Execute, InputU8},
+    opcode::{CBOpcode, Opcode, R16mem},
+    registers::{Reg8, Reg16},
+};
+
+/// Step of an instruction, instructions are queued as a list of these when they start
+/// so the CPU can stop after any M-cycle and resume from there
+#[derive(Debug, Clone, Copy)]
+pub enum MicroOp {
+    /// Fetch the next opcode, latching pending interrupts if IME is set
+    Prefetch,
+    /// Fetch the first opcode of an interrupt handler
+    Fetch,
+    /// Internal M-cycle
+    Idle,
+    /// M-cycle spent in the current CPU state
+    State,
+    /// Read the byte at PC into a temporary register, incrementing PC
+    ReadImm(Temp),
+    /// Read a byte into Z
+    Read(Address),
+    Write(Address, Byte),
+    /// Decrement SP and write a byte there
+    Push(Byte),
+    /// Read the byte at SP into a temporary register, incrementing SP
+    Pop(Temp),
+    /// Write PC.low to the stack and jump to the handler of the interrupt being serviced
+    Dispatch,
+    // The following take no time, they run right after the previous M-cycle
+    /// Register changes of an opcode, operands read from memory are in Z and W
+    Execute(Opcode),
+    ExecuteCb(CBOpcode),
+    /// Load WZ into PC
+    Jump,
+}
+
+impl MicroOp {
+    pub fn takes_cycle(&self) -> bool {
+        !matches!(self, Self::Execute(_) | Self::ExecuteCb(_) | Self::Jump)
+    }
+}
+
+/// Temporary registers the CPU reads operands into
+#[derive(Debug, Clone, Copy)]
+pub enum Temp {
+    Z,
+    W,
+}
+
+/// 16 bit value an M-cycle uses
+#[derive(Debug, Clone, Copy)]
+pub enum Word {
+    Reg(Reg16),
+    Pc,
+    WZ,
+}
+
+/// 8 bit value an M-cycle writes
+#[derive(Debug, Clone, Copy)]
+pub enum Byte {
+    Reg(Reg8),
+    Z,
+    High(Word),
+    Low(Word),
+}
+
+/// Address of a memory M-cycle
+#[derive(Debug, Clone, Copy)]
+pub enum Address {
+    Reg(Reg16),
+    /// HL, incremented afterwards
+    HLi,
+    /// HL, decremented afterwards
+    HLd,
+    /// WZ, incremented afterwards
+    WZ,
+    /// FF00 + C
+    HighC,
+    /// FF00 + Z
+    HighZ,
+}
+
+impl From<R16mem> for Address {
+    fn from(r16mem: R16mem) -> Self {
+        match r16mem {
+            R16mem::BC => Self::Reg(Reg16::BC),
+            R16mem::DE => Self::Reg(Reg16::DE),
+            R16mem::HLi => Self::HLi,
+            R16mem::HLd => Self::HLd,
+        }
+    }
+}
+
+impl InputU8<Temp> for Cpu {
+    fn read(&self, temp: Temp) -> u8 {
+        match temp {
+            Temp::Z => self.z,
+            Temp::W => self.w,
+        }
+    }
+}
+
+impl Cpu {
+    /// Queue micro-ops after the ones already queued
+    pub fn queue_ops(&mut self, ops: &[MicroOp]) {
+        self.ops.extend(ops);
+    }
+    /// Queue micro-ops to run next, for the branches of conditional instructions
+    pub fn insert_ops(&mut self, ops: &[MicroOp]) {
+        for &op in ops.iter().rev() {
+            self.ops.push_front(op);
+        }
+    }
+    /// Run queued micro-ops up to and including the next M-cycle,
+    /// along with the register changes that follow it
+    pub fn run_micro_ops(&mut self, ctx: &mut impl CpuContext) {
+        let mut cycled = false;
+        while let Some(&op) = self.ops.front() {
+            if cycled && op.takes_cycle() {
+                break;
+            }
+            self.ops.pop_front();
+            cycled |= op.takes_cycle();
+            self.run_micro_op(ctx, op);
+        }
+        debug_assert!(cycled, "instructions end with an M-cycle");
+    }
+    fn run_micro_op(&mut self, ctx: &mut impl CpuContext, op: MicroOp) {
+        match op {
+            MicroOp::Prefetch => self.cycle_prefetch(ctx),
+            MicroOp::Fetch => {
+                let opcode = self.cycle_read_pc(ctx);
+                self.opcode = Opcode::lookup(opcode);
+            }
+            MicroOp::Idle => ctx.cycle(),
+            MicroOp::State => {
+                ctx.cycle_state_itrs(self.state);
+            }
+            MicroOp::ReadImm(temp) => {
+                let data = self.cycle_read_pc(ctx);
+                self.set_temp(temp, data);
+            }
+            MicroOp::Read(address) => {
+                let addr = self.address(address);
+                self.z = ctx.cycle_read(addr);
+            }
+            MicroOp::Write(address, byte) => {
+                let addr = self.address(address);
+                ctx.cycle_write(addr, self.byte(byte));
+            }
+            MicroOp::Push(byte) => {
+                self.regs.sp = self.regs.sp.wrapping_sub(1);
+                ctx.cycle_write(self.regs.sp, self.byte(byte));
+            }
+            MicroOp::Pop(temp) => {
+                let data = ctx.cycle_read(self.regs.sp);
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+                self.set_temp(temp, data);
+            }
+            MicroOp::Dispatch => {
+                self.regs.sp = self.regs.sp.wrapping_sub(1);
+                // The actual interrupt being serviced only matters in this cycle
+                let interrupts = ctx.cycle_write_itrs(self.regs.sp, self.regs.pc as u8);
+                if let Some(interrupt) = interrupts.highest_priority() {
+                    self.regs.pc = interrupt.handler_address();
+                    ctx.ack_interrupt(interrupt);
+                } else {
+                    // Bugged interrupt?
+                    self.regs.pc = 0
+                }
+            }
+            MicroOp::Execute(opcode) => self.execute(ctx, opcode),
+            MicroOp::ExecuteCb(opcode) => self.execute(ctx, opcode),
+            MicroOp::Jump => self.regs.pc = self.wz(),
+        }
+    }
+    pub fn wz(&self) -> u16 {
+        u16::from_be_bytes([self.w, self.z])
+    }
+    pub fn set_wz(&mut self, data: u16) {
+        [self.w, self.z] = data.to_be_bytes();
+    }
+    fn set_temp(&mut self, temp: Temp, data: u8) {
+        match temp {
+            Temp::Z => self.z = data,
+            Temp::W => self.w = data,
+        }
+    }
+    fn word(&self, word: Word) -> u16 {
+        match word {
+            Word::Reg(reg16) => self.regs.get16(reg16),
+            Word::Pc => self.regs.pc,
+            Word::WZ => self.wz(),
+        }
+    }
+    fn byte(&self, byte: Byte) -> u8 {
+        match byte {
+            Byte::Reg(reg8) => self.regs.get8(reg8),
+            Byte::Z => self.z,
+            Byte::High(word) => (self.word(word) >> 8) as u8,
+            Byte::Low(word) => self.word(word) as u8,
+        }
+    }
+    /// Address to access, applying its increment or decrement
+    fn address(&mut self, address: Address) -> u16 {
+        match address {
+            Address::Reg(reg16) => self.regs.get16(reg16),
+            Address::HLi => {
+                let hl = self.regs.get16(Reg16::HL);
+                self.regs.set16(Reg16::HL, hl.wrapping_add(1));
+                hl
+            }
+            Address::HLd => {
+                let hl = self.regs.get16(Reg16::HL);
+                self.regs.set16(Reg16::HL, hl.wrapping_sub(1));
+                hl
+            }
+            Address::WZ => {
+                let wz = self.wz();
+                self.set_wz(wz.wrapping_add(1));
+                wz
+            }
+            Address::HighC => 0xFF00 | self.regs.c as u16,
+            Address::HighZ => 0xFF00 | self.z as u16,
+        }
+    }
+}
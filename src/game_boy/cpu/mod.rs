@@ -1,11 +1,21 @@
-use instructions::{Execute, InputU8};
+use std::collections::VecDeque;
+
+use instructions::Execute;
+use micro_op::{Byte, MicroOp, Temp, Word};
 use opcode::Opcode;
 use registers::Registers;
 
-use super::context::interrupts::{Interrupt, InterruptFlags};
+use super::{
+    context::interrupts::{Interrupt, InterruptFlags},
+    hardware::HardwareMode,
+};
+
+/// M-cycles the CPU stays halted after a CGB speed switch
+const SPEED_SWITCH_HALT_CYCLES: u32 = 2050;
 
 mod decode;
 mod instructions;
+mod micro_op;
 pub mod opcode;
 pub mod registers;
 #[cfg(test)]
@@ -25,6 +35,11 @@ pub struct Cpu {
     rqst_itrs: InterruptFlags,
     /// Current state
     state: CPUState,
+    /// Temporary registers operands are read into
+    z: u8,
+    w: u8,
+    /// Micro-ops left in the instruction in progress
+    ops: VecDeque<MicroOp>,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -37,6 +52,8 @@ pub enum CPUState {
     Halt(u32),
     // In STOP mode the CPU does nothing while waiting for input
     Stop,
+    // After executing an illegal opcode the CPU hangs until reset
+    Locked,
 }
 
 impl CPUState {
@@ -67,9 +84,18 @@ impl CPUState {
     pub fn is_halt(&self) -> bool {
         matches!(self, Self::Halt(_))
     }
+    pub fn has_halt_timer(&self) -> bool {
+        matches!(self, Self::Halt(timer) if *timer > 0)
+    }
     pub fn is_stop(&self) -> bool {
         matches!(self, Self::Stop)
     }
+    pub fn set_locked(&mut self) {
+        *self = Self::Locked
+    }
+    pub fn is_locked(&self) -> bool {
+        matches!(self, Self::Locked)
+    }
 }
 
 pub trait CpuContext {
@@ -95,15 +121,47 @@ pub trait CpuContext {
     fn ack_interrupt(&mut self, itr: Interrupt);
     /// Check if theres a pending interrupt
     fn has_interrupt(&mut self) -> bool;
-    /// Switch context speed (CGB)
-    fn speed_switch(&mut self);
+    /// Switch context speed if it was prepared through KEY1 (CGB)
+    /// Returns true if the speed was switched
+    fn speed_switch(&mut self) -> bool;
     /// Check input line for a pressed button
     fn has_pressed_input(&self) -> bool;
+    /// Enter STOP mode, resetting DIV and stopping the system clock
+    fn enter_stop(&mut self);
 }
 
 impl Cpu {
+    /// CPU as it is left by the boot ROM, about to execute the cartridge entry point
+    pub fn post_boot(hardware: HardwareMode) -> Self {
+        Self {
+            regs: Registers::post_boot(hardware),
+            ..Default::default()
+        }
+    }
+    pub fn registers(&self) -> &Registers {
+        &self.regs
+    }
+    /// Address of the illegal opcode that locked up the CPU
+    pub fn locked_address(&self) -> Option<u16> {
+        // The opcode was already fetched, so PC points past it
+        self.state.is_locked().then(|| self.regs.pc.wrapping_sub(1))
+    }
+    /// Run until the instruction in progress, or a new one, is completed
     pub fn step(&mut self, ctx: &mut impl CpuContext) {
-        if self.state.is_stop() {
+        while !self.step_cycle(ctx) {}
+    }
+    /// Whether an instruction was started but not finished
+    pub fn in_progress(&self) -> bool {
+        !self.ops.is_empty()
+    }
+    /// Advance by a single M-cycle, returns true if it completed the instruction
+    pub fn step_cycle(&mut self, ctx: &mut impl CpuContext) -> bool {
+        if self.in_progress() {
+            self.run_micro_ops(ctx);
+        } else if self.state.is_locked() {
+            // Interrupts are ignored but the rest of the system keeps running
+            ctx.cycle_state_itrs(self.state);
+        } else if self.state.is_stop() {
             // In STOP mode the CPU does nothing while waiting for input
             ctx.cycle_state_itrs(self.state);
             if ctx.has_pressed_input() {
@@ -121,40 +179,34 @@ impl Cpu {
             // Interrupt servicing happens after fetching the next opcode
             // As that wont be executed rn, we need to adjust the program counter
             self.regs.dec_pc();
-
-            // 2: Decrement SP
-            self.cycle(ctx);
-            self.regs.sp = self.regs.sp.wrapping_sub(1);
-
-            let [hi, lo] = self.regs.pc.to_be_bytes();
-            // 3: Write PC.high to SP, decrement SP
-            self.cycle_write(ctx, self.regs.sp, hi);
-            self.regs.sp = self.regs.sp.wrapping_sub(1);
-
-            // 4: Write PC.low to SP, set PC to the interrupt handler
-            // The actual interrupt being serviced only matters in this cycle
-            let interrupts = ctx.cycle_write_itrs(self.regs.sp, lo);
-            if let Some(interrupt) = interrupts.highest_priority() {
-                self.regs.pc = interrupt.handler_address();
-                ctx.ack_interrupt(interrupt);
-            } else {
-                // Bugged interrupt?
-                self.regs.pc = 0
-            }
-
-            // 5: Generic fetch
-            self.opcode = Opcode::lookup(self.cycle_read_pc(ctx));
+            self.queue_ops(&[
+                // 2: Decrement SP
+                MicroOp::Idle,
+                // 3: Write PC.high to SP, decrement SP
+                MicroOp::Push(Byte::High(Word::Pc)),
+                // 4: Write PC.low to SP, set PC to the interrupt handler
+                MicroOp::Dispatch,
+                // 5: Generic fetch
+                MicroOp::Fetch,
+            ]);
+            self.run_micro_ops(ctx);
         } else if self.state.is_halt() {
             // In HALT mode the CPU does nothing while waiting for an interrupt
-            self.rqst_itrs = ctx.cycle_state_itrs(self.state);
-            // May also exit with a timer
-            if self.state.dec_halt_timer() {
-                self.state.reset();
+            let rqst_itrs = ctx.cycle_state_itrs(self.state);
+            if self.state.has_halt_timer() {
+                // The HALT after a speed switch only exits with its timer
+                if self.state.dec_halt_timer() {
+                    self.state.reset();
+                }
+            } else {
+                self.rqst_itrs = rqst_itrs;
             }
         } else {
             let opcode = self.opcode;
-            self.execute(ctx, opcode);
+            self.queue(opcode);
+            self.run_micro_ops(ctx);
         }
+        !self.in_progress()
     }
     pub fn cycle_prefetch(&mut self, ctx: &mut impl CpuContext) {
         let pc = self.regs.pc;
@@ -170,45 +222,44 @@ impl Cpu {
             self.rqst_itrs = rqst_itrs;
         }
     }
-    // The halt function is here and not in instructions.rs bc it's logic is
-    // entangled with cycle_prefetch and the halt bug
-    pub fn halt(&mut self, ctx: &mut impl CpuContext) {
-        self.state.set_halt();
-        self.cycle_prefetch(ctx);
-    }
-    fn cycle(&self, ctx: &mut impl CpuContext) {
-        ctx.cycle();
-    }
-    pub fn cycle_read(&self, ctx: &mut impl CpuContext, addr: u16) -> u8 {
-        ctx.cycle_read(addr)
-    }
     pub fn cycle_read_pc(&mut self, ctx: &mut impl CpuContext) -> u8 {
         let addr = self.regs.pc;
         self.regs.inc_pc();
-        self.cycle_read(ctx, addr)
-    }
-    pub fn cycle_write(&self, ctx: &mut impl CpuContext, addr: u16, data: u8) {
-        ctx.cycle_write(addr, data);
-    }
-    /// Push 16 bit value to the stack, takes 3 cycles
-    pub fn cycle_push16(&mut self, ctx: &mut impl CpuContext, data: u16) {
-        let [hi, lo] = data.to_be_bytes();
-        self.cycle(ctx);
-        self.regs.sp = self.regs.sp.wrapping_sub(1);
-        self.cycle_write(ctx, self.regs.sp, hi);
-        self.regs.sp = self.regs.sp.wrapping_sub(1);
-        self.cycle_write(ctx, self.regs.sp, lo);
-    }
-    /// Pop 16 bit value from the stack, takes 2 cycles
-    pub fn cycle_pop16(&mut self, ctx: &mut impl CpuContext) -> u16 {
-        let lo = self.cycle_read(ctx, self.regs.sp);
-        self.regs.sp = self.regs.sp.wrapping_add(1);
-        let hi = self.cycle_read(ctx, self.regs.sp);
-        self.regs.sp = self.regs.sp.wrapping_add(1);
-        u16::from_be_bytes([hi, lo])
+        ctx.cycle_read(addr)
     }
-
+    /// Queues the rest of STOP, which depends on the state of the system
     pub fn stop(&mut self, ctx: &mut impl CpuContext) {
-        todo!()
+        use MicroOp::{Prefetch, ReadImm};
+        let interrupt_pending = ctx.has_interrupt();
+        if ctx.has_pressed_input() {
+            if interrupt_pending {
+                // STOP is a 1 byte opcode and nothing happens
+                self.queue_ops(&[Prefetch]);
+            } else {
+                // The byte after STOP is skipped and HALT mode is entered instead
+                self.state.set_halt();
+                self.queue_ops(&[ReadImm(Temp::Z), Prefetch]);
+            }
+            return;
+        }
+        if ctx.speed_switch() {
+            if interrupt_pending {
+                // With an interrupt pending STOP is a 1 byte opcode and the CPU keeps going
+                self.queue_ops(&[Prefetch]);
+            } else {
+                // Otherwise the byte after STOP is skipped and the CPU waits
+                // in HALT mode until the clock is stable
+                self.state.set_halt_timer(SPEED_SWITCH_HALT_CYCLES);
+                self.queue_ops(&[ReadImm(Temp::Z), Prefetch]);
+            }
+            return;
+        }
+        ctx.enter_stop();
+        // The byte after STOP is only skipped if no interrupt is pending
+        if !interrupt_pending {
+            self.queue_ops(&[ReadImm(Temp::Z)]);
+        }
+        self.state.set_stop();
+        self.queue_ops(&[Prefetch]);
     }
 }
@@ -7,7 +7,7 @@ use compact_str::{CompactString, format_compact};
 use enum_assoc::Assoc;
 
 use super::{
-    Cpu, CpuContext,
+    Cpu,
     instructions::{InputU8, OutputU8},
     registers::{Reg8, Reg16},
 };
@@ -186,6 +186,26 @@ pub enum CBOpcode {
     SET { b3: u8, r8: R8 },
 }
 
+impl CBOpcode {
+    /// Operand of the opcode
+    pub fn r8(&self) -> R8 {
+        use CBOpcode::*;
+        match *self {
+            RLC { r8 }
+            | RRC { r8 }
+            | RL { r8 }
+            | RR { r8 }
+            | SLA { r8 }
+            | SRA { r8 }
+            | SWAP { r8 }
+            | SRL { r8 }
+            | BIT { r8, .. }
+            | RES { r8, .. }
+            | SET { r8, .. } => r8,
+        }
+    }
+}
+
 /// Reference to specific registers for use in Opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum R16mem {
@@ -207,59 +227,6 @@ impl Display for R16mem {
     }
 }
 
-impl InputU8<R16mem> for Cpu {
-    fn read(&mut self, ctx: &mut impl CpuContext, input: R16mem) -> u8 {
-        use R16mem::*;
-        match input {
-            BC => {
-                let addr = self.regs.get16(Reg16::BC);
-                self.cycle_read(ctx, addr)
-            }
-            DE => {
-                let addr = self.regs.get16(Reg16::DE);
-                self.cycle_read(ctx, addr)
-            }
-            HLi => {
-                let addr = self.regs.get16(Reg16::HL);
-                let res = self.cycle_read(ctx, addr);
-                self.regs.set16(Reg16::HL, addr.wrapping_add(1));
-                res
-            }
-            HLd => {
-                let addr = self.regs.get16(Reg16::HL);
-                let res = self.cycle_read(ctx, addr);
-                self.regs.set16(Reg16::HL, addr.wrapping_sub(1));
-                res
-            }
-        }
-    }
-}
-impl OutputU8<R16mem> for Cpu {
-    fn write(&mut self, ctx: &mut impl CpuContext, output: R16mem, data: u8) {
-        use R16mem::*;
-        match output {
-            BC => {
-                let addr = self.regs.get16(Reg16::BC);
-                self.cycle_write(ctx, addr, data);
-            }
-            DE => {
-                let addr = self.regs.get16(Reg16::DE);
-                self.cycle_write(ctx, addr, data);
-            }
-            HLi => {
-                let addr = self.regs.get16(Reg16::HL);
-                self.cycle_write(ctx, addr, data);
-                self.regs.set16(Reg16::HL, addr.wrapping_add(1));
-            }
-            HLd => {
-                let addr = self.regs.get16(Reg16::HL);
-                self.cycle_write(ctx, addr, data);
-                self.regs.set16(Reg16::HL, addr.wrapping_sub(1));
-            }
-        }
-    }
-}
-
 /// Reference to 8-bit registers and [hl] for use in Opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum R8 {
@@ -278,26 +245,20 @@ impl Display for R8 {
 }
 
 impl InputU8<R8> for Cpu {
-    fn read(&mut self, ctx: &mut impl CpuContext, input: R8) -> u8 {
+    fn read(&self, input: R8) -> u8 {
         use R8::*;
         match input {
             Reg(reg8) => self.regs.get8(reg8),
-            HLaddr => {
-                let hl = self.regs.get16(Reg16::HL);
-                self.cycle_read(ctx, hl)
-            }
+            HLaddr => self.z,
         }
     }
 }
 impl OutputU8<R8> for Cpu {
-    fn write(&mut self, ctx: &mut impl CpuContext, output: R8, data: u8) {
+    fn write(&mut self, output: R8, data: u8) {
         use R8::*;
         match output {
             Reg(reg8) => self.regs.set8(reg8, data),
-            HLaddr => {
-                let hl = self.regs.get16(Reg16::HL);
-                self.cycle_write(ctx, hl, data);
-            }
+            HLaddr => self.z = data,
         }
     }
 }
@@ -2,9 +2,11 @@ use std::fmt::{Display, Write};
 
 use modular_bitfield::prelude::*;
 
+use crate::game_boy::hardware::HardwareMode;
+
 use super::{
-    Cpu, CpuContext,
-    instructions::{InputU8, InputU16, OutputU8, OutputU16},
+    Cpu,
+    instructions::{InputU8, OutputU8},
 };
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -34,6 +36,36 @@ pub struct Flags {
 }
 
 impl Registers {
+    /// Register values left by the boot ROM when it jumps to the cartridge
+    pub fn post_boot(hardware: HardwareMode) -> Self {
+        match hardware {
+            HardwareMode::Dmg => Self {
+                a: 0x01,
+                f: 0xB0.into(),
+                b: 0x00,
+                c: 0x13,
+                d: 0x00,
+                e: 0xD8,
+                h: 0x01,
+                l: 0x4D,
+                sp: 0xFFFE,
+                pc: 0x0100,
+            },
+            // A = 11 is how games detect they are running on a CGB
+            HardwareMode::Cgb | HardwareMode::DmgCompatibility => Self {
+                a: 0x11,
+                f: 0x80.into(),
+                b: 0x00,
+                c: 0x00,
+                d: 0xFF,
+                e: 0x56,
+                h: 0x00,
+                l: 0x0D,
+                sp: 0xFFFE,
+                pc: 0x0100,
+            },
+        }
+    }
     pub fn inc_pc(&mut self) {
         self.pc = self.pc.wrapping_add(1);
     }
@@ -88,17 +120,6 @@ impl Display for Reg16 {
     }
 }
 
-impl InputU16<Reg16> for Cpu {
-    fn read16(&mut self, _: &mut impl CpuContext, reg: Reg16) -> u16 {
-        self.regs.get16(reg)
-    }
-}
-impl OutputU16<Reg16> for Cpu {
-    fn write16(&mut self, _: &mut impl CpuContext, reg: Reg16, data: u16) {
-        self.regs.set16(reg, data)
-    }
-}
-
 impl Registers {
     pub fn get16(&self, reg: Reg16) -> u16 {
         use Reg16::*;
@@ -165,12 +186,12 @@ impl Display for Reg8 {
 }
 
 impl InputU8<Reg8> for Cpu {
-    fn read(&mut self, _: &mut impl CpuContext, reg: Reg8) -> u8 {
+    fn read(&self, reg: Reg8) -> u8 {
         self.regs.get8(reg)
     }
 }
 impl OutputU8<Reg8> for Cpu {
-    fn write(&mut self, _: &mut impl CpuContext, reg: Reg8, data: u8) {
+    fn write(&mut self, reg: Reg8, data: u8) {
         self.regs.set8(reg, data);
     }
 }
@@ -3,6 +3,7 @@ use crate::game_boy::context::interrupts::{Interrupt, InterruptFlags};
 use super::{
     CPUState, Cpu, CpuContext,
     opcode::{Condition, Opcode, R8},
+    registers::{Reg8, Reg16},
 };
 
 struct StubContext {
@@ -41,20 +42,20 @@ impl CpuContext for StubContext {
         false
     }
 
-    fn speed_switch(&mut self) {}
+    fn speed_switch(&mut self) -> bool {
+        false
+    }
 
     fn has_pressed_input(&self) -> bool {
         false
     }
+
+    fn enter_stop(&mut self) {}
 }
 #[test]
 fn instruction_duration() {
     for i in 0..255u8 {
         let opcode = Opcode::lookup(i);
-        if matches!(opcode, Opcode::STOP | Opcode::INVALID) {
-            // TODO: remove when instructions are implemented
-            continue;
-        }
         let mut cpu = Cpu::default();
         let mut context = StubContext::with_read_value(i);
 
@@ -103,7 +104,7 @@ fn instruction_duration() {
             Opcode::CPL => 1,
             Opcode::SCF => 1,
             Opcode::CCF => 1,
-            Opcode::JR_imm8 => 2,
+            Opcode::JR_imm8 => 3,
             Opcode::JR_cond_imm8 { cond } => {
                 if matches!(cond, Condition::NZ | Condition::NC) {
                     3
@@ -230,8 +231,7 @@ fn instruction_duration() {
             Opcode::INVALID => 1,
         };
         if matches!(opcode, Opcode::INVALID) {
-            // TODO: test invalid event
-            continue;
+            assert_eq!(cpu.locked_address(), Some(0x0000), "{opcode} locks the CPU");
         }
         assert_eq!(
             context.cycle_count, instruction_duration,
@@ -239,3 +239,256 @@ fn instruction_duration() {
         );
     }
 }
+
+/// Flat 64 KiB memory to run small programs from address 0
+struct RamContext {
+    memory: Box<[u8; 0x10000]>,
+    cycle_count: usize,
+}
+
+impl RamContext {
+    fn with_program(program: &[u8]) -> Self {
+        let mut memory = Box::new([0; 0x10000]);
+        memory[..program.len()].copy_from_slice(program);
+        Self {
+            memory,
+            cycle_count: 0,
+        }
+    }
+}
+
+impl CpuContext for RamContext {
+    fn cycle_read_itrs(&mut self, addr: u16) -> (u8, InterruptFlags) {
+        self.cycle();
+        (self.memory[addr as usize], InterruptFlags::new())
+    }
+
+    fn cycle_write_itrs(&mut self, addr: u16, data: u8) -> InterruptFlags {
+        self.cycle();
+        self.memory[addr as usize] = data;
+        InterruptFlags::new()
+    }
+
+    fn cycle_state_itrs(&mut self, _state: CPUState) -> InterruptFlags {
+        self.cycle_count += 1;
+        InterruptFlags::new()
+    }
+
+    fn ack_interrupt(&mut self, _: Interrupt) {}
+
+    fn has_interrupt(&mut self) -> bool {
+        false
+    }
+
+    fn speed_switch(&mut self) -> bool {
+        false
+    }
+
+    fn has_pressed_input(&self) -> bool {
+        false
+    }
+
+    fn enter_stop(&mut self) {}
+}
+
+/// Fetch the first opcode of `program`, the CPU is then ready to execute it
+fn load(program: &[u8]) -> (Cpu, RamContext) {
+    let mut cpu = Cpu::default();
+    let mut ctx = RamContext::with_program(program);
+    cpu.step(&mut ctx);
+    (cpu, ctx)
+}
+
+#[test]
+fn decode_block0_and_r8() {
+    assert!(matches!(Opcode::lookup(0x10), Opcode::STOP));
+    assert!(matches!(Opcode::lookup(0x18), Opcode::JR_imm8));
+    assert!(matches!(
+        Opcode::lookup(0x20),
+        Opcode::JR_cond_imm8 {
+            cond: Condition::NZ
+        }
+    ));
+    assert!(matches!(
+        Opcode::lookup(0x47),
+        Opcode::LD_r8_r8 {
+            dest: R8::Reg(Reg8::B),
+            src: R8::Reg(Reg8::A)
+        }
+    ));
+    assert!(matches!(
+        Opcode::lookup(0x7E),
+        Opcode::LD_r8_r8 {
+            dest: R8::Reg(Reg8::A),
+            src: R8::HLaddr
+        }
+    ));
+}
+
+#[test]
+fn jr_offsets_are_signed() {
+    // NOP; INC A; JR -3 (back to INC A)
+    let (mut cpu, mut ctx) = load(&[0x00, 0x3C, 0x18, 0xFD]);
+    for _ in 0..4 {
+        cpu.step(&mut ctx);
+    }
+    assert_eq!(cpu.regs.a, 2);
+    assert_eq!(cpu.regs.pc, 0x0003, "JR is prefetched again");
+
+    // JR +2 over LD A,$11 to LD A,$22
+    let (mut cpu, mut ctx) = load(&[0x18, 0x02, 0x3E, 0x11, 0x3E, 0x22]);
+    cpu.step(&mut ctx);
+    cpu.step(&mut ctx);
+    assert_eq!(cpu.regs.a, 0x22);
+}
+
+#[test]
+fn cb_shifts_write_their_operand() {
+    let (mut cpu, mut ctx) = load(&[
+        0x3E, 0x11, // LD A,$11
+        0xB7, // OR A (clears carry)
+        0x06, 0x81, // LD B,$81
+        0xCB, 0x18, // RR B
+        0xCB, 0x10, // RL B
+        0xCB, 0x28, // SRA B
+        0xCB, 0x30, // SWAP B
+        0xCB, 0x08, // RRC B
+    ]);
+    for _ in 0..3 {
+        cpu.step(&mut ctx);
+    }
+    let expected = [
+        (0x40, true),
+        (0x81, false),
+        (0xC0, true),
+        (0x0C, false),
+        (0x06, false),
+    ];
+    for (b, c) in expected {
+        cpu.step(&mut ctx);
+        assert_eq!((cpu.regs.b, cpu.regs.get_c_flag()), (b, c));
+    }
+    assert_eq!(cpu.regs.a, 0x11, "A is left untouched");
+
+    // RRC sets carry from the bit rotated out
+    let (mut cpu, mut ctx) = load(&[0x06, 0x01, 0xCB, 0x08]);
+    for _ in 0..2 {
+        cpu.step(&mut ctx);
+    }
+    assert_eq!((cpu.regs.b, cpu.regs.get_c_flag()), (0x80, true));
+}
+
+#[test]
+fn arithmetic_half_carry_and_carry() {
+    let (mut cpu, mut ctx) = load(&[
+        0x06, 0x0F, // LD B,$0F
+        0x04, // INC B
+        0x05, // DEC B
+        0x21, 0xFF, 0x0F, // LD HL,$0FFF
+        0x01, 0x01, 0x00, // LD BC,$0001
+        0x09, // ADD HL,BC
+    ]);
+    cpu.step(&mut ctx);
+    cpu.step(&mut ctx);
+    assert_eq!((cpu.regs.b, cpu.regs.get_h_flag()), (0x10, true), "INC");
+    cpu.step(&mut ctx);
+    assert_eq!((cpu.regs.b, cpu.regs.get_h_flag()), (0x0F, true), "DEC");
+    for _ in 0..3 {
+        cpu.step(&mut ctx);
+    }
+    assert_eq!(cpu.regs.get16(Reg16::HL), 0x1000);
+    assert!(cpu.regs.get_h_flag(), "ADD HL carries out of bit 11");
+    assert!(!cpu.regs.get_c_flag());
+}
+
+#[test]
+fn stack_pointer_arithmetic() {
+    let (mut cpu, mut ctx) = load(&[
+        0x31, 0xFF, 0x00, // LD SP,$00FF
+        0xE8, 0x01, // ADD SP,1
+        0xF8, 0x01, // LD HL,SP+1
+        0x21, 0x00, 0xC0, // LD HL,$C000
+        0xF9, // LD SP,HL
+    ]);
+    cpu.step(&mut ctx);
+    cpu.step(&mut ctx);
+    assert_eq!(cpu.regs.sp, 0x0100);
+    assert!(cpu.regs.get_h_flag() && cpu.regs.get_c_flag());
+    cpu.step(&mut ctx);
+    assert_eq!(cpu.regs.get16(Reg16::HL), 0x0101);
+    assert!(!cpu.regs.get_h_flag() && !cpu.regs.get_c_flag());
+    cpu.step(&mut ctx);
+    cpu.step(&mut ctx);
+    assert_eq!(cpu.regs.sp, 0xC000, "LD SP,HL copies HL into SP");
+}
+
+#[test]
+fn cycle_stepping_matches_instruction_stepping() {
+    let program = [
+        0x31, 0x00, 0xD0, // LD SP,$D000
+        0x21, 0x00, 0xC0, // LD HL,$C000
+        0x3E, 0x5A, // LD A,$5A
+        0x22, // LD (HL+),A
+        0xCD, 0x20, 0x00, // CALL $0020
+        0xCB, 0x16, // RL (HL)
+        0x18, 0xFE, // JR -2
+    ];
+    let mut program = program.to_vec();
+    program.resize(0x20, 0x00);
+    // PUSH HL; INC (HL); POP BC; RET
+    program.extend([0xE5, 0x34, 0xC1, 0xC9]);
+    let (mut cpu, mut ctx) = load(&program);
+    for _ in 0..10 {
+        cpu.step(&mut ctx);
+    }
+    let (mut cycled_cpu, mut cycled_ctx) = load(&program);
+    let mut cycles = 0;
+    let mut instructions = 0;
+    while instructions < 10 {
+        let cycle_count = cycled_ctx.cycle_count;
+        instructions += cycled_cpu.step_cycle(&mut cycled_ctx) as usize;
+        assert_eq!(
+            cycled_ctx.cycle_count,
+            cycle_count + 1,
+            "one M-cycle per step"
+        );
+        cycles += 1;
+    }
+    assert!(!cycled_cpu.in_progress());
+    assert_eq!(format!("{:?}", cycled_cpu.regs), format!("{:?}", cpu.regs));
+    assert_eq!(cycled_ctx.memory, ctx.memory);
+    // 3 + 3 + 2 + 2 + 6 + 4 + 3 + 3 + 4 + 4
+    assert_eq!(cycles, 34);
+}
+
+#[test]
+fn instructions_resume_between_m_cycles() {
+    let (mut cpu, mut ctx) = load(&[
+        0x31, 0x00, 0xD0, // LD SP,$D000
+        0xCD, 0x34, 0x12, // CALL $1234
+    ]);
+    cpu.step(&mut ctx);
+    // Read the address, then an internal cycle
+    for _ in 0..3 {
+        assert!(!cpu.step_cycle(&mut ctx));
+    }
+    assert_eq!(cpu.regs.sp, 0xD000, "nothing pushed yet");
+    assert!(!cpu.step_cycle(&mut ctx));
+    assert_eq!((cpu.regs.sp, ctx.memory[0xCFFF]), (0xCFFF, 0x00), "PC.high");
+    assert_eq!(cpu.regs.pc, 0x0006, "PC is only loaded once it's pushed");
+    assert!(!cpu.step_cycle(&mut ctx));
+    assert_eq!((cpu.regs.sp, ctx.memory[0xCFFE]), (0xCFFE, 0x06), "PC.low");
+    assert_eq!(cpu.regs.pc, 0x1234);
+    // Fetch from the new address
+    assert!(cpu.step_cycle(&mut ctx));
+    assert!(!cpu.in_progress());
+    assert_eq!(cpu.regs.pc, 0x1235);
+
+    // Stepping by instruction finishes one started by M-cycle
+    let (mut cpu, mut ctx) = load(&[0xCD, 0x34, 0x12]);
+    cpu.step_cycle(&mut ctx);
+    cpu.step(&mut ctx);
+    assert!(!cpu.in_progress());
+    assert_eq!(ctx.cycle_count, 1 + 6);
+    assert_eq!(cpu.regs.pc, 0x1235);
+}
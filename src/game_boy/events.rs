@@ -4,10 +4,17 @@ use modular_bitfield::prelude::*;
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Events {
-    vblank: bool,
-    breakpoint: bool,
+    /// A frame was completed and the PPU entered VBlank
+    pub vblank: bool,
+    pub breakpoint: bool,
+    /// The CPU executed an illegal opcode and locked up
+    pub cpu_locked: bool,
+    /// The cartridge's rumble motor was turned on or off
+    pub rumble_changed: bool,
+    /// The link cable waits for the other side, the system doesn't advance until it catches up
+    pub link_stalled: bool,
     #[skip]
-    __: B6,
+    __: B3,
 }
 
 impl Events {
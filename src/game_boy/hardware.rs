@@ -0,0 +1,49 @@
+use super::{Cartridge, config::Model};
+
+/// How the console behaves for the loaded cartridge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HardwareMode {
+    /// Original Game Boy
+    #[default]
+    Dmg,
+    /// Game Boy Color running a cartridge with CGB support
+    Cgb,
+    /// Game Boy Color running a DMG-only cartridge, selected by the boot ROM through KEY0
+    DmgCompatibility,
+}
+
+impl HardwareMode {
+    /// Pick the mode like the boot ROM does, from the cartridge header
+    pub fn select(model: Model, cartridge: &Cartridge) -> Self {
+        match model {
+            Model::Dmg => Self::Dmg,
+            Model::Auto if !cartridge.supports_cgb() => Self::Dmg,
+            Model::Auto | Model::Cgb if cartridge.supports_cgb() => Self::Cgb,
+            Model::Auto | Model::Cgb => Self::DmgCompatibility,
+        }
+    }
+    /// Whether the console itself is a Game Boy Color
+    pub fn is_cgb_hardware(self) -> bool {
+        !matches!(self, Self::Dmg)
+    }
+    /// Whether CGB-only features (banking, color palettes, attributes) are unlocked
+    pub fn cgb_features(self) -> bool {
+        matches!(self, Self::Cgb)
+    }
+    /// Mode selected by a KEY0 write on CGB hardware
+    pub fn from_key0(key0: u8) -> Self {
+        // Bits 2-3 select the CPU mode, anything but 0 disables the CGB features
+        if key0 & 0x0C == 0 {
+            Self::Cgb
+        } else {
+            Self::DmgCompatibility
+        }
+    }
+    /// Value the boot ROM leaves in KEY0
+    pub fn key0(self) -> u8 {
+        match self {
+            Self::Dmg | Self::Cgb => 0x00,
+            Self::DmgCompatibility => 0x04,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// 8-bit grayscale image
+#[derive(Debug, Clone)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Box<[u8]>,
+}
+
+impl GrayImage {
+    /// Load a PNG of any color type, colors are converted to their luma
+    pub fn load_png(path: &Path) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let channels = info.color_type.samples();
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| match pixel {
+                [r, g, b, ..] if channels >= 3 => {
+                    ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8
+                }
+                [gray, ..] => *gray,
+                [] => 0,
+            })
+            .collect();
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+    /// Pixel at `x`, `y` of this image stretched to `width` x `height`
+    pub fn sample(&self, x: usize, y: usize, width: usize, height: usize) -> u8 {
+        let x = x * self.width / width;
+        let y = y * self.height / height;
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Save rows of `width` gray pixels as a PNG, creating its directory if needed
+pub fn save_png(path: &Path, width: usize, pixels: &[u8]) -> Result<(), png::EncodingError> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let height = pixels.len() / width;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        width as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()
+}
@@ -0,0 +1,58 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+/// What an infrared port sees, shared by the CGB RP port and cartridges with an IR LED
+/// Two ports facing each other see each other's LED
+pub trait InfraredPeer: Debug {
+    /// Our LED was turned on or off
+    fn set_led(&mut self, on: bool);
+    /// Whether our receiver sees light
+    fn light(&self) -> bool;
+}
+
+/// Nothing in front of the port
+#[derive(Debug, Default)]
+pub struct NoLight;
+
+impl InfraredPeer for NoLight {
+    fn set_led(&mut self, _on: bool) {}
+    fn light(&self) -> bool {
+        false
+    }
+}
+
+/// One of two in-process ports facing each other
+#[derive(Debug)]
+pub struct InfraredEnd {
+    leds: Arc<Mutex<[bool; 2]>>,
+    index: usize,
+}
+
+/// Point two ports at each other, connect one end to each system
+pub fn infrared_beam() -> (InfraredEnd, InfraredEnd) {
+    let leds = Arc::new(Mutex::new([false; 2]));
+    (
+        InfraredEnd {
+            leds: leds.clone(),
+            index: 0,
+        },
+        InfraredEnd { leds, index: 1 },
+    )
+}
+
+impl InfraredPeer for InfraredEnd {
+    fn set_led(&mut self, on: bool) {
+        self.leds.lock().unwrap()[self.index] = on;
+    }
+    fn light(&self) -> bool {
+        self.leds.lock().unwrap()[1 - self.index]
+    }
+}
+
+impl Drop for InfraredEnd {
+    fn drop(&mut self) {
+        self.set_led(false);
+    }
+}
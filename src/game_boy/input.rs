@@ -28,3 +28,11 @@ impl Input {
         !(byte & 0b1111)
     }
 }
+
+/// Tilt of the console for cartridges with an accelerometer, in g from -1.0 to 1.0 on each axis
+/// Positive x lowers the right side, positive y lowers the bottom side
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tilt {
+    pub x: f32,
+    pub y: f32,
+}
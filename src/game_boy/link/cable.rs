@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+use crate::game_boy::time::SystemTime;
+
+use super::{LinkPeer, NO_CABLE};
+
+/// What one side of the cable is presenting to the other
+#[derive(Debug, Default)]
+struct Side {
+    /// SB of a side waiting for the other side's clock
+    offer: Option<u8>,
+    /// Byte shifted in by the other side, delivered once this side reaches the timestamp
+    incoming: Option<(SystemTime, u8)>,
+}
+
+/// One plug of a cable connecting two in-process systems
+/// Both systems must be stepped so their times stay close together,
+/// a transfer is delivered when the receiving side reaches the time it completed
+#[derive(Debug)]
+pub struct CableEnd {
+    sides: Arc<Mutex<[Side; 2]>>,
+    index: usize,
+}
+
+/// Create a cable, plug one end into each system
+pub fn link_cable() -> (CableEnd, CableEnd) {
+    let sides = Arc::new(Mutex::new(Default::default()));
+    (
+        CableEnd {
+            sides: sides.clone(),
+            index: 0,
+        },
+        CableEnd { sides, index: 1 },
+    )
+}
+
+impl LinkPeer for CableEnd {
+    fn exchange(&mut self, time: SystemTime, data: u8) -> u8 {
+        let mut sides = self.sides.lock().unwrap();
+        let other = &mut sides[1 - self.index];
+        // The other side only shifts while it has a transfer pending
+        match other.offer.take() {
+            Some(reply) => {
+                other.incoming = Some((time, data));
+                reply
+            }
+            None => NO_CABLE,
+        }
+    }
+    fn listen(&mut self, data: Option<u8>) {
+        self.sides.lock().unwrap()[self.index].offer = data;
+    }
+    fn poll(&mut self, time: SystemTime) -> Option<u8> {
+        let mut sides = self.sides.lock().unwrap();
+        let side = &mut sides[self.index];
+        match side.incoming {
+            Some((at, data)) if at <= time => {
+                side.incoming = None;
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+}
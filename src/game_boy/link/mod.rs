@@ -0,0 +1,57 @@
+use std::fmt::Debug;
+
+use super::time::SystemTime;
+
+pub mod cable;
+pub mod pair;
+pub mod printer;
+pub mod serial_log;
+pub mod tcp;
+#[cfg(test)]
+mod tests;
+mod wire;
+
+/// Byte shifted in when nothing drives the serial line
+pub const NO_CABLE: u8 = 0xFF;
+
+/// The other end of the link cable
+/// Transfers are exchanged a whole byte at a time, timestamped with the system time
+/// at which the side driving the clock finished shifting
+pub trait LinkPeer: Debug {
+    /// Our internal clock finished shifting `data` out at `time`
+    /// Returns the byte shifted in from the other side
+    fn exchange(&mut self, time: SystemTime, data: u8) -> u8;
+    /// Offer `data` to be shifted out by the other side's clock,
+    /// `None` withdraws the offer
+    fn listen(&mut self, data: Option<u8>) {
+        let _ = data;
+    }
+    /// A byte shifted in by the other side's clock at or before `time`,
+    /// which completes a transfer using the external clock
+    fn poll(&mut self, time: SystemTime) -> Option<u8> {
+        let _ = time;
+        None
+    }
+    /// Called on every M-cycle with the current time, lets the peer keep up with
+    /// the other side
+    fn sync(&mut self, time: SystemTime) {
+        let _ = time;
+    }
+    /// Whether the M-cycle ending at `time` can run without waiting for the other side,
+    /// `exchange_due` is set when our internal clock may finish a transfer on it
+    /// The system isn't stepped until it returns true, so waiting never blocks the caller
+    fn ready(&mut self, time: SystemTime, exchange_due: bool) -> bool {
+        let _ = (time, exchange_due);
+        true
+    }
+}
+
+/// No cable plugged in, the line is pulled high
+#[derive(Debug, Default)]
+pub struct Disconnected;
+
+impl LinkPeer for Disconnected {
+    fn exchange(&mut self, _time: SystemTime, _data: u8) -> u8 {
+        NO_CABLE
+    }
+}
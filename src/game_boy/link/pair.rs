@@ -0,0 +1,55 @@
+use crate::game_boy::{System, events::Events, time::SystemTime};
+
+use super::cable::link_cable;
+
+/// Two systems connected by a link cable, run in lockstep
+/// The system that is behind is always stepped first by a single M-cycle, so the two clocks
+/// never drift apart by more than one M-cycle, and each transfer reaches the other side on
+/// the M-cycle it completed on
+#[derive(Debug)]
+pub struct LinkedPair {
+    systems: [System; 2],
+}
+
+impl LinkedPair {
+    pub fn new(mut first: System, mut second: System) -> Self {
+        let (first_end, second_end) = link_cable();
+        first.connect_link(first_end);
+        second.connect_link(second_end);
+        Self {
+            systems: [first, second],
+        }
+    }
+    pub fn systems(&self) -> &[System; 2] {
+        &self.systems
+    }
+    pub fn systems_mut(&mut self) -> &mut [System; 2] {
+        &mut self.systems
+    }
+    /// The time both systems have reached
+    pub fn time(&self) -> SystemTime {
+        self.systems[0].time().min(self.systems[1].time())
+    }
+    /// Step the system that is behind by one M-cycle
+    pub fn step(&mut self) -> [Events; 2] {
+        let mut events = [Events::new(); 2];
+        let behind = if self.systems[0].time() <= self.systems[1].time() {
+            0
+        } else {
+            1
+        };
+        events[behind] = self.systems[behind].step_cycle();
+        events
+    }
+    /// Run both systems for `delta`, returns every event that happened on each side
+    pub fn run_for(&mut self, delta: SystemTime) -> [Events; 2] {
+        let target_time = self.time() + delta;
+        let mut events = [0u8; 2];
+        while self.time() < target_time {
+            for (all, new) in events.iter_mut().zip(self.step()) {
+                *all |= u8::from(new);
+            }
+        }
+        events.map(Events::from)
+    }
+}
@@ -0,0 +1,341 @@
+use std::path::PathBuf;
+
+use modular_bitfield::prelude::*;
+
+use crate::game_boy::{WINDOW_WIDTH, image::save_png, time::SystemTime};
+
+use super::LinkPeer;
+
+#[cfg(test)]
+mod tests;
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+/// Sent back during the first byte after the checksum
+const DEVICE_ID: u8 = 0x81;
+/// Size of the printer's image buffer
+const BUFFER_SIZE: usize = 0x2280;
+/// A row of 20 tiles
+const TILE_ROW_BYTES: usize = 20 * 16;
+/// Time spent printing each pixel row, during which the busy bit is set
+const PRINT_TIME_PER_LINE: f64 = 0.01;
+/// Paper fed for each unit of the margins, in pixel rows
+const MARGIN_UNIT_LINES: usize = 8;
+/// Gray levels of the 4 printer shades, white to black
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrinterCommand {
+    Init = 0x01,
+    Print = 0x02,
+    Data = 0x04,
+    Status = 0x0F,
+}
+
+impl PrinterCommand {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(Self::Init),
+            0x02 => Some(Self::Print),
+            0x04 => Some(Self::Data),
+            0x0F => Some(Self::Status),
+            _ => None,
+        }
+    }
+}
+
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Status {
+    checksum_error: bool,
+    printing: bool,
+    image_data_full: bool,
+    unprocessed_data: bool,
+    packet_error: bool,
+    paper_jam: bool,
+    other_error: bool,
+    low_battery: bool,
+}
+
+/// Where we are in the packet being received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum PacketState {
+    #[default]
+    Magic0,
+    Magic1,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    /// We answer with the device ID
+    Alive,
+    /// We answer with the status
+    Status,
+}
+
+#[derive(Debug, Default)]
+struct Packet {
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+}
+
+impl Packet {
+    fn computed_checksum(&self) -> u16 {
+        let header = [
+            self.command,
+            self.compressed as u8,
+            self.length as u8,
+            (self.length >> 8) as u8,
+        ];
+        header
+            .iter()
+            .chain(&self.data)
+            .fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16))
+    }
+}
+
+/// Game Boy Printer plugged into the link port
+/// Packets are made of the magic bytes, a command, a compression flag, a little-endian
+/// data length, the data and a checksum, followed by two bytes where the printer answers
+/// with its device ID and status.
+/// Every print job is saved as a grayscale PNG in the output directory, a job ends with
+/// the first PRINT command that feeds paper after the image, or when the printer is dropped.
+/// The exposure setting is ignored
+#[derive(Debug)]
+pub struct Printer {
+    directory: PathBuf,
+    state: PacketState,
+    packet: Packet,
+    status: Status,
+    /// Tile data received since the last print
+    buffer: Vec<u8>,
+    /// The busy bit stays set until then
+    printing_until: SystemTime,
+    /// Gray pixel rows of the current job
+    job: Vec<u8>,
+    saved: Vec<PathBuf>,
+}
+
+impl Printer {
+    /// Save print jobs into `directory`, which is created if needed
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            state: Default::default(),
+            packet: Default::default(),
+            status: Status::new(),
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            printing_until: SystemTime::new(),
+            job: Vec::new(),
+            saved: Vec::new(),
+        }
+    }
+    /// Images saved so far
+    pub fn saved(&self) -> &[PathBuf] {
+        &self.saved
+    }
+    /// Save the current job even though no margin ended it
+    pub fn finish_job(&mut self) {
+        if self.job.is_empty() {
+            return;
+        }
+        let job = std::mem::take(&mut self.job);
+        let path = self.next_path();
+        match save_png(&path, WINDOW_WIDTH as usize, &job) {
+            Ok(()) => {
+                log::info!("printed {}", path.display());
+                self.saved.push(path);
+            }
+            Err(err) => log::error!("could not save print to {}: {err}", path.display()),
+        }
+    }
+    fn next_path(&self) -> PathBuf {
+        (1..)
+            .map(|index| self.directory.join(format!("print_{index:03}.png")))
+            .find(|path| !path.exists())
+            .unwrap()
+    }
+    /// Receive a byte, returns the next state
+    fn receive(&mut self, time: SystemTime, byte: u8) -> PacketState {
+        use PacketState::*;
+        let packet = &mut self.packet;
+        match self.state {
+            Magic0 if byte == MAGIC[0] => Magic1,
+            Magic0 => Magic0,
+            Magic1 if byte == MAGIC[1] => Command,
+            // Resynchronize on a stray first magic byte
+            Magic1 if byte == MAGIC[0] => Magic1,
+            Magic1 => Magic0,
+            Command => {
+                *packet = Packet {
+                    command: byte,
+                    ..Default::default()
+                };
+                Compression
+            }
+            Compression => {
+                packet.compressed = byte & 0x01 != 0;
+                LengthLow
+            }
+            LengthLow => {
+                packet.length = byte as u16;
+                LengthHigh
+            }
+            LengthHigh => {
+                packet.length |= (byte as u16) << 8;
+                if packet.length == 0 {
+                    ChecksumLow
+                } else {
+                    Data
+                }
+            }
+            Data => {
+                packet.data.push(byte);
+                if packet.data.len() < packet.length as usize {
+                    Data
+                } else {
+                    ChecksumLow
+                }
+            }
+            ChecksumLow => {
+                packet.checksum = byte as u16;
+                ChecksumHigh
+            }
+            ChecksumHigh => {
+                packet.checksum |= (byte as u16) << 8;
+                self.execute(time);
+                Alive
+            }
+            Alive => Status,
+            Status => Magic0,
+        }
+    }
+    fn execute(&mut self, time: SystemTime) {
+        let packet = std::mem::take(&mut self.packet);
+        if packet.checksum != packet.computed_checksum() {
+            log::warn!("printer packet checksum mismatch");
+            self.status.set_checksum_error(true);
+            return;
+        }
+        self.status.set_checksum_error(false);
+        match PrinterCommand::from_byte(packet.command) {
+            Some(PrinterCommand::Init) => {
+                self.buffer.clear();
+                self.status = Status::new();
+            }
+            Some(PrinterCommand::Data) => {
+                if packet.compressed {
+                    decompress(&packet.data, &mut self.buffer);
+                } else {
+                    self.buffer.extend(&packet.data);
+                }
+                self.buffer.truncate(BUFFER_SIZE);
+                self.status.set_unprocessed_data(!self.buffer.is_empty());
+                self.status
+                    .set_image_data_full(self.buffer.len() >= BUFFER_SIZE);
+            }
+            Some(PrinterCommand::Print) => {
+                let [sheets, margins, palette, _exposure] = packet.data[..] else {
+                    self.status.set_packet_error(true);
+                    return;
+                };
+                let lines = self.print(sheets, margins, palette);
+                self.printing_until =
+                    time + SystemTime::from_seconds(lines as f64 * PRINT_TIME_PER_LINE);
+            }
+            Some(PrinterCommand::Status) => {}
+            None => {
+                log::warn!("unknown printer command {:02x}", packet.command);
+                self.status.set_packet_error(true);
+            }
+        }
+    }
+    /// Print the buffer `sheets` times between the margins, returns the number of lines fed
+    fn print(&mut self, sheets: u8, margins: u8, palette: u8) -> usize {
+        let width = WINDOW_WIDTH as usize;
+        let before = (margins >> 4) as usize * MARGIN_UNIT_LINES;
+        let after = (margins & 0x0F) as usize * MARGIN_UNIT_LINES;
+        let image = decode_tiles(&self.buffer, palette);
+        self.buffer.clear();
+        self.status.set_unprocessed_data(false);
+        self.status.set_image_data_full(false);
+
+        let start = self.job.len();
+        self.job.resize(start + before * width, SHADES[0]);
+        for _ in 0..sheets {
+            self.job.extend(&image);
+        }
+        self.job.resize(self.job.len() + after * width, SHADES[0]);
+        let lines = (self.job.len() - start) / width;
+        if after > 0 {
+            self.finish_job();
+        }
+        lines
+    }
+    fn status(&self, time: SystemTime) -> u8 {
+        let mut status = self.status;
+        status.set_printing(time < self.printing_until);
+        status.into()
+    }
+}
+
+impl Drop for Printer {
+    fn drop(&mut self) {
+        self.finish_job();
+    }
+}
+
+impl LinkPeer for Printer {
+    fn exchange(&mut self, time: SystemTime, data: u8) -> u8 {
+        // The answer is shifted out while the byte is shifted in
+        let reply = match self.state {
+            PacketState::Alive => DEVICE_ID,
+            PacketState::Status => self.status(time),
+            _ => 0x00,
+        };
+        self.state = self.receive(time, data);
+        reply
+    }
+}
+
+/// The printer's run-length encoding: a control byte with bit 7 set repeats the next byte
+/// (control & 0x7F) + 2 times, otherwise the next control + 1 bytes are copied as is
+fn decompress(data: &[u8], out: &mut Vec<u8>) {
+    let mut bytes = data.iter().copied();
+    while let Some(control) = bytes.next() {
+        if control & 0x80 != 0 {
+            let Some(byte) = bytes.next() else {
+                break;
+            };
+            let count = (control & 0x7F) as usize + 2;
+            out.extend(std::iter::repeat_n(byte, count));
+        } else {
+            out.extend(bytes.by_ref().take(control as usize + 1));
+        }
+    }
+}
+
+/// Convert rows of 20 tiles into gray pixel rows, with `palette` mapping colors to shades
+fn decode_tiles(tiles: &[u8], palette: u8) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(tiles.len() * 4);
+    for tile_row in tiles.chunks_exact(TILE_ROW_BYTES) {
+        for line in 0..8 {
+            for tile in tile_row.chunks_exact(16) {
+                let low = tile[line * 2];
+                let high = tile[line * 2 + 1];
+                for bit in (0..8).rev() {
+                    let color = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+                    let shade = (palette >> (color * 2)) & 0x03;
+                    pixels.push(SHADES[shade as usize]);
+                }
+            }
+        }
+    }
+    pixels
+}
@@ -0,0 +1,205 @@
+use std::{fs, path::PathBuf};
+
+use crate::game_boy::{link::LinkPeer, time::SystemTime};
+
+use super::{Printer, decompress};
+
+fn output_directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("cvgb_printer_{}_{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    directory
+}
+
+/// A packet as sent by a game, followed by the two bytes the printer answers on
+fn packet(command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x88, 0x33, command, compressed as u8];
+    bytes.extend((data.len() as u16).to_le_bytes());
+    bytes.extend(data);
+    let checksum = bytes[2..]
+        .iter()
+        .fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16));
+    bytes.extend(checksum.to_le_bytes());
+    bytes.extend([0x00, 0x00]);
+    bytes
+}
+
+/// Feed a packet stream, returns the device ID and status bytes the printer answered with
+fn send(printer: &mut Printer, time: SystemTime, stream: &[u8]) -> (u8, u8) {
+    let replies: Vec<u8> = stream
+        .iter()
+        .map(|&byte| printer.exchange(time, byte))
+        .collect();
+    let [.., id, status] = replies[..] else {
+        unreachable!()
+    };
+    assert!(
+        replies[..replies.len() - 2].iter().all(|&reply| reply == 0),
+        "the printer only answers after the checksum"
+    );
+    (id, status)
+}
+
+/// Two rows of tiles: every pixel of the first tile row has color 1, the second color 3
+fn tile_data() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend([0xFF, 0x00].repeat(20 * 8));
+    data.extend([0xFF, 0xFF].repeat(20 * 8));
+    data
+}
+
+/// The same data in the printer's run-length encoding
+fn compressed_tile_data() -> Vec<u8> {
+    let mut data = Vec::new();
+    // 320 bytes of FF 00 as literals of 2 bytes
+    for _ in 0..160 {
+        data.extend([0x01, 0xFF, 0x00]);
+    }
+    // 320 bytes of FF as runs of 129 + 129 + 62
+    data.extend([0xFF, 0xFF, 0xFF, 0xFF, 0xBC, 0xFF]);
+    data
+}
+
+fn read_png(path: &PathBuf) -> (u32, u32, Vec<u8>) {
+    let decoder = png::Decoder::new(fs::File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    pixels.truncate(info.buffer_size());
+    (info.width, info.height, pixels)
+}
+
+#[test]
+fn decompress_runs_and_literals() {
+    let mut out = Vec::new();
+    decompress(&compressed_tile_data(), &mut out);
+    assert_eq!(out, tile_data());
+}
+
+#[test]
+fn print_job_to_png() {
+    let directory = output_directory("job");
+    let mut printer = Printer::new(&directory);
+    let time = SystemTime::new();
+    assert_eq!(
+        send(&mut printer, time, &packet(0x01, false, &[])),
+        (0x81, 0x00)
+    );
+    assert_eq!(
+        send(&mut printer, time, &packet(0x04, false, &tile_data())),
+        (0x81, 0x08),
+        "unprocessed data"
+    );
+    assert_eq!(
+        send(
+            &mut printer,
+            time,
+            &packet(0x04, true, &compressed_tile_data())
+        ),
+        (0x81, 0x08)
+    );
+    // End of data
+    send(&mut printer, time, &packet(0x04, false, &[]));
+    // 1 sheet, 1 unit of margin before and 3 after, inverted palette
+    assert_eq!(
+        send(
+            &mut printer,
+            time,
+            &packet(0x02, false, &[0x01, 0x13, 0x1B, 0x40])
+        ),
+        (0x81, 0x02),
+        "printing"
+    );
+    let later = time + SystemTime::from_seconds(5.0);
+    assert_eq!(
+        send(&mut printer, later, &packet(0x0F, false, &[])),
+        (0x81, 0x00)
+    );
+
+    assert_eq!(printer.saved(), [directory.join("print_001.png")]);
+    let (width, height, pixels) = read_png(&printer.saved()[0]);
+    assert_eq!((width, height), (160, 8 + 32 + 24));
+    let line = |y: usize| &pixels[y * 160..(y + 1) * 160];
+    assert!(line(0).iter().all(|&p| p == 0xFF), "margin");
+    // Palette 1B maps color 1 to shade 2 and color 3 to shade 0
+    assert!(line(8).iter().all(|&p| p == 0x55));
+    assert!(line(16).iter().all(|&p| p == 0xFF));
+    assert!(line(24).iter().all(|&p| p == 0x55), "second packet");
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn checksum_error() {
+    let directory = output_directory("checksum");
+    let mut printer = Printer::new(&directory);
+    let time = SystemTime::new();
+    let mut stream = packet(0x04, false, &tile_data());
+    let checksum = stream.len() - 4;
+    stream[checksum] ^= 0x01;
+    assert_eq!(send(&mut printer, time, &stream), (0x81, 0x01));
+    assert_eq!(
+        send(&mut printer, time, &packet(0x0F, false, &[])),
+        (0x81, 0x00),
+        "the data was dropped"
+    );
+    // Garbage before the magic bytes is skipped
+    let mut stream = vec![0x00, 0x88, 0x12];
+    stream.extend(packet(0x0F, false, &[]));
+    assert_eq!(send(&mut printer, time, &stream), (0x81, 0x00));
+    drop(printer);
+    assert!(!directory.exists(), "nothing was printed");
+}
+
+/// Packets of a capture under test/printer, each ending with the two bytes the printer answered
+fn read_capture(name: &str) -> Vec<Vec<u8>> {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test/printer", name]
+        .iter()
+        .collect();
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+/// Replay a capture at 10 packets per second, the printer must answer as it did
+/// The capture and the image it prints are written by test/printer/synthetic_capture.py
+#[test]
+fn replay_capture() {
+    let directory = output_directory("capture");
+    let mut printer = Printer::new(&directory);
+    let mut time = SystemTime::new();
+    for (index, packet) in read_capture("synthetic_capture.txt").iter().enumerate() {
+        let (sent, answer) = packet.split_at(packet.len() - 2);
+        let mut stream = sent.to_vec();
+        stream.extend([0x00, 0x00]);
+        assert_eq!(
+            send(&mut printer, time, &stream),
+            (answer[0], answer[1]),
+            "answer to packet {index}"
+        );
+        time += SystemTime::from_seconds(0.1);
+    }
+
+    assert_eq!(printer.saved(), [directory.join("print_001.png")]);
+    let (width, height, pixels) = read_png(&printer.saved()[0]);
+    let golden: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "test/printer",
+        "synthetic_capture.png",
+    ]
+    .iter()
+    .collect();
+    let (expected_width, expected_height, expected) = read_png(&golden);
+    assert_eq!((width, height), (expected_width, expected_height));
+    let line = |y: usize| y * width as usize..(y + 1) * width as usize;
+    for y in 0..height as usize {
+        assert!(pixels[line(y)] == expected[line(y)], "line {y} differs");
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,32 @@
+use std::sync::{Arc, Mutex};
+
+use crate::game_boy::time::SystemTime;
+
+use super::{LinkPeer, NO_CABLE};
+
+/// Records every byte sent with the internal clock, like the output of test ROMs
+/// Clones share the same log, so one can be kept to read what the system sent
+#[derive(Debug, Default, Clone)]
+pub struct SerialLog {
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SerialLog {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().unwrap().clone()
+    }
+    /// The log decoded as text, invalid UTF-8 is replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes.lock().unwrap()).into_owned()
+    }
+}
+
+impl LinkPeer for SerialLog {
+    fn exchange(&mut self, _time: SystemTime, data: u8) -> u8 {
+        self.bytes.lock().unwrap().push(data);
+        NO_CABLE
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::game_boy::time::SystemTime;
+
+use super::{
+    LinkPeer, NO_CABLE,
+    wire::{Message, PROTOCOL_VERSION, WireError},
+};
+
+/// How long to wait for the other side before considering it gone
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the two processes keep their clocks together
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// A transfer waits until the other side reached the time it completes on,
+    /// exact but every transfer costs a round trip
+    Lockstep,
+    /// A transfer uses the last known state of the other side, which is never more
+    /// than the maximum lead behind
+    #[default]
+    Speculative,
+}
+
+impl SyncMode {
+    /// How far ahead of the other side's last reported time we may run
+    fn max_lead(self) -> SystemTime {
+        match self {
+            // Half a byte at 8192 Hz
+            Self::Lockstep => SystemTime::from_system_clocks(512),
+            // About 3 frames
+            Self::Speculative => SystemTime::from_seconds(0.05),
+        }
+    }
+    /// How often our time is reported without being asked for
+    fn report_interval(self) -> SystemTime {
+        match self {
+            Self::Lockstep => SystemTime::from_system_clocks(128),
+            Self::Speculative => SystemTime::from_seconds(0.01),
+        }
+    }
+}
+
+/// Whether a [`TcpLink`] is still connected, shared with the frontend
+#[derive(Debug, Clone)]
+pub struct LinkStatus(Arc<AtomicBool>);
+
+impl LinkStatus {
+    pub fn is_connected(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+struct Connection {
+    stream: TcpStream,
+    /// Filled by a thread reading the socket, disconnected when the socket closes
+    incoming: Receiver<Message>,
+}
+
+/// Link cable waiting on a background thread for the other side to join
+#[derive(Debug)]
+pub struct PendingLink {
+    address: SocketAddr,
+    result: Receiver<io::Result<TcpLink>>,
+}
+
+impl PendingLink {
+    /// Address the other side should join
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+    /// The link once the other side joined, or why listening failed
+    pub fn poll(&self) -> Option<io::Result<TcpLink>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(io::Error::other("link cable listener stopped")))
+            }
+        }
+    }
+}
+
+/// Link cable to another process over TCP
+/// Once the connection drops, it behaves like an unplugged cable
+#[derive(Debug)]
+pub struct TcpLink {
+    connection: Option<Connection>,
+    mode: SyncMode,
+    status: LinkStatus,
+    local_time: SystemTime,
+    last_report: SystemTime,
+    remote_time: SystemTime,
+    /// Received messages stamped after our current time, in the order they were sent
+    /// Still delivered after the connection drops
+    pending: VecDeque<Message>,
+    remote_offer: Option<u8>,
+    /// What we last told the other side we are offering
+    local_offer: Option<u8>,
+    /// Transfer clocked by the other side, delivered once we reach its timestamp
+    incoming: Option<(SystemTime, u8)>,
+    /// When we started waiting for the other side to catch up
+    waiting_since: Option<Instant>,
+}
+
+impl TcpLink {
+    /// Wait for the other side to join on `listener`
+    pub fn accept(listener: &TcpListener, mode: SyncMode) -> io::Result<Self> {
+        let (stream, address) = listener.accept()?;
+        log::info!("link cable connected to {address}");
+        Self::from_stream(stream, mode)
+    }
+    /// Listen on `address` for the other side to join, without waiting for it
+    pub fn listen(address: impl ToSocketAddrs, mode: SyncMode) -> io::Result<PendingLink> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let (sender, result) = mpsc::channel();
+        thread::spawn(move || {
+            let link = loop {
+                let (stream, address) = match listener.accept() {
+                    Ok(connection) => connection,
+                    Err(err) => break Err(err),
+                };
+                // Whoever connected may not be another emulator, keep waiting if so
+                match Self::from_stream(stream, mode) {
+                    Ok(link) => {
+                        log::info!("link cable connected to {address}");
+                        break Ok(link);
+                    }
+                    Err(err) => log::warn!("link cable handshake with {address} failed: {err}"),
+                }
+            };
+            let _ = sender.send(link);
+        });
+        Ok(PendingLink { address, result })
+    }
+    /// Join the other side listening at `address`
+    pub fn connect(address: impl ToSocketAddrs, mode: SyncMode) -> io::Result<Self> {
+        Self::from_stream(TcpStream::connect(address)?, mode)
+    }
+    fn from_stream(mut stream: TcpStream, mode: SyncMode) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+        }
+        .write_to(&mut stream)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        match Message::read_from(&mut stream) {
+            Ok(Message::Hello {
+                version: PROTOCOL_VERSION,
+            }) => {}
+            Ok(message) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected handshake {message:?}"),
+                ));
+            }
+            Err(WireError::Io(err)) => return Err(err),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+        stream.set_read_timeout(None)?;
+
+        let (sender, incoming) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            loop {
+                match Message::read_from(&mut reader) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() || message == Message::Bye {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        log::debug!("link cable read failed: {err}");
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            connection: Some(Connection { stream, incoming }),
+            mode,
+            status: LinkStatus(Arc::new(AtomicBool::new(true))),
+            local_time: SystemTime::new(),
+            last_report: SystemTime::new(),
+            remote_time: SystemTime::new(),
+            pending: VecDeque::new(),
+            remote_offer: None,
+            local_offer: None,
+            incoming: None,
+            waiting_since: None,
+        })
+    }
+    pub fn status(&self) -> LinkStatus {
+        self.status.clone()
+    }
+    fn send(&mut self, message: Message) {
+        let Some(connection) = self.connection.as_mut() else {
+            return;
+        };
+        if let Err(err) = message.write_to(&mut connection.stream) {
+            log::warn!("link cable write failed: {err}");
+            self.disconnect();
+        }
+    }
+    fn report_time(&mut self, time: SystemTime) {
+        self.send(Message::Time(time));
+        self.last_report = time;
+    }
+    /// Handle one message from the other side if one arrived
+    /// Returns false if there was nothing to handle
+    fn receive(&mut self) -> bool {
+        let Some(connection) = self.connection.as_ref() else {
+            return false;
+        };
+        match connection.incoming.try_recv() {
+            Ok(message) => {
+                self.handle(message);
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => {
+                self.disconnect();
+                false
+            }
+        }
+    }
+    fn handle(&mut self, message: Message) {
+        if let Some(time) = message.time() {
+            self.remote_time = self.remote_time.max(time);
+        }
+        match message {
+            Message::Bye => {
+                log::info!("the other side closed the link cable");
+                self.disconnect();
+            }
+            Message::Hello { .. } | Message::Time(_) => {}
+            Message::Offer(..) | Message::Withdraw(_) | Message::Transfer(..) => {
+                self.pending.push_back(message)
+            }
+        }
+    }
+    /// Apply the messages the other side sent up to `time`
+    fn apply_until(&mut self, time: SystemTime) {
+        while let Some(message) = self.pending.front().copied()
+            && message.time().is_some_and(|stamp| stamp <= time)
+        {
+            self.pending.pop_front();
+            match message {
+                Message::Offer(_, data) => self.remote_offer = Some(data),
+                Message::Withdraw(_) => self.remote_offer = None,
+                // The shift register only moves while we wait for the other side's clock
+                Message::Transfer(stamp, data) if self.local_offer.is_some() => {
+                    self.incoming = Some((stamp, data))
+                }
+                _ => {}
+            }
+        }
+    }
+    fn disconnect(&mut self) {
+        let Some(connection) = self.connection.take() else {
+            return;
+        };
+        log::warn!("link cable disconnected");
+        let _ = connection.stream.shutdown(Shutdown::Both);
+        self.status.0.store(false, Ordering::Relaxed);
+        self.waiting_since = None;
+        // Messages that were already received are still delivered on time
+        self.remote_offer = None;
+    }
+}
+
+impl LinkPeer for TcpLink {
+    fn exchange(&mut self, time: SystemTime, data: u8) -> u8 {
+        if self.mode == SyncMode::Lockstep {
+            // `ready` held us back until the other side reached `time`
+            while self.receive() {}
+            self.apply_until(time);
+        } else {
+            self.apply_until(self.remote_time);
+        }
+        let reply = self.remote_offer.take();
+        self.send(Message::Transfer(time, data));
+        match self.connection {
+            Some(_) => reply.unwrap_or(NO_CABLE),
+            None => NO_CABLE,
+        }
+    }
+    fn listen(&mut self, data: Option<u8>) {
+        if data == self.local_offer {
+            return;
+        }
+        self.local_offer = data;
+        match data {
+            Some(data) => self.send(Message::Offer(self.local_time, data)),
+            None => {
+                self.incoming = None;
+                self.send(Message::Withdraw(self.local_time));
+            }
+        }
+    }
+    fn poll(&mut self, time: SystemTime) -> Option<u8> {
+        match self.incoming {
+            Some((stamp, data)) if stamp <= time => {
+                self.incoming = None;
+                // The other side already took our offer
+                self.local_offer = None;
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+    fn sync(&mut self, time: SystemTime) {
+        self.local_time = time;
+        while self.receive() {}
+        if self.connection.is_some() && time >= self.last_report + self.mode.report_interval() {
+            self.report_time(time);
+        }
+        self.apply_until(time);
+    }
+    fn ready(&mut self, time: SystemTime, exchange_due: bool) -> bool {
+        if self.connection.is_none() {
+            return true;
+        }
+        while self.receive() {}
+        let too_far_ahead = time > self.remote_time + self.mode.max_lead();
+        let transfer_early =
+            self.mode == SyncMode::Lockstep && exchange_due && time > self.remote_time;
+        if !too_far_ahead && !transfer_early {
+            self.waiting_since = None;
+            return true;
+        }
+        // The other side may be waiting for us as well
+        if self.last_report < self.local_time {
+            self.report_time(self.local_time);
+        }
+        let waiting_since = *self.waiting_since.get_or_insert_with(Instant::now);
+        if waiting_since.elapsed() > TIMEOUT {
+            log::warn!("link cable timed out");
+            self.disconnect();
+        }
+        false
+    }
+}
+
+impl Drop for TcpLink {
+    fn drop(&mut self) {
+        if self.connection.is_some() {
+            self.send(Message::Bye);
+            self.disconnect();
+        }
+    }
+}
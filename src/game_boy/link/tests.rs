@@ -0,0 +1,318 @@
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::game_boy::{System, cpu::registers::Registers, time::SystemTime};
+
+use super::{
+    LinkPeer, NO_CABLE,
+    pair::LinkedPair,
+    serial_log::SerialLog,
+    tcp::{SyncMode, TcpLink},
+    wire::{Message, WireError},
+};
+
+fn program_rom(program: &[u8]) -> Box<[u8]> {
+    let mut rom = vec![0; 0x8000];
+    // Jump over the header
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    rom[0x150..0x150 + program.len()].copy_from_slice(program);
+    rom.into_boxed_slice()
+}
+
+/// Start a transfer with SC = `sc` and wait for it to complete
+fn transfer(sc: u8) -> [u8; 10] {
+    [
+        0x3E, sc, 0xE0, 0x02, // LD A,sc; LDH (SC),A
+        0xF0, 0x02, 0xCB, 0x7F, // LDH A,(SC); BIT 7,A
+        0x20, 0xFA, // JR NZ,-6
+    ]
+}
+
+/// Send `text` over serial with the internal clock, waiting for each transfer
+fn print_program(text: &str) -> Box<[u8]> {
+    let mut program = Vec::new();
+    for byte in text.bytes() {
+        // LD A,byte; LDH (SB),A
+        program.extend([0x3E, byte, 0xE0, 0x01]);
+        program.extend(transfer(0x81));
+    }
+    program.extend([0x18, 0xFE]);
+    program_rom(&program)
+}
+
+/// Exchange `byte` using SC = `sc`, then send back whatever was received with the internal clock
+fn forward_program(byte: u8, sc: u8) -> Box<[u8]> {
+    let mut program = vec![0x3E, byte, 0xE0, 0x01];
+    program.extend(transfer(sc));
+    program.extend(transfer(0x81));
+    program.extend([0x18, 0xFE]);
+    program_rom(&program)
+}
+
+#[test]
+fn serial_log_records_output() {
+    let mut system = System::now(print_program("Passed")).unwrap();
+    let log = SerialLog::new();
+    system.connect_link(log.clone());
+    system.advance(SystemTime::from_seconds(0.1));
+    assert_eq!(log.text(), "Passed");
+}
+
+#[test]
+fn unplugged_cable_stops_logging() {
+    let mut system = System::now(print_program("ab")).unwrap();
+    let log = SerialLog::new();
+    system.connect_link(log.clone());
+    // A byte takes a bit less than 1ms at 8192 Hz
+    system.advance(SystemTime::from_seconds(0.0015));
+    system.disconnect_link();
+    system.advance(SystemTime::from_seconds(0.01));
+    assert_eq!(log.text(), "a");
+}
+
+#[test]
+fn linked_pair_exchanges_bytes() {
+    let master = System::now(forward_program(0x42, 0x81)).unwrap();
+    let slave = System::now(forward_program(0x99, 0x80)).unwrap();
+    let mut pair = LinkedPair::new(master, slave);
+    // The exchange completes after about 1ms
+    pair.run_for(SystemTime::from_seconds(0.0015));
+    // Catch what each side forwards after the exchange
+    let logs = [SerialLog::new(), SerialLog::new()];
+    for (system, log) in pair.systems_mut().iter_mut().zip(&logs) {
+        system.connect_link(log.clone());
+    }
+    pair.run_for(SystemTime::from_seconds(0.01));
+    assert_eq!(
+        logs[0].bytes(),
+        [0x99],
+        "the master received the slave's byte"
+    );
+    assert_eq!(
+        logs[1].bytes(),
+        [0x42],
+        "the slave received the master's byte"
+    );
+    let [first, second] = pair.systems();
+    assert!(first.time().max(second.time()) - pair.time() <= SystemTime::from_system_clocks(1));
+}
+
+#[test]
+fn linked_pair_stays_within_one_m_cycle() {
+    let master = System::now(forward_program(0x42, 0x81)).unwrap();
+    let slave = System::now(forward_program(0x99, 0x80)).unwrap();
+    let mut pair = LinkedPair::new(master, slave);
+    let end = SystemTime::from_seconds(0.003);
+    while pair.time() < end {
+        pair.step();
+        let [first, second] = pair.systems();
+        let drift = first.time().max(second.time()) - first.time().min(second.time());
+        assert!(
+            drift <= SystemTime::from_system_clocks(1),
+            "the systems drifted {drift:?} apart at {:?}",
+            pair.time()
+        );
+    }
+}
+
+#[test]
+fn wire_messages_round_trip() {
+    let time = SystemTime::from_master_clocks(0x0123_4567_89AB);
+    for message in [
+        Message::Hello { version: 1 },
+        Message::Time(time),
+        Message::Offer(time, 0x42),
+        Message::Withdraw(time),
+        Message::Transfer(time, 0x99),
+        Message::Bye,
+    ] {
+        assert_eq!(Message::decode(message.encode()).unwrap(), message);
+    }
+    let mut bytes = Message::Bye.encode();
+    bytes[0] = 0x77;
+    assert!(matches!(
+        Message::decode(bytes),
+        Err(WireError::UnknownTag(0x77))
+    ));
+}
+
+/// Advance `system` by `delta`, retrying while the link cable waits for the other side
+fn advance_linked(system: &mut System, delta: SystemTime) {
+    let target_time = system.time() + delta;
+    while system.time() < target_time {
+        let (events, _) = system.advance(target_time - system.time());
+        if events.link_stalled() {
+            thread::yield_now();
+        }
+    }
+}
+
+/// Run `rom` linked through `link`, then log what it sends after the first exchange
+fn run_forwarding(rom: Box<[u8]>, link: TcpLink) -> Vec<u8> {
+    let mut system = System::now(rom).unwrap();
+    system.connect_link(link);
+    advance_linked(&mut system, SystemTime::from_seconds(0.0015));
+    let log = SerialLog::new();
+    system.connect_link(log.clone());
+    system.advance(SystemTime::from_seconds(0.01));
+    log.bytes()
+}
+
+#[test]
+fn tcp_link_lockstep_exchange() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let slave = thread::spawn(move || {
+        let link = TcpLink::connect(address, SyncMode::Lockstep).unwrap();
+        run_forwarding(forward_program(0x99, 0x80), link)
+    });
+    let link = TcpLink::accept(&listener, SyncMode::Lockstep).unwrap();
+    let master = run_forwarding(forward_program(0x42, 0x81), link);
+    assert_eq!(master, [0x99], "the master received the slave's byte");
+    assert_eq!(
+        slave.join().unwrap(),
+        [0x42],
+        "the slave received the master's byte"
+    );
+}
+
+/// Spin for `iterations` of a 7 M-cycle loop
+fn delay(iterations: u16) -> [u8; 8] {
+    let [lo, hi] = iterations.to_le_bytes();
+    [
+        0x01, lo, hi,   // LD BC,iterations
+        0x0B, // DEC BC
+        0x78, 0xB1, // LD A,B; OR C
+        0x20, 0xFB, // JR NZ,-5
+    ]
+}
+
+/// Exchange `byte` using SC = `sc`, leaving the byte received in A
+fn exchange(byte: u8, sc: u8) -> Vec<u8> {
+    let mut program = vec![0x3E, byte, 0xE0, 0x01]; // LD A,byte; LDH (SB),A
+    program.extend(transfer(sc));
+    program.extend([0xF0, 0x01]); // LDH A,(SB)
+    program
+}
+
+/// Set E to `DONE` once `program` is done
+fn finished_program(mut program: Vec<u8>) -> Box<[u8]> {
+    program.extend([0x1E, DONE, 0x18, 0xFE]); // LD E,DONE; JR -2
+    program_rom(&program)
+}
+const DONE: u8 = 0x5A;
+
+/// Run `rom` linked through `link` until it's done, returns the registers it left
+fn run_until_done(rom: Box<[u8]>, link: TcpLink) -> Registers {
+    let mut system = System::now(rom).unwrap();
+    system.connect_link(link);
+    let deadline = system.time() + SystemTime::from_seconds(2.0);
+    while system.registers().e != DONE {
+        assert!(system.time() < deadline, "the program never finished");
+        advance_linked(&mut system, SystemTime::from_seconds(0.01));
+    }
+    *system.registers()
+}
+
+#[test]
+fn tcp_link_speculative_exchange() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let slave = thread::spawn(move || {
+        let link = TcpLink::connect(address, SyncMode::Speculative).unwrap();
+        // Offer $11 for 7 ms, then withdraw it by clearing SC
+        let mut program = vec![0x3E, 0x11, 0xE0, 0x01, 0x3E, 0x80, 0xE0, 0x02];
+        program.extend(delay(1000));
+        program.extend([0xAF, 0xE0, 0x02]); // XOR A; LDH (SC),A
+        // Offer $99 200 ms later
+        program.extend(delay(30000));
+        program.extend(exchange(0x99, 0x80));
+        program.push(0x67); // LD H,A
+        run_until_done(finished_program(program), link)
+    });
+    let link = TcpLink::accept(&listener, SyncMode::Speculative).unwrap();
+    // The slave can't be more than the maximum lead of 50 ms apart, so the withdrawal
+    // is known after 100 ms and the second offer isn't
+    let mut program = delay(15000).to_vec();
+    program.extend(exchange(0x42, 0x81));
+    program.push(0x67); // LD H,A
+    // By 400 ms the second offer is known
+    program.extend(delay(45000));
+    program.extend(exchange(0x43, 0x81));
+    program.push(0x6F); // LD L,A
+    let master = run_until_done(finished_program(program), link);
+    assert_eq!(master.h, NO_CABLE, "the withdrawn offer was not taken");
+    assert_eq!(
+        master.l, 0x99,
+        "the master received the slave's second offer"
+    );
+    assert_eq!(
+        slave.join().unwrap().h,
+        0x43,
+        "the slave only took the transfer clocked while it offered"
+    );
+}
+
+#[test]
+fn tcp_link_listens_in_the_background() {
+    let pending = TcpLink::listen("127.0.0.1:0", SyncMode::Speculative).unwrap();
+    let address = pending.address();
+    assert!(pending.poll().is_none(), "nobody joined yet");
+    // Something that isn't the other side connects first
+    let mut stranger = TcpStream::connect(address).unwrap();
+    stranger.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    drop(stranger);
+    let other = thread::spawn(move || TcpLink::connect(address, SyncMode::Speculative).unwrap());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let link = loop {
+        if let Some(result) = pending.poll() {
+            break result.unwrap();
+        }
+        assert!(Instant::now() < deadline, "the other side was not accepted");
+        thread::sleep(Duration::from_millis(1));
+    };
+    assert!(link.status().is_connected());
+    drop(other.join().unwrap());
+}
+
+#[test]
+fn tcp_link_stalls_without_blocking() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let other = thread::spawn(move || TcpLink::connect(address, SyncMode::Lockstep).unwrap());
+    let link = TcpLink::accept(&listener, SyncMode::Lockstep).unwrap();
+    // The other side stays connected but never runs
+    let _other = other.join().unwrap();
+    let mut system = System::now(forward_program(0x42, 0x81)).unwrap();
+    system.connect_link(link);
+    let start = Instant::now();
+    let (events, elapsed) = system.advance(SystemTime::from_seconds(0.01));
+    assert!(events.link_stalled());
+    assert!(elapsed < SystemTime::from_seconds(0.01));
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "advance waited for the other side"
+    );
+}
+
+#[test]
+fn tcp_link_disconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let other = thread::spawn(move || TcpLink::connect(address, SyncMode::Speculative).unwrap());
+    let mut link = TcpLink::accept(&listener, SyncMode::Speculative).unwrap();
+    let status = link.status();
+    assert!(status.is_connected());
+    drop(other.join().unwrap());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while status.is_connected() {
+        assert!(Instant::now() < deadline, "the disconnect was not noticed");
+        link.sync(SystemTime::new());
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(link.exchange(SystemTime::new(), 0x12), NO_CABLE);
+}
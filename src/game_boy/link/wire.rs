@@ -0,0 +1,86 @@
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use crate::game_boy::time::SystemTime;
+
+/// Bumped whenever the meaning of a message changes
+pub const PROTOCOL_VERSION: u64 = 1;
+/// Every message is a tag, a little-endian timestamp in master clocks and a data byte
+pub const MESSAGE_SIZE: usize = 10;
+
+/// What two linked processes tell each other
+/// Every timestamp is the sender's system time when the message was produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// First message on a connection, carries the protocol version instead of a time
+    Hello { version: u64 },
+    /// The sender reached `time`
+    Time(SystemTime),
+    /// The sender started waiting for our clock with `data` in SB
+    Offer(SystemTime, u8),
+    /// The sender stopped waiting for our clock
+    Withdraw(SystemTime),
+    /// The sender's internal clock shifted `data` out, completing at the timestamp
+    Transfer(SystemTime, u8),
+    /// The sender is closing the connection
+    Bye,
+}
+
+#[derive(Debug, Error)]
+pub enum WireError {
+    #[error("unknown message tag {0:02x}")]
+    UnknownTag(u8),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Message {
+    /// The sender's time when the message was produced, if it carries one
+    pub fn time(self) -> Option<SystemTime> {
+        match self {
+            Self::Time(time)
+            | Self::Offer(time, _)
+            | Self::Withdraw(time)
+            | Self::Transfer(time, _) => Some(time),
+            Self::Hello { .. } | Self::Bye => None,
+        }
+    }
+    pub fn encode(self) -> [u8; MESSAGE_SIZE] {
+        let (tag, stamp, data) = match self {
+            Self::Hello { version } => (0, version, 0),
+            Self::Time(time) => (1, time.master_clocks(), 0),
+            Self::Offer(time, data) => (2, time.master_clocks(), data),
+            Self::Withdraw(time) => (3, time.master_clocks(), 0),
+            Self::Transfer(time, data) => (4, time.master_clocks(), data),
+            Self::Bye => (5, 0, 0),
+        };
+        let mut bytes = [0; MESSAGE_SIZE];
+        bytes[0] = tag;
+        bytes[1..9].copy_from_slice(&stamp.to_le_bytes());
+        bytes[9] = data;
+        bytes
+    }
+    pub fn decode(bytes: [u8; MESSAGE_SIZE]) -> Result<Self, WireError> {
+        let stamp = u64::from_le_bytes(bytes[1..9].try_into().unwrap());
+        let time = SystemTime::from_master_clocks(stamp);
+        let data = bytes[9];
+        Ok(match bytes[0] {
+            0 => Self::Hello { version: stamp },
+            1 => Self::Time(time),
+            2 => Self::Offer(time, data),
+            3 => Self::Withdraw(time),
+            4 => Self::Transfer(time, data),
+            5 => Self::Bye,
+            tag => return Err(WireError::UnknownTag(tag)),
+        })
+    }
+    pub fn write_to(self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.encode())
+    }
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, WireError> {
+        let mut bytes = [0; MESSAGE_SIZE];
+        reader.read_exact(&mut bytes)?;
+        Self::decode(bytes)
+    }
+}
@@ -1,16 +1,35 @@
+mod apu;
+mod audio;
 mod cartridge;
 mod config;
 mod context;
 mod cpu;
 mod events;
+mod hardware;
+mod image;
+mod infrared;
 mod input;
+mod link;
+mod ppu;
 mod system;
+#[cfg(test)]
+mod tests;
 mod time;
 
-pub use cartridge::{Cartridge, Rom};
+pub use audio::ring::RingBufferSink;
+// Only read by the audio output of the app
+#[cfg(feature = "audio")]
+pub use audio::{Sample, ring::RingBufferReader};
+pub use cartridge::{CameraImage, Cartridge, Rom, save_photos};
 pub use config::Config;
-pub use input::Input;
+pub use input::{Input, Tilt};
+pub use link::{
+    pair::LinkedPair,
+    printer::Printer,
+    tcp::{LinkStatus, PendingLink, SyncMode, TcpLink},
+};
 pub use system::System;
+pub use time::SystemTime;
 
 pub const WINDOW_WIDTH: u8 = 160;
 pub const WINDOW_HEIGHT: u8 = 144;
@@ -0,0 +1,349 @@
+use std::collections::VecDeque;
+
+use modular_bitfield::prelude::*;
+
+use crate::game_boy::{WINDOW_WIDTH, hardware::HardwareMode};
+
+use super::{DMG_SHADES, Pixel, Ppu, VRAM_BANK_SIZE, cgb_color, object::Object};
+
+/// Dots it takes the fetchers to read a tile: tile number, low byte and high byte
+const FETCH_DOTS: u8 = 6;
+/// The background fetcher is considered ready for an object fetch from
+/// the second half of its high byte read
+const OBJECT_FETCH_READY_DOTS: u8 = 5;
+
+/// CGB tile map attributes, stored in VRAM bank 1
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+struct BgAttributes {
+    palette: B3,
+    bank: B1,
+    #[skip]
+    __: B1,
+    x_flip: bool,
+    y_flip: bool,
+    /// When set, colors 1-3 of this tile are drawn over objects
+    priority: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BgPixel {
+    color: u8,
+    palette: u8,
+    priority: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ObjPixel {
+    color: u8,
+    /// OBP0/OBP1 on DMG, OBJ palette 0-7 on CGB
+    palette: u8,
+    bg_priority: bool,
+    /// OAM index of the object, used for CGB priority
+    index: u8,
+}
+
+#[derive(Debug, Default)]
+struct BgFetcher {
+    /// Dots spent on the current tile
+    dots: u8,
+    /// Tile column relative to the start of the background or window
+    tile_x: u8,
+    tile_id: u8,
+    attrs: BgAttributes,
+    data_lo: u8,
+    data_hi: u8,
+    /// The first tile of every line is fetched twice
+    dummy_fetch: bool,
+}
+
+/// State of the mode 3 pixel pipeline
+#[derive(Debug)]
+pub struct PixelPipeline {
+    bg_fifo: VecDeque<BgPixel>,
+    obj_fifo: VecDeque<ObjPixel>,
+    fetcher: BgFetcher,
+    /// Pixels to throw away before the first visible one
+    discard: u8,
+    /// Next pixel of the line to be sent to the LCD
+    x: u8,
+    /// Next object (sorted by x) that hasn't been fetched
+    next_object: usize,
+    /// Dots left on the current object fetch
+    object_fetch: Option<u8>,
+    in_window: bool,
+}
+
+impl Default for PixelPipeline {
+    fn default() -> Self {
+        Self {
+            bg_fifo: VecDeque::with_capacity(8),
+            obj_fifo: VecDeque::with_capacity(8),
+            fetcher: Default::default(),
+            discard: 0,
+            x: 0,
+            next_object: 0,
+            object_fetch: None,
+            in_window: false,
+        }
+    }
+}
+
+impl PixelPipeline {
+    /// Whether the window was drawn on this line
+    pub fn in_window(&self) -> bool {
+        self.in_window
+    }
+    fn reset_fetcher(&mut self) {
+        self.fetcher = BgFetcher::default();
+        self.bg_fifo.clear();
+    }
+}
+
+impl Ppu {
+    pub(super) fn start_drawing(&mut self) {
+        // Objects are fetched left to right, ties are broken by OAM order
+        self.line_objects.sort_by_key(|obj| obj.x);
+        self.pipeline.reset_fetcher();
+        self.pipeline.obj_fifo.clear();
+        self.pipeline.fetcher.dummy_fetch = true;
+        self.pipeline.discard = self.scx % 8;
+        self.pipeline.x = 0;
+        self.pipeline.next_object = 0;
+        self.pipeline.object_fetch = None;
+        self.pipeline.in_window = false;
+    }
+    /// Advance mode 3 by one dot, returns true once the whole line was drawn
+    pub(super) fn draw_dot(&mut self) -> bool {
+        if self.step_objects() {
+            return false;
+        }
+        self.check_window_start();
+        self.step_bg_fetcher();
+        self.shift_pixel();
+        self.pipeline.x == WINDOW_WIDTH
+    }
+    /// Handles object fetching, which stalls the pixel output
+    /// Returns true if the dot was spent on objects
+    fn step_objects(&mut self) -> bool {
+        if let Some(dots) = self.pipeline.object_fetch.as_mut() {
+            *dots -= 1;
+            if *dots == 0 {
+                self.pipeline.object_fetch = None;
+                let object = self.line_objects[self.pipeline.next_object];
+                self.pipeline.next_object += 1;
+                self.merge_object(object);
+            }
+            return true;
+        }
+        if !self.lcdc.obj_enable() {
+            return false;
+        }
+        let Some(object) = self.line_objects.get(self.pipeline.next_object) else {
+            return false;
+        };
+        if object.x > self.pipeline.x + 8 {
+            return false;
+        }
+        // The background fetcher has to finish its current tile first
+        let pipeline = &self.pipeline;
+        if pipeline.bg_fifo.is_empty() || pipeline.fetcher.dots < OBJECT_FETCH_READY_DOTS {
+            self.step_bg_fetcher();
+            return true;
+        }
+        self.pipeline.object_fetch = Some(FETCH_DOTS - 1);
+        true
+    }
+    fn check_window_start(&mut self) {
+        if self.pipeline.in_window
+            || !self.lcdc.window_enable()
+            || !self.window_y_triggered
+            || self.pipeline.x + 7 < self.wx
+        {
+            return;
+        }
+        self.pipeline.in_window = true;
+        self.pipeline.reset_fetcher();
+        // With WX < 7 the window starts partially offscreen
+        self.pipeline.discard = 7u8.saturating_sub(self.wx);
+    }
+    fn step_bg_fetcher(&mut self) {
+        let fetcher = &mut self.pipeline.fetcher;
+        if fetcher.dots == FETCH_DOTS && self.pipeline.bg_fifo.is_empty() {
+            if fetcher.dummy_fetch {
+                fetcher.dummy_fetch = false;
+            } else {
+                let (lo, hi, attrs) = (fetcher.data_lo, fetcher.data_hi, fetcher.attrs);
+                for i in 0..8 {
+                    let bit = if attrs.x_flip() { i } else { 7 - i };
+                    self.pipeline.bg_fifo.push_back(BgPixel {
+                        color: tile_color(lo, hi, bit),
+                        palette: attrs.palette(),
+                        priority: attrs.priority(),
+                    });
+                }
+                fetcher.tile_x = fetcher.tile_x.wrapping_add(1);
+            }
+            fetcher.dots = 0;
+        }
+        if fetcher.dots == FETCH_DOTS {
+            return;
+        }
+        fetcher.dots += 1;
+        match fetcher.dots {
+            2 => self.fetch_bg_tile(),
+            4 => self.pipeline.fetcher.data_lo = self.fetch_bg_tile_data(0),
+            6 => self.pipeline.fetcher.data_hi = self.fetch_bg_tile_data(1),
+            _ => {}
+        }
+    }
+    fn fetch_bg_tile(&mut self) {
+        let tile_x = self.pipeline.fetcher.tile_x;
+        let (map, column, row) = if self.pipeline.in_window {
+            (self.lcdc.window_tile_map(), tile_x, self.window_line / 8)
+        } else {
+            (
+                self.lcdc.bg_tile_map(),
+                (self.scx / 8).wrapping_add(tile_x),
+                self.ly.wrapping_add(self.scy) / 8,
+            )
+        };
+        let map_start = if map { 0x1C00 } else { 0x1800 };
+        let index = map_start + (row as usize % 32) * 32 + (column as usize % 32);
+        self.pipeline.fetcher.tile_id = self.vram[index];
+        self.pipeline.fetcher.attrs = if self.hardware.cgb_features() {
+            self.vram[VRAM_BANK_SIZE + index].into()
+        } else {
+            BgAttributes::new()
+        };
+    }
+    fn fetch_bg_tile_data(&self, byte: usize) -> u8 {
+        let tile_id = self.pipeline.fetcher.tile_id;
+        let attrs = self.pipeline.fetcher.attrs;
+        let fine_y = if self.pipeline.in_window {
+            self.window_line % 8
+        } else {
+            self.ly.wrapping_add(self.scy) % 8
+        };
+        let fine_y = if attrs.y_flip() { 7 - fine_y } else { fine_y };
+        let bank = attrs.bank() as usize * VRAM_BANK_SIZE;
+        self.vram[bank + self.bg_tile_address(tile_id) + fine_y as usize * 2 + byte]
+    }
+    fn bg_tile_address(&self, tile_id: u8) -> usize {
+        if self.lcdc.bg_window_tiles() {
+            tile_id as usize * 16
+        } else {
+            (0x1000 + (tile_id as i8 as isize) * 16) as usize
+        }
+    }
+    /// Fetch an object's row and merge it into the object FIFO
+    fn merge_object(&mut self, object: Object) {
+        let height = self.object_height();
+        let row = object.row(self.ly, height);
+        let tile = if height == 16 {
+            (object.tile & 0xFE) + row / 8
+        } else {
+            object.tile
+        };
+        let bank = if self.hardware.cgb_features() {
+            object.attrs.bank() as usize * VRAM_BANK_SIZE
+        } else {
+            0
+        };
+        let address = bank + tile as usize * 16 + (row % 8) as usize * 2;
+        let (lo, hi) = (self.vram[address], self.vram[address + 1]);
+        let palette = if self.hardware.cgb_features() {
+            object.attrs.cgb_palette()
+        } else {
+            object.attrs.dmg_palette()
+        };
+        for i in 0..8u8 {
+            let screen_x = object.x as i16 - 8 + i as i16;
+            let offset = screen_x - self.pipeline.x as i16;
+            if offset < 0 {
+                continue;
+            }
+            let bit = if object.attrs.x_flip() { i } else { 7 - i };
+            let pixel = ObjPixel {
+                color: tile_color(lo, hi, bit),
+                palette,
+                bg_priority: object.attrs.bg_priority(),
+                index: object.index,
+            };
+            let fifo = &mut self.pipeline.obj_fifo;
+            while fifo.len() <= offset as usize {
+                fifo.push_back(ObjPixel::default());
+            }
+            // Objects fetched earlier (lower X) have priority over later ones,
+            // unless objects are prioritized by OAM index
+            let current = fifo[offset as usize];
+            if current.color == 0
+                || (pixel.color != 0 && !self.dmg_object_priority && pixel.index < current.index)
+            {
+                fifo[offset as usize] = pixel;
+            }
+        }
+    }
+    fn shift_pixel(&mut self) {
+        let Some(bg) = self.pipeline.bg_fifo.pop_front() else {
+            return;
+        };
+        if self.pipeline.discard > 0 {
+            self.pipeline.discard -= 1;
+            return;
+        }
+        let obj = self.pipeline.obj_fifo.pop_front().unwrap_or_default();
+        let x = self.pipeline.x as usize;
+        self.frame[self.ly as usize * WINDOW_WIDTH as usize + x] = self.mix_pixel(bg, obj);
+        self.pipeline.x += 1;
+    }
+    fn mix_pixel(&self, bg: BgPixel, obj: ObjPixel) -> Pixel {
+        if self.hardware.cgb_features() {
+            // In CGB mode LCDC bit 0 removes the background's priority
+            let obj_visible = obj.color != 0
+                && (!self.lcdc.bg_window_enable()
+                    || bg.color == 0
+                    || (!obj.bg_priority && !bg.priority));
+            return if obj_visible {
+                cgb_color(&self.obj_palettes, obj.palette, obj.color)
+            } else {
+                cgb_color(&self.bg_palettes, bg.palette, bg.color)
+            };
+        }
+        // On DMG, LCDC bit 0 blanks both the background and the window
+        let bg_color = if self.lcdc.bg_window_enable() {
+            bg.color
+        } else {
+            0
+        };
+        let (palettes, palette, shade) = if obj.color != 0 && (!obj.bg_priority || bg_color == 0) {
+            let register = if obj.palette == 0 {
+                self.obp0
+            } else {
+                self.obp1
+            };
+            (
+                &self.obj_palettes,
+                obj.palette,
+                palette_shade(register, obj.color),
+            )
+        } else {
+            (&self.bg_palettes, 0, palette_shade(self.bgp, bg_color))
+        };
+        match self.hardware {
+            // The DMG palettes index into the CGB palettes set up by the boot ROM
+            HardwareMode::DmgCompatibility => cgb_color(palettes, palette, shade),
+            HardwareMode::Dmg | HardwareMode::Cgb => DMG_SHADES[shade as usize],
+        }
+    }
+}
+
+fn tile_color(lo: u8, hi: u8, bit: u8) -> u8 {
+    (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1)
+}
+
+/// Map a color index through a DMG palette register
+fn palette_shade(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0b11
+}
@@ -0,0 +1,482 @@
+mod fetcher;
+mod object;
+#[cfg(test)]
+mod tests;
+
+use fetcher::PixelPipeline;
+use modular_bitfield::prelude::*;
+use object::Object;
+
+use super::{
+    WINDOW_HEIGHT, WINDOW_WIDTH, context::interrupts::InterruptFlags, hardware::HardwareMode,
+};
+
+pub const VRAM_BANK_SIZE: usize = 0x2000;
+pub const OAM_SIZE: usize = 0xA0;
+/// 8 palettes of 4 RGB555 colors
+const PALETTE_RAM_SIZE: usize = 64;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+const VBLANK_LINE: u8 = WINDOW_HEIGHT;
+const LINES_PER_FRAME: u8 = 154;
+const OBJECT_COUNT: u8 = 40;
+const MAX_OBJECTS_PER_LINE: usize = 10;
+
+/// RGBA color
+pub type Pixel = [u8; 4];
+/// Row-major LCD output
+pub type FrameBuffer = [Pixel; WINDOW_WIDTH as usize * WINDOW_HEIGHT as usize];
+
+const DMG_SHADES: [Pixel; 4] = [
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+    [0x00, 0x00, 0x00, 0xFF],
+];
+/// RGB555 palette the boot ROM sets up for DMG cartridges
+const COMPATIBILITY_PALETTE: [u16; 4] = [0x7FFF, 0x56B5, 0x294A, 0x0000];
+
+/// Convert an entry of CGB palette RAM to RGBA
+fn cgb_color(palettes: &[u8; PALETTE_RAM_SIZE], palette: u8, color: u8) -> Pixel {
+    let index = (palette as usize * 4 + color as usize) * 2;
+    let rgb555 = u16::from_le_bytes([palettes[index], palettes[index + 1]]);
+    let channel = |shift: u16| {
+        let c = ((rgb555 >> shift) & 0x1F) as u8;
+        (c << 3) | (c >> 2)
+    };
+    [channel(0), channel(5), channel(10), 0xFF]
+}
+
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lcdc {
+    pub bg_window_enable: bool,
+    pub obj_enable: bool,
+    /// 8x16 objects when set
+    pub obj_size: bool,
+    /// Background uses the 9C00 tile map when set
+    pub bg_tile_map: bool,
+    /// Background and window use unsigned 8000 tile addressing when set
+    pub bg_window_tiles: bool,
+    pub window_enable: bool,
+    /// Window uses the 9C00 tile map when set
+    pub window_tile_map: bool,
+    pub lcd_enable: bool,
+}
+
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stat {
+    pub mode: B2,
+    pub lyc_eq_ly: bool,
+    pub mode0_select: bool,
+    pub mode1_select: bool,
+    pub mode2_select: bool,
+    pub lyc_select: bool,
+    #[skip]
+    __: B1,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// Picture Processing Unit
+/// Runs one dot at a time, drawing pixels through a background/window and object FIFO
+#[derive(Debug)]
+pub struct Ppu {
+    hardware: HardwareMode,
+    /// Two banks on CGB, only the first one is used on DMG
+    vram: Box<[u8; VRAM_BANK_SIZE * 2]>,
+    oam: Box<[u8; OAM_SIZE]>,
+    frame: Box<FrameBuffer>,
+    lcdc: Lcdc,
+    stat: Stat,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    mode: Mode,
+    /// Dot within the current line
+    dot: u16,
+    /// Objects selected during OAM scan for the current line
+    line_objects: Vec<Object>,
+    /// WY matched LY at some point during this frame
+    window_y_triggered: bool,
+    /// Internal line counter, only incremented on lines where the window was drawn
+    window_line: u8,
+    /// The first line after enabling the LCD skips OAM scan
+    first_line: bool,
+    /// All STAT interrupt sources are ORed into a single line,
+    /// the interrupt is only requested on its rising edge
+    stat_line: bool,
+    pipeline: PixelPipeline,
+    /// Interrupts requested since the last tick
+    requested: InterruptFlags,
+    /// VBK, bank used for CPU VRAM accesses
+    vram_bank: u8,
+    bg_palettes: [u8; PALETTE_RAM_SIZE],
+    obj_palettes: [u8; PALETTE_RAM_SIZE],
+    /// BCPS, palette RAM address and auto-increment
+    bcps: u8,
+    /// OCPS, palette RAM address and auto-increment
+    ocps: u8,
+    /// OPRI, objects are prioritized by X coordinate like on DMG instead of OAM index
+    dmg_object_priority: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new(HardwareMode::Dmg)
+    }
+}
+
+impl Ppu {
+    pub fn new(hardware: HardwareMode) -> Self {
+        let mut ppu = Self {
+            hardware,
+            vram: Box::new([0; VRAM_BANK_SIZE * 2]),
+            oam: Box::new([0; OAM_SIZE]),
+            frame: Box::new([DMG_SHADES[0]; WINDOW_WIDTH as usize * WINDOW_HEIGHT as usize]),
+            // Values left by the boot ROM
+            lcdc: 0x91.into(),
+            stat: Stat::new(),
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0xFC,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::OamScan,
+            dot: 0,
+            line_objects: Vec::with_capacity(MAX_OBJECTS_PER_LINE),
+            window_y_triggered: false,
+            window_line: 0,
+            first_line: false,
+            stat_line: false,
+            pipeline: Default::default(),
+            requested: InterruptFlags::new(),
+            vram_bank: 0,
+            // The boot ROM leaves the palettes white
+            bg_palettes: [0xFF; PALETTE_RAM_SIZE],
+            obj_palettes: [0xFF; PALETTE_RAM_SIZE],
+            bcps: 0,
+            ocps: 0,
+            dmg_object_priority: !hardware.cgb_features(),
+        };
+        ppu.set_hardware(hardware);
+        ppu
+    }
+    /// Switch mode when the boot ROM writes KEY0
+    pub fn set_hardware(&mut self, hardware: HardwareMode) {
+        self.hardware = hardware;
+        self.dmg_object_priority = !hardware.cgb_features();
+        if hardware == HardwareMode::DmgCompatibility {
+            for (i, color) in COMPATIBILITY_PALETTE.iter().enumerate() {
+                let [lo, hi] = color.to_le_bytes();
+                self.bg_palettes[i * 2..i * 2 + 2].copy_from_slice(&[lo, hi]);
+                // OBJ palettes 0 and 1 back OBP0 and OBP1
+                self.obj_palettes[i * 2..i * 2 + 2].copy_from_slice(&[lo, hi]);
+                self.obj_palettes[8 + i * 2..8 + i * 2 + 2].copy_from_slice(&[lo, hi]);
+            }
+        }
+    }
+    /// Advance the PPU by one dot, returns the interrupts that were requested
+    pub fn tick(&mut self) -> InterruptFlags {
+        if self.lcdc.lcd_enable() {
+            self.step();
+            self.update_stat_line();
+        }
+        std::mem::take(&mut self.requested)
+    }
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.frame
+    }
+    fn step(&mut self) {
+        match self.mode {
+            Mode::OamScan => {
+                // One object is checked every 2 dots
+                if self.dot % 2 == 1 {
+                    self.scan_object((self.dot / 2) as u8);
+                }
+            }
+            Mode::Drawing => {
+                if self.draw_dot() {
+                    self.mode = Mode::HBlank;
+                }
+            }
+            Mode::HBlank | Mode::VBlank => {}
+        }
+        self.dot += 1;
+        if (self.mode == Mode::OamScan || self.first_line) && self.dot == OAM_SCAN_DOTS {
+            self.first_line = false;
+            self.mode = Mode::Drawing;
+            self.start_drawing();
+        }
+        if self.dot == DOTS_PER_LINE {
+            self.dot = 0;
+            self.next_line();
+        }
+    }
+    fn next_line(&mut self) {
+        if self.pipeline.in_window() {
+            self.window_line = self.window_line.wrapping_add(1);
+        }
+        self.ly += 1;
+        if self.ly == LINES_PER_FRAME {
+            self.ly = 0;
+            self.window_line = 0;
+            self.window_y_triggered = false;
+        }
+        if self.ly == VBLANK_LINE {
+            self.mode = Mode::VBlank;
+            self.requested.set_vblank(true);
+        } else if self.ly < VBLANK_LINE {
+            self.start_line();
+        }
+    }
+    fn start_line(&mut self) {
+        self.line_objects.clear();
+        if self.ly == self.wy {
+            self.window_y_triggered = true;
+        }
+        self.mode = Mode::OamScan;
+    }
+    fn scan_object(&mut self, index: u8) {
+        if index >= OBJECT_COUNT || self.line_objects.len() == MAX_OBJECTS_PER_LINE {
+            return;
+        }
+        let object = Object::from_oam(&self.oam[..], index);
+        if object.on_line(self.ly, self.object_height()) {
+            self.line_objects.push(object);
+        }
+    }
+    fn object_height(&self) -> u8 {
+        if self.lcdc.obj_size() { 16 } else { 8 }
+    }
+    /// Value of the LY register
+    /// On line 153 LY already reads 0 for most of the line
+    fn ly_register(&self) -> u8 {
+        if self.ly == LINES_PER_FRAME - 1 && self.dot >= 8 {
+            0
+        } else {
+            self.ly
+        }
+    }
+    /// Value the LYC comparator sees, which is briefly unavailable after LY changes
+    fn ly_compare(&self) -> Option<u8> {
+        if !self.lcdc.lcd_enable() {
+            return Some(self.ly);
+        }
+        match (self.ly, self.dot) {
+            (153, 0..4) => None,
+            (153, 4..8) => Some(153),
+            (153, 8..12) => None,
+            (153, _) => Some(0),
+            (0, _) => Some(0),
+            (_, 0..4) => None,
+            (ly, _) => Some(ly),
+        }
+    }
+    fn lyc_eq_ly(&self) -> bool {
+        self.ly_compare() == Some(self.lyc)
+    }
+    fn stat_sources(&self, stat: Stat) -> bool {
+        if !self.lcdc.lcd_enable() {
+            return false;
+        }
+        // Entering VBlank also triggers the mode 2 source
+        let vblank_start = self.ly == VBLANK_LINE && self.dot == 0;
+        (stat.lyc_select() && self.lyc_eq_ly())
+            || (stat.mode0_select() && self.mode == Mode::HBlank)
+            || (stat.mode1_select() && self.mode == Mode::VBlank)
+            || (stat.mode2_select() && (self.mode == Mode::OamScan || vblank_start))
+    }
+    fn update_stat_line(&mut self) {
+        let line = self.stat_sources(self.stat);
+        if line && !self.stat_line {
+            self.requested.set_lcd(true);
+        }
+        self.stat_line = line;
+    }
+    fn write_stat(&mut self, data: u8) {
+        // On DMG, writing STAT behaves as if every source was selected for a cycle
+        if !self.hardware.is_cgb_hardware() && self.stat_sources(0xFF.into()) && !self.stat_line {
+            self.requested.set_lcd(true);
+            self.stat_line = true;
+        }
+        // Mode and LY=LYC bits are read only
+        self.stat = (data & 0x78).into();
+        self.update_stat_line();
+    }
+    fn set_lcdc(&mut self, data: u8) {
+        let was_enabled = self.lcdc.lcd_enable();
+        self.lcdc = data.into();
+        if was_enabled && !self.lcdc.lcd_enable() {
+            // Turning the LCD off resets the PPU and blanks the screen
+            self.ly = 0;
+            self.dot = 0;
+            self.mode = Mode::HBlank;
+            self.stat_line = false;
+            self.frame.fill(DMG_SHADES[0]);
+        } else if !was_enabled && self.lcdc.lcd_enable() {
+            self.window_line = 0;
+            self.window_y_triggered = false;
+            self.start_line();
+            // The first line reports mode 0 instead of OAM scan
+            self.mode = Mode::HBlank;
+            self.first_line = true;
+            self.update_stat_line();
+        }
+    }
+    /// The PPU is frozen in STOP mode and the LCD stops being driven,
+    /// so the screen goes blank until the CPU wakes up
+    pub fn stop(&mut self) {
+        if self.lcdc.lcd_enable() {
+            self.frame.fill(DMG_SHADES[0]);
+        }
+    }
+    /// VRAM is locked while the PPU is drawing
+    pub fn vram_accessible(&self) -> bool {
+        self.mode != Mode::Drawing
+    }
+    /// OAM is locked while the PPU is scanning or drawing
+    pub fn oam_accessible(&self) -> bool {
+        !matches!(self.mode, Mode::OamScan | Mode::Drawing)
+    }
+    fn vram_index(&self, addr: u16) -> usize {
+        self.vram_bank as usize * VRAM_BANK_SIZE + (addr & 0x1FFF) as usize
+    }
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        if !self.vram_accessible() {
+            return 0xFF;
+        }
+        self.vram[self.vram_index(addr)]
+    }
+    pub fn write_vram(&mut self, addr: u16, data: u8) {
+        if self.vram_accessible() {
+            self.vram[self.vram_index(addr)] = data;
+        }
+    }
+    pub fn read_oam(&self, addr: u16) -> u8 {
+        if !self.oam_accessible() {
+            return 0xFF;
+        }
+        self.oam[(addr - 0xFE00) as usize]
+    }
+    pub fn write_oam(&mut self, addr: u16, data: u8) {
+        if self.oam_accessible() {
+            self.oam[(addr - 0xFE00) as usize] = data;
+        }
+    }
+    /// OAM DMA writes bypass the PPU's OAM lock
+    pub fn write_oam_dma(&mut self, index: u8, data: u8) {
+        self.oam[index as usize] = data;
+    }
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF40 => self.lcdc.into(),
+            0xFF41 => {
+                let mut stat = self.stat;
+                // The mode reads as 0 while the LCD is off
+                let mode = if self.lcdc.lcd_enable() {
+                    self.mode
+                } else {
+                    Mode::HBlank
+                };
+                stat.set_mode(mode as u8);
+                stat.set_lyc_eq_ly(self.lyc_eq_ly());
+                0x80 | u8::from(stat)
+            }
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly_register(),
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            0xFF4F => 0xFE | self.vram_bank,
+            0xFF68 => 0x40 | self.bcps,
+            0xFF69 => self.read_palette_data(&self.bg_palettes, self.bcps),
+            0xFF6A => 0x40 | self.ocps,
+            0xFF6B => self.read_palette_data(&self.obj_palettes, self.ocps),
+            0xFF6C => 0xFE | self.dmg_object_priority as u8,
+            _ => unreachable!("{addr:04x} is not a PPU register"),
+        }
+    }
+    fn read_palette_data(&self, palettes: &[u8; PALETTE_RAM_SIZE], spec: u8) -> u8 {
+        // Palette RAM is locked while the PPU is drawing
+        if self.mode == Mode::Drawing && self.lcdc.lcd_enable() {
+            return 0xFF;
+        }
+        palettes[(spec & 0x3F) as usize]
+    }
+    /// Write through BCPD/OCPD, returns the updated BCPS/OCPS
+    fn write_palette_data(
+        palettes: &mut [u8; PALETTE_RAM_SIZE],
+        spec: u8,
+        locked: bool,
+        data: u8,
+    ) -> u8 {
+        if !locked {
+            palettes[(spec & 0x3F) as usize] = data;
+        }
+        // The address increments even when the write is ignored
+        if spec & 0x80 != 0 {
+            0x80 | (spec.wrapping_add(1) & 0x3F)
+        } else {
+            spec
+        }
+    }
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0xFF40 => self.set_lcdc(data),
+            0xFF41 => self.write_stat(data),
+            0xFF42 => self.scy = data,
+            0xFF43 => self.scx = data,
+            0xFF44 => {}
+            0xFF45 => {
+                self.lyc = data;
+                self.update_stat_line();
+            }
+            0xFF47 => self.bgp = data,
+            0xFF48 => self.obp0 = data,
+            0xFF49 => self.obp1 = data,
+            0xFF4A => self.wy = data,
+            0xFF4B => self.wx = data,
+            0xFF4F => self.vram_bank = data & 0x01,
+            0xFF68 => self.bcps = data & 0xBF,
+            0xFF69 => {
+                let locked = self.mode == Mode::Drawing && self.lcdc.lcd_enable();
+                self.bcps =
+                    Self::write_palette_data(&mut self.bg_palettes, self.bcps, locked, data);
+            }
+            0xFF6A => self.ocps = data & 0xBF,
+            0xFF6B => {
+                let locked = self.mode == Mode::Drawing && self.lcdc.lcd_enable();
+                self.ocps =
+                    Self::write_palette_data(&mut self.obj_palettes, self.ocps, locked, data);
+            }
+            0xFF6C => self.dmg_object_priority = data & 0x01 != 0,
+            _ => unreachable!("{addr:04x} is not a PPU register"),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use modular_bitfield::prelude::*;
+
+#[bitfield(bits = 8)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObjectAttributes {
+    pub cgb_palette: B3,
+    pub bank: B1,
+    pub dmg_palette: B1,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    /// When set, BG and window colors 1-3 are drawn over this object
+    pub bg_priority: bool,
+}
+
+/// An entry of OAM
+#[derive(Debug, Clone, Copy)]
+pub struct Object {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attrs: ObjectAttributes,
+    /// Position in OAM, used for priority
+    pub index: u8,
+}
+
+impl Object {
+    pub fn from_oam(oam: &[u8], index: u8) -> Self {
+        let start = index as usize * 4;
+        Self {
+            y: oam[start],
+            x: oam[start + 1],
+            tile: oam[start + 2],
+            attrs: oam[start + 3].into(),
+            index,
+        }
+    }
+    /// Whether the object covers the line `ly`
+    pub fn on_line(&self, ly: u8, height: u8) -> bool {
+        let top = self.y as i16 - 16;
+        let line = ly as i16;
+        top <= line && line < top + height as i16
+    }
+    /// Row of the object's tiles that's drawn on line `ly`, accounting for vertical flip
+    pub fn row(&self, ly: u8, height: u8) -> u8 {
+        let row = (ly as i16 - (self.y as i16 - 16)) as u8;
+        if self.attrs.y_flip() {
+            height - 1 - row
+        } else {
+            row
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::{DOTS_PER_LINE, LINES_PER_FRAME, Mode, Ppu};
+
+/// Count the dots the PPU spends in mode 3 on the next line
+fn next_mode3_length(ppu: &mut Ppu) -> u32 {
+    while ppu.mode != Mode::OamScan {
+        ppu.tick();
+    }
+    while ppu.mode != Mode::Drawing {
+        ppu.tick();
+    }
+    let mut dots = 0;
+    while ppu.mode == Mode::Drawing {
+        ppu.tick();
+        dots += 1;
+    }
+    dots
+}
+
+#[test]
+fn frame_timing() {
+    let mut ppu = Ppu::default();
+    let mut dots = 0;
+    while !ppu.tick().vblank() {
+        dots += 1;
+    }
+    assert_eq!(dots + 1, 144 * DOTS_PER_LINE as u32);
+    dots = 0;
+    while !ppu.tick().vblank() {
+        dots += 1;
+    }
+    assert_eq!(dots + 1, LINES_PER_FRAME as u32 * DOTS_PER_LINE as u32);
+}
+
+#[test]
+fn mode3_length_scx() {
+    let mut ppu = Ppu::default();
+    assert_eq!(next_mode3_length(&mut ppu), 172);
+    ppu.write(0xFF43, 0x03);
+    assert_eq!(next_mode3_length(&mut ppu), 175);
+}
+
+#[test]
+fn mode3_length_objects() {
+    let mut ppu = Ppu::default();
+    // Enable objects, put one at the left edge and one in the middle of a tile
+    ppu.write(0xFF40, 0x93);
+    ppu.oam[0..4].copy_from_slice(&[16, 8, 0, 0]);
+    assert_eq!(next_mode3_length(&mut ppu), 172 + 11);
+    ppu.oam[4..8].copy_from_slice(&[16, 8 + 21, 0, 0]);
+    assert_eq!(next_mode3_length(&mut ppu), 172 + 11 + 6);
+}
+
+#[test]
+fn mode3_length_window() {
+    let mut ppu = Ppu::default();
+    ppu.write(0xFF4A, 0x00);
+    ppu.write(0xFF4B, 0x57);
+    ppu.write(0xFF40, 0x00);
+    ppu.write(0xFF40, 0xB1);
+    assert_eq!(next_mode3_length(&mut ppu), 172 + 6);
+}
+
+/// Run the PPU until it reaches `ly` at dot 0
+fn run_to_line(ppu: &mut Ppu, ly: u8) {
+    while !(ppu.ly == ly && ppu.dot == 0) {
+        ppu.tick();
+    }
+}
+
+#[test]
+fn stat_irq_blocking() {
+    let mut ppu = Ppu::default();
+    ppu.write(0xFF45, 10);
+    // Select both the LY=LYC and mode 0 sources
+    ppu.write(0xFF41, 0x48);
+    run_to_line(&mut ppu, 10);
+    let mut interrupts = 0;
+    for _ in 0..DOTS_PER_LINE {
+        if ppu.tick().lcd() {
+            interrupts += 1;
+        }
+    }
+    assert_eq!(
+        interrupts, 1,
+        "mode 0 is blocked by LY=LYC on the same line"
+    );
+}
+
+#[test]
+fn ly_153_early_reset() {
+    let mut ppu = Ppu::default();
+    ppu.write(0xFF45, 0);
+    run_to_line(&mut ppu, 153);
+    assert_eq!(ppu.read(0xFF44), 153);
+    assert_eq!(ppu.read(0xFF41) & 0x04, 0);
+    for _ in 0..12 {
+        ppu.tick();
+    }
+    assert_eq!(ppu.read(0xFF44), 0, "LY reads 0 for most of line 153");
+    assert_eq!(
+        ppu.read(0xFF41) & 0x04,
+        0x04,
+        "LYC=0 matches during line 153"
+    );
+}
+
+#[test]
+fn stat_write_spurious_interrupt() {
+    let mut ppu = Ppu::default();
+    run_to_line(&mut ppu, 145);
+    ppu.write(0xFF41, 0x00);
+    assert!(
+        ppu.tick().lcd(),
+        "writing STAT during VBlank requests an interrupt"
+    );
+    ppu.write(0xFF41, 0x00);
+    ppu.tick();
+    run_to_line(&mut ppu, 10);
+    for _ in 0..100 {
+        ppu.tick();
+    }
+    ppu.write(0xFF41, 0x00);
+    assert!(
+        !ppu.tick().lcd(),
+        "but not during mode 3 without matching LYC"
+    );
+}
@@ -1,9 +1,15 @@
 use super::{
-    Cartridge, Input, Rom,
-    cartridge::{self, CartridgeParseError},
+    Cartridge, Config, Input, Rom, Tilt,
+    apu::Sample,
+    audio::AudioSink,
+    cartridge::{CameraImage, CartridgeParseError},
     context::Context,
-    cpu::Cpu,
+    cpu::{Cpu, registers::Registers},
     events::Events,
+    hardware::HardwareMode,
+    infrared::InfraredPeer,
+    link::{Disconnected, LinkPeer},
+    ppu::FrameBuffer,
     time::SystemTime,
 };
 
@@ -15,16 +21,51 @@ pub struct System {
 
 impl System {
     pub fn now(rom: Rom) -> Result<Self, CartridgeParseError> {
+        Self::with_config(rom, &Config::default())
+    }
+    pub fn with_config(rom: Rom, config: &Config) -> Result<Self, CartridgeParseError> {
         let cartridge = Cartridge::from_rom(rom)?;
+        let hardware = HardwareMode::select(config.model, &cartridge);
+        log::info!("hardware mode = {hardware:?}");
+        // The boot ROM runs in CGB mode on CGB hardware and selects the final mode through KEY0
+        let console = if hardware.is_cgb_hardware() {
+            HardwareMode::Cgb
+        } else {
+            HardwareMode::Dmg
+        };
+        let mut context = Context::new(cartridge, console);
+        context.finish_boot(hardware.key0());
         Ok(Self {
-            cpu: Default::default(),
-            context: Context::new(cartridge),
+            cpu: Cpu::post_boot(hardware),
+            context,
         })
     }
 
     pub fn step(&mut self) -> Events {
+        let was_locked = self.cpu.locked_address().is_some();
+        // Finishes the instruction step_cycle started, if any
         self.cpu.step(&mut self.context);
-        self.context.fetch_clear_events()
+        self.step_events(was_locked)
+    }
+    /// Advance by a single M-cycle, stopping in the middle of an instruction if needed
+    pub fn step_cycle(&mut self) -> Events {
+        let was_locked = self.cpu.locked_address().is_some();
+        self.cpu.step_cycle(&mut self.context);
+        self.step_events(was_locked)
+    }
+    fn step_events(&mut self, was_locked: bool) -> Events {
+        let mut events = self.context.fetch_clear_events();
+        if !was_locked && self.cpu.locked_address().is_some() {
+            events.set_cpu_locked(true);
+        }
+        events
+    }
+    /// Address of the illegal opcode that locked up the CPU, if any
+    pub fn locked_address(&self) -> Option<u16> {
+        self.cpu.locked_address()
+    }
+    pub fn registers(&self) -> &Registers {
+        self.cpu.registers()
     }
     pub fn time(&self) -> SystemTime {
         self.context.system_time()
@@ -33,15 +74,91 @@ impl System {
         let target_time = self.time() + delta;
         let start_time = self.time();
         let mut events = Events::new();
-        while self.time() < target_time && !events.is_empty() {
-            events = self.step();
+        while self.time() < target_time && events.is_empty() {
+            if !self.context.link_ready() {
+                events.set_link_stalled(true);
+                break;
+            }
+            // The link cable may wait for the other side on the exact M-cycle a transfer ends
+            events = if self.context.link_clocking() {
+                self.step_cycle()
+            } else {
+                self.step()
+            };
         }
         let elapsed_time = self.time() - start_time;
         (events, elapsed_time)
     }
+    /// Whether the cartridge's rumble motor is on, see [`Events::rumble_changed`]
+    pub fn rumble(&self) -> bool {
+        self.context.cartridge().rumble()
+    }
+    /// Whether the cartridge's speaker is playing its alarm tone
+    pub fn cartridge_tone(&self) -> bool {
+        self.context.cartridge().tone()
+    }
+    /// Point the cartridge's IR port at `peer`, returns the previously connected peer
+    /// Cartridges without an IR port hand `peer` back
+    pub fn connect_cartridge_infrared(
+        &mut self,
+        peer: impl InfraredPeer + 'static,
+    ) -> Box<dyn InfraredPeer> {
+        self.context
+            .cartridge_mut()
+            .connect_infrared(Box::new(peer))
+    }
+    /// Whether the cartridge keeps its memory with a battery, see [`System::save_data`]
+    pub fn has_battery(&self) -> bool {
+        self.context.cartridge().has_battery()
+    }
+    /// Battery-backed cartridge memory to persist, None if the cartridge has no battery
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        self.context.cartridge().save_data()
+    }
+    /// Restore battery-backed cartridge memory written by [`System::save_data`]
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.context.cartridge_mut().load_save_data(data);
+    }
+    /// Plug `peer` into the link port, returns the previously connected peer
+    pub fn connect_link(&mut self, peer: impl LinkPeer + 'static) -> Box<dyn LinkPeer> {
+        self.context.connect_link(Box::new(peer))
+    }
+    /// Unplug the link cable
+    pub fn disconnect_link(&mut self) -> Box<dyn LinkPeer> {
+        self.connect_link(Disconnected)
+    }
+    /// Read the cartridge's external RAM (A000-BFFF) like the CPU would,
+    /// for test ROMs reporting their results there
+    pub fn read_cartridge_ram(&self, addr: u16) -> u8 {
+        self.context.cartridge().read_ram(addr)
+    }
+    /// Set the rate of the stereo audio output, 0 disables it
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.context.set_sample_rate(sample_rate);
+    }
+    /// Audio samples produced since the last call
+    pub fn take_samples(&mut self) -> Vec<Sample> {
+        self.context.take_samples()
+    }
+    /// Send the audio samples produced since the last call to `sink`
+    pub fn drain_audio(&mut self, sink: &mut impl AudioSink) {
+        sink.push_samples(&self.context.take_samples());
+    }
+    /// The last frame drawn by the PPU
+    pub fn frame(&self) -> &FrameBuffer {
+        self.context.frame()
+    }
     pub fn set_input(&mut self, input: Input) {
         self.context.set_input(input);
     }
+    /// What the Game Boy Camera sees, a test pattern until set
+    pub fn set_camera_image(&mut self, image: CameraImage) {
+        self.context.cartridge_mut().set_camera_image(image);
+    }
+    /// Tilt the console, for cartridges with an accelerometer
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        self.context.set_tilt(tilt);
+    }
     pub fn press_key(&mut self, input: Input) {
         self.context.press_key(input);
     }
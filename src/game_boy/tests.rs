@@ -0,0 +1,124 @@
+//! Test ROM suites, run with `CVGB_TEST_ROMS=<dir> cargo test --release -- --ignored`
+//! The directory holds a build of the mooneye test suite in `mooneye/`
+//! and blargg's test ROMs in `blargg/`
+
+use std::path::{Path, PathBuf};
+
+use super::{System, time::SystemTime};
+
+/// Registers B, C, D, E, H, L of a passing mooneye test
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// Registers of a failing mooneye test
+const MOONEYE_FAIL: [u8; 6] = [0x42; 6];
+/// Blargg's tests write their status to A000 once A001-A003 hold this signature
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_RUNNING: u8 = 0x80;
+
+fn test_rom_directory() -> PathBuf {
+    match std::env::var_os("CVGB_TEST_ROMS") {
+        Some(directory) => PathBuf::from(directory),
+        None => panic!("set CVGB_TEST_ROMS to the directory holding the test ROMs"),
+    }
+}
+
+/// Every ROM in `directory` under the test ROM directory, sorted by name
+fn roms_in(directory: &str) -> Vec<PathBuf> {
+    let directory = test_rom_directory().join(directory);
+    let entries = std::fs::read_dir(&directory)
+        .unwrap_or_else(|err| panic!("could not list {}: {err}", directory.display()));
+    let mut roms: Vec<PathBuf> = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "gb"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty(), "no ROMs in {}", directory.display());
+    roms
+}
+
+fn load(path: &Path) -> System {
+    let rom = std::fs::read(path).unwrap().into_boxed_slice();
+    System::now(rom).unwrap()
+}
+
+/// Run `roms` with `run`, then fail with every ROM that didn't pass
+fn run_suite(roms: Vec<PathBuf>, run: impl Fn(System) -> Result<(), String>) {
+    let failures: Vec<String> = roms
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            run(load(path)).err().map(|err| format!("{name}: {err}"))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} failed:\n{}",
+        failures.len(),
+        roms.len(),
+        failures.join("\n")
+    );
+}
+
+/// Mooneye tests load the Fibonacci numbers into B-L when they pass
+fn run_mooneye(mut system: System) -> Result<(), String> {
+    let deadline = system.time() + SystemTime::from_seconds(10.0);
+    while system.time() < deadline {
+        system.step();
+        let regs = system.registers();
+        match [regs.b, regs.c, regs.d, regs.e, regs.h, regs.l] {
+            MOONEYE_PASS => return Ok(()),
+            MOONEYE_FAIL => return Err("failed".to_owned()),
+            _ => {}
+        }
+    }
+    Err("timed out".to_owned())
+}
+
+/// Blargg's tests report a result code and a message in cartridge RAM
+fn run_blargg(mut system: System) -> Result<(), String> {
+    let deadline = system.time() + SystemTime::from_seconds(60.0);
+    let status = |system: &System| {
+        let signature = [0xA001, 0xA002, 0xA003].map(|addr| system.read_cartridge_ram(addr));
+        (signature == BLARGG_SIGNATURE).then(|| system.read_cartridge_ram(0xA000))
+    };
+    while system.time() < deadline {
+        system.advance(SystemTime::from_seconds(0.1));
+        match status(&system) {
+            Some(BLARGG_RUNNING) | None => {}
+            Some(0x00) => return Ok(()),
+            Some(code) => {
+                let text: Vec<u8> = (0xA004..0xC000)
+                    .map(|addr| system.read_cartridge_ram(addr))
+                    .take_while(|&byte| byte != 0)
+                    .collect();
+                let text = String::from_utf8_lossy(&text);
+                return Err(format!("result {code}: {}", text.trim()));
+            }
+        }
+    }
+    Err("timed out".to_owned())
+}
+
+#[test]
+#[ignore = "needs CVGB_TEST_ROMS"]
+fn mooneye_timer() {
+    run_suite(roms_in("mooneye/acceptance/timer"), run_mooneye);
+}
+
+/// Includes stat_irq_blocking
+#[test]
+#[ignore = "needs CVGB_TEST_ROMS"]
+fn mooneye_ppu() {
+    run_suite(roms_in("mooneye/acceptance/ppu"), run_mooneye);
+}
+
+#[test]
+#[ignore = "needs CVGB_TEST_ROMS"]
+fn mooneye_mbc1() {
+    run_suite(roms_in("mooneye/emulator-only/mbc1"), run_mooneye);
+}
+
+#[test]
+#[ignore = "needs CVGB_TEST_ROMS"]
+fn blargg_dmg_sound() {
+    run_suite(roms_in("blargg/dmg_sound/rom_singles"), run_blargg);
+}
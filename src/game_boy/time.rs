@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Sub};
 
-const BASE_SYSTEM_CLOCK: u64 = 4_194_304;
+pub const BASE_SYSTEM_CLOCK: u64 = 4_194_304;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SystemTime {
@@ -32,6 +32,9 @@ impl SystemTime {
             base_master_clock_cycles: clocks.floor() as u64,
         }
     }
+    pub fn master_clocks(&self) -> u64 {
+        self.base_master_clock_cycles
+    }
     pub fn seconds(&self) -> f64 {
         self.base_master_clock_cycles as f64 / BASE_SYSTEM_CLOCK as f64
     }
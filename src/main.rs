@@ -1,16 +1,40 @@
 #![allow(dead_code)]
+// modular_bitfield expands field types with redundant parentheses
+#![allow(unused_parens)]
 
 mod app;
 mod game_boy;
 
-use app::CvgbApp;
+use app::{CvgbApp, Launch};
 use winit::{error::EventLoopError, event_loop::EventLoop};
 
 fn main() -> Result<(), EventLoopError> {
     env_logger::init();
 
+    let launch = match Launch::from_args(std::env::args().skip(1)) {
+        Ok(launch) => launch,
+        Err(usage) => {
+            eprintln!("{usage}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Launch::ExtractPhotos { save, directory } = &launch {
+        let saved = std::fs::read(save)
+            .map_err(|err| err.to_string())
+            .and_then(|ram| game_boy::save_photos(&ram, directory).map_err(|err| err.to_string()));
+        match saved {
+            Ok(paths) => println!("saved {} photos to {}", paths.len(), directory.display()),
+            Err(err) => {
+                eprintln!("could not extract photos from {}: {err}", save.display());
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     let event_loop = EventLoop::new().unwrap();
 
-    let mut app = CvgbApp::default();
+    let mut app = CvgbApp::new(&launch);
     event_loop.run_app(&mut app)
 }
@@ -0,0 +1,135 @@
+#!/usr/bin/env python3
+"""Write synthetic_capture.txt and the image it should print, synthetic_capture.png
+
+The capture follows the packet sequence games send to the Game Boy Printer: INIT, a status
+poll, DATA packets of two tile rows with a status poll after each, an empty DATA packet,
+PRINT, then status polls while the printer is busy. It is generated here, not recorded from
+a game. One packet per line as hex bytes, ending with the two bytes the printer answered
+"""
+
+import struct
+import zlib
+from pathlib import Path
+
+WIDTH, HEIGHT = 160, 144
+SHADES = [0xFF, 0xAA, 0x55, 0x00]
+# 1 unit of margin before and 3 after, identity palette, default exposure
+SHEETS, MARGINS, PALETTE, EXPOSURE = 0x01, 0x13, 0xE4, 0x40
+MARGIN_LINES = 8
+
+
+def color(x, y):
+    """Four vertical bars over a checkerboard of tiles"""
+    if y < HEIGHT // 2:
+        return x // 40
+    return 3 if (x // 8 + y // 8) % 2 == 0 else 0
+
+
+def tile_rows():
+    rows = []
+    for tile_y in range(HEIGHT // 8):
+        row = bytearray()
+        for tile_x in range(WIDTH // 8):
+            for line in range(8):
+                y = tile_y * 8 + line
+                low = high = 0
+                for bit in range(8):
+                    c = color(tile_x * 8 + bit, y)
+                    low |= (c & 1) << (7 - bit)
+                    high |= (c >> 1) << (7 - bit)
+                row += bytes([low, high])
+        rows.append(bytes(row))
+    return rows
+
+
+def compress(data):
+    """The printer's run-length encoding"""
+    out = bytearray()
+    literal = bytearray()
+
+    def flush():
+        while literal:
+            chunk = literal[:128]
+            out.append(len(chunk) - 1)
+            out.extend(chunk)
+            del literal[:128]
+
+    i = 0
+    while i < len(data):
+        run = 1
+        while i + run < len(data) and data[i + run] == data[i] and run < 129:
+            run += 1
+        if run >= 2:
+            flush()
+            out += bytes([0x80 | (run - 2), data[i]])
+            i += run
+        else:
+            literal.append(data[i])
+            i += 1
+    flush()
+    return bytes(out)
+
+
+def packet(command, data=b"", compressed=False, status=0x00):
+    body = bytes([command, int(compressed)]) + struct.pack("<H", len(data)) + data
+    checksum = sum(body) & 0xFFFF
+    return b"\x88\x33" + body + struct.pack("<H", checksum) + bytes([0x81, status])
+
+
+def capture():
+    packets = [("init", packet(0x01)), ("status", packet(0x0F))]
+    rows = tile_rows()
+    for index in range(0, len(rows), 2):
+        data = rows[index] + rows[index + 1]
+        # Like some games, compress part of the image
+        compressed = index // 2 == 4
+        if compressed:
+            data = compress(data)
+        packets.append((f"data {index // 2}", packet(0x04, data, compressed, status=0x08)))
+        packets.append(("status", packet(0x0F, status=0x08)))
+    packets.append(("end of data", packet(0x04, status=0x08)))
+    print_data = bytes([SHEETS, MARGINS, PALETTE, EXPOSURE])
+    # The printer answers PRINT already busy
+    packets.append(("print", packet(0x02, print_data, status=0x02)))
+    lines = (MARGINS >> 4) * MARGIN_LINES + HEIGHT + (MARGINS & 0x0F) * MARGIN_LINES
+    # The printer feeds 100 lines per second, polled 10 times per second
+    for poll in range(1, lines // 10 + 3):
+        busy = poll * 10 < lines
+        packets.append(("status", packet(0x0F, status=0x02 if busy else 0x00)))
+    return packets
+
+
+def png(width, pixels):
+    height = len(pixels) // width
+    raw = b"".join(b"\x00" + pixels[y * width:(y + 1) * width] for y in range(height))
+
+    def chunk(kind, data):
+        return (struct.pack(">I", len(data)) + kind + data
+                + struct.pack(">I", zlib.crc32(kind + data) & 0xFFFFFFFF))
+
+    header = struct.pack(">IIBBBBB", width, height, 8, 0, 0, 0, 0)
+    return (b"\x89PNG\r\n\x1a\n" + chunk(b"IHDR", header)
+            + chunk(b"IDAT", zlib.compress(raw, 9)) + chunk(b"IEND", b""))
+
+
+def expected_print():
+    blank = bytes([SHADES[0]]) * WIDTH
+    image = bytes(SHADES[(PALETTE >> (color(x, y) * 2)) & 3]
+                  for y in range(HEIGHT) for x in range(WIDTH))
+    before = blank * ((MARGINS >> 4) * MARGIN_LINES)
+    after = blank * ((MARGINS & 0x0F) * MARGIN_LINES)
+    return before + image * SHEETS + after
+
+
+def main():
+    directory = Path(__file__).parent
+    lines = ["# Synthetic capture written by synthetic_capture.py, not recorded from a game"]
+    for name, data in capture():
+        lines.append(f"# {name}")
+        lines.append(" ".join(f"{byte:02X}" for byte in data))
+    (directory / "synthetic_capture.txt").write_text("\n".join(lines) + "\n")
+    (directory / "synthetic_capture.png").write_bytes(png(WIDTH, expected_print()))
+
+
+if __name__ == "__main__":
+    main()